@@ -1,26 +1,44 @@
 pub mod light_rig;
 pub mod material;
 pub mod state;
+pub mod ui;
 use bevy_mod_picking::prelude::*;
 
 use bevy::{
+    core_pipeline::prepass::NormalPrepass,
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
     math::vec4,
     pbr::ExtendedMaterial,
     prelude::*,
 };
 use bevy_asset_loader::prelude::*;
-use bevy_cameras::pan_orbit_camera::{OrbitCameraController, OrbitCameraControllerPlugin};
+use bevy_cameras::{
+    framing::FitToBounds,
+    mode::CameraModesPlugin,
+    pan_orbit_camera::{OrbitCameraController, OrbitCameraControllerPlugin},
+    rig::{CameraRigPlugin, DefaultCameraRig},
+    CameraRig,
+};
 use bevy_mod_picking::{
     debug::DebugPickingPlugin, prelude::low_latency_window_plugin, DefaultPickingPlugins,
 };
-use bevy_protein::{protein_asset_loader::ProteinAsset, ProteinPlugin};
+use bevy_protein::{
+    protein_asset_loader::ProteinAsset,
+    protein_bundle::ProteinBundle,
+    representation::atom_position,
+    selection::{HOVERED_COLOR, SELECTED_COLOR},
+    ProteinPlugin,
+};
 use light_rig::LightRigPlugin;
 use material::{custom_material::CustomMaterial, extended_marerial::MyExtension};
 use pdbtbx::*;
-use state::camera::CameraModeImpl;
+use state::camera::{bookmark_views, switch_camera_mode, CameraModeImpl};
+use ui::{
+    contact_map::ContactMapPlugin, molecule_toggles::MoleculeTogglesPlugin,
+    ramachandran::RamachandranPlotPlugin, sequence::SequenceViewerPlugin,
+};
 
-use bevy_instanced::plugin::InstancedMaterialPlugin;
+use bevy_instanced::{instance_data::instanced::InstancesData, render::outline::InstanceOutline};
 
 #[derive(AssetCollection, Resource)]
 struct ProteinAssetsMap {
@@ -53,12 +71,18 @@ impl Plugin for AppPlugin {
                 .build()
                 .disable::<DebugPickingPlugin>(),
             OrbitCameraControllerPlugin::<CameraModeImpl>::default(),
+            CameraModesPlugin::<CameraModeImpl>::default(),
+            CameraRigPlugin,
             LogDiagnosticsPlugin::default(),
             FrameTimeDiagnosticsPlugin,
             MaterialPlugin::<CustomMaterial>::default(),
             MaterialPlugin::<ExtendedMaterial<StandardMaterial, MyExtension>>::default(),
             ProteinPlugin,
             LightRigPlugin,
+            RamachandranPlotPlugin,
+            ContactMapPlugin,
+            SequenceViewerPlugin,
+            MoleculeTogglesPlugin,
         ))
         .init_state::<AppState>()
         .add_loading_state(
@@ -68,18 +92,50 @@ impl Plugin for AppPlugin {
         )
         .add_systems(
             OnEnter(AppState::Main),
-            (Self::setup_camera, make_pickable).chain(),
+            (Self::setup_camera, Self::spawn_protein),
+        )
+        // Protein geometry is built once its asset is available, so keep picking up new meshes.
+        .add_systems(
+            Update,
+            (make_pickable, switch_camera_mode, bookmark_views).run_if(in_state(AppState::Main)),
         );
     }
 }
 
 impl AppPlugin {
-    fn setup_camera(mut commands: Commands) {
-        commands.spawn((
+    fn setup_camera(mut commands: Commands, mut rig: ResMut<DefaultCameraRig>) {
+        let camera = commands.spawn((
             Camera3dBundle { ..default() },
-            OrbitCameraController::new(100., Vec3::new(-2.6, -8.3, 9.0)),
+            // The instancing prepass marks the hovered and selected atoms in the normals for the outline.
+            NormalPrepass,
+            InstanceOutline {
+                hovered_color: HOVERED_COLOR,
+                selected_color: SELECTED_COLOR,
+                ..default()
+            },
+            // Placed by `spawn_protein`, once the protein is known.
+            OrbitCameraController::default(),
             MainCamera,
         ));
+        rig.add_camera(camera.id());
+    }
+
+    fn spawn_protein(
+        mut commands: Commands,
+        protein_assets_map: Res<ProteinAssetsMap>,
+        protein_assets: Res<Assets<ProteinAsset>>,
+        mut fits: EventWriter<FitToBounds>,
+    ) {
+        commands.spawn((
+            ProteinBundle::new(protein_assets_map.primary_protein.clone()),
+            Name::new("primary protein"),
+        ));
+
+        // The protein is spawned at the origin, so its atoms are where the file puts them.
+        if let Some(protein_asset) = protein_assets.get(&protein_assets_map.primary_protein) {
+            let atoms = protein_asset.pdb.atoms().map(atom_position).collect();
+            fits.send(FitToBounds::points(atoms, 0.));
+        }
     }
 }
 
@@ -90,29 +146,17 @@ fn cleanup<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Comma
     }
 }
 
+/// Meshes not yet pickable. Instanced meshes are left out: their instances are hovered through `bevy_protein`'s own
+/// ray casts and highlighted by the instancing shaders.
+type UnpickableMesh = (
+    With<Handle<Mesh>>,
+    Without<Pickable>,
+    Without<InstancesData>,
+);
+
 /// Makes everything in the scene with a mesh pickable
-fn make_pickable(
-    mut commands: Commands,
-    meshes: Query<Entity, (With<Handle<Mesh>>, Without<Pickable>)>,
-) {
+fn make_pickable(mut commands: Commands, meshes: Query<Entity, UnpickableMesh>) {
     for entity in meshes.iter() {
-        commands
-            .entity(entity)
-            .insert((PickableBundle::default(), HIGHLIGHT_TINT.clone()));
+        commands.entity(entity).insert(PickableBundle::default());
     }
 }
-
-const HIGHLIGHT_TINT: Highlight<StandardMaterial> = Highlight {
-    hovered: Some(HighlightKind::new_dynamic(|matl| StandardMaterial {
-        base_color: matl.base_color + Color::rgba(-0.2, -0.2, 0.4, 0.0),
-        ..matl.to_owned()
-    })),
-    pressed: Some(HighlightKind::new_dynamic(|matl| StandardMaterial {
-        base_color: matl.base_color + Color::rgba(-0.3, -0.3, 0.5, 0.0),
-        ..matl.to_owned()
-    })),
-    selected: Some(HighlightKind::new_dynamic(|matl| StandardMaterial {
-        base_color: matl.base_color + Color::rgba(-0.3, 0.2, -0.3, 0.0),
-        ..matl.to_owned()
-    })),
-};
@@ -1,55 +1,125 @@
+use crate::MainCamera;
 use bevy::prelude::*;
-use bevy_cameras::CameraMode;
-
-#[derive(Debug, PartialEq, Eq)]
-pub enum CameraModes {
-    //     Orbiting: The camera rotates around a target object or point of interest. The camera's movement is constrained to a certain distance from the target and a fixed angle of inclination.
-    Orbiting {
-        target: Option<Entity>,
-        // distance: i32,
-        // elevation: i32,
-        // azimuth: i32,
-    },
-    // Following: The camera follows a target object or character as it moves through the scene. The camera's movement is constrained to a certain distance and angle from the target.
-    Following {
-        target: Entity,
-        // offset: Vec3,
-    },
-    // First-person: The camera is positioned at the player's eye level and follows the player's movements. The camera's movement is generally limited to the player's movements and the player's field of view.
-    FirstPerson {
-        // yaw: f32,
-        // pitch: f32,
-    },
-    // Third-person: The camera is positioned behind the player and follows the player's movements. The camera's movement is generally limited to a certain distance and angle from the player.
-    ThirdPerson {},
-    // Top-down: The camera is positioned directly above the scene and provides a bird's-eye view of the action. The camera's movement is generally limited to panning and zooming.
-    TopDown,
-    // Cinematic: The camera is used to create a cinematic effect, such as a cutscene or dramatic reveal. The camera's movement is generally scripted and may include special effects such as depth of field or motion blur.
-    Cinematic,
-}
+use bevy_cameras::{
+    bookmarks::{CameraBookmarks, CameraPath, Easing, RestoreView, SaveView},
+    cinematic_camera::CinematicCameraController,
+    CameraMode,
+};
+use bevy_protein::protein_asset_loader::ProteinAsset;
+
+pub use bevy_cameras::mode::{CameraModeImpl, CameraModes};
+
+/// Where the views bookmarked with [`bookmark_views`] are saved.
+const BOOKMARKS_PATH: &str = "camera_bookmarks.ron";
+
+/// How long the camera takes to go from one bookmarked view to the next, in seconds.
+const VIEW_TRANSITION_DURATION: f32 = 1.5;
+
+const VIEW_KEYS: [KeyCode; 9] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+];
 
-impl Default for CameraModes {
-    fn default() -> Self {
-        Self::Orbiting { target: None }
+/// Switches the camera mode with the number keys: 1 orbits, 2 flies, 3 looks down, 4 and 5 follow the protein, and 6
+/// holds the camera for [`bookmark_views`] to play a tour.
+pub fn switch_camera_mode(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    proteins: Query<Entity, With<Handle<ProteinAsset>>>,
+    mut camera_mode: ResMut<CameraModeImpl>,
+) {
+    let protein = proteins.iter().next();
+    let mode = if keyboard.just_pressed(KeyCode::Digit1) {
+        Some(CameraModes::Orbiting { target: None })
+    } else if keyboard.just_pressed(KeyCode::Digit2) {
+        Some(CameraModes::FirstPerson {})
+    } else if keyboard.just_pressed(KeyCode::Digit3) {
+        Some(CameraModes::TopDown)
+    } else if keyboard.just_pressed(KeyCode::Digit4) {
+        protein.map(|target| CameraModes::Following { target })
+    } else if keyboard.just_pressed(KeyCode::Digit5) {
+        protein.map(|target| CameraModes::ThirdPerson { target })
+    } else if keyboard.just_pressed(KeyCode::Digit6) {
+        Some(CameraModes::Cinematic)
+    } else {
+        None
+    };
+
+    if let Some(mode) = mode {
+        if *camera_mode.mode() != mode {
+            camera_mode.set_mode(mode);
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Resource, Default)]
-pub struct CameraModeImpl {
-    locked: bool,
-    mode: CameraModes,
-}
+/// B bookmarks the view of the orbit camera, F1 to F9 go back to the bookmarked views, and P plays a tour through
+/// them all. Ctrl+S saves the bookmarks to [`BOOKMARKS_PATH`] and Ctrl+O loads them back.
+pub fn bookmark_views(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut camera_mode: ResMut<CameraModeImpl>,
+    mut saves: EventWriter<SaveView>,
+    mut restores: EventWriter<RestoreView>,
+    mut cameras: Query<(Entity, Option<&mut CinematicCameraController>), With<MainCamera>>,
+) {
+    let control = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if control && keyboard.just_pressed(KeyCode::KeyS) {
+        match bookmarks.save(BOOKMARKS_PATH) {
+            Ok(()) => info!("Saved the camera bookmarks to {BOOKMARKS_PATH}"),
+            Err(error) => error!("{error}"),
+        }
+        return;
+    }
+    if control && keyboard.just_pressed(KeyCode::KeyO) {
+        match CameraBookmarks::load(BOOKMARKS_PATH) {
+            Ok(loaded) => *bookmarks = loaded,
+            Err(error) => error!("{error}"),
+        }
+        return;
+    }
 
-impl CameraMode for CameraModeImpl {
-    fn is_locked(&self) -> bool {
-        self.locked
+    if keyboard.just_pressed(KeyCode::KeyB) {
+        // Zero-padded, so that the views sort in the order they were bookmarked.
+        let name = format!("view {:02}", bookmarks.views.len() + 1);
+        saves.send(SaveView { name, camera: None });
     }
 
-    fn lock(&mut self) {
-        self.locked = true;
+    if let Some(index) = VIEW_KEYS.iter().position(|key| keyboard.just_pressed(*key)) {
+        if let Some(name) = bookmarks.views.keys().nth(index) {
+            camera_mode.set_mode(CameraModes::Orbiting { target: None });
+            restores.send(RestoreView {
+                name: name.clone(),
+                camera: None,
+                duration: VIEW_TRANSITION_DURATION,
+            });
+        }
     }
 
-    fn unlock(&mut self) {
-        self.locked = false
+    if keyboard.just_pressed(KeyCode::KeyP) && !bookmarks.views.is_empty() {
+        let tour = CameraPath::through(
+            bookmarks.views.values().copied(),
+            VIEW_TRANSITION_DURATION,
+            Easing::SmoothStep,
+        );
+        bookmarks.paths.insert("tour".to_string(), tour.clone());
+
+        for (entity, cinematic) in cameras.iter_mut() {
+            match cinematic {
+                Some(mut cinematic) => cinematic.play(tour.clone()),
+                None => {
+                    let mut cinematic = CinematicCameraController::default();
+                    cinematic.play(tour.clone());
+                    commands.entity(entity).insert(cinematic);
+                }
+            }
+        }
+        camera_mode.set_mode(CameraModes::Cinematic);
     }
 }
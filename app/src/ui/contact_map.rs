@@ -0,0 +1,179 @@
+//! A Cα contact map of the primary protein, rendered as a texture. Clicking a pixel selects
+//! the pair of residues it represents and recentres the orbit camera between them.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        renderer::RenderDevice,
+        texture::ImageSampler,
+    },
+    ui::RelativeCursorPosition,
+};
+use bevy_cameras::pan_orbit_camera::OrbitCameraController;
+use bevy_protein::{
+    contact_map::{ContactMap, DistanceMode, DEFAULT_CONTACT_THRESHOLD},
+    protein_asset_loader::ProteinAsset,
+    selection::ResidueSelection,
+};
+
+use crate::{AppState, MainCamera, ProteinAssetsMap};
+
+const PANEL_SIZE: f32 = 280.;
+// Distances beyond this are left blank in the image.
+const DISTANCE_CUTOFF: f32 = 20.;
+// The widest texture wgpu allows by default, for when the render device isn't known.
+const DEFAULT_MAX_TEXTURE_SIZE: u32 = 8192;
+
+pub struct ContactMapPlugin;
+
+impl Plugin for ContactMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Main), Self::spawn_panel)
+            .add_systems(
+                Update,
+                Self::select_on_click.run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct ContactMapPanel {
+    pub protein: AssetId<ProteinAsset>,
+    pub contact_map: ContactMap,
+}
+
+/// Contacts are drawn in black, fading to white at the cutoff.
+fn distance_color(distance: f32) -> [u8; 4] {
+    if distance <= DEFAULT_CONTACT_THRESHOLD {
+        return [0, 0, 0, 255];
+    }
+    let t = ((distance - DEFAULT_CONTACT_THRESHOLD)
+        / (DISTANCE_CUTOFF - DEFAULT_CONTACT_THRESHOLD))
+        .clamp(0., 1.);
+    let value = (100. + 155. * t) as u8;
+    [value, value, value, 255]
+}
+
+/// One pixel per residue pair, or, for maps wider than `max_size`, per block of residue pairs, showing the closest
+/// pair of the block.
+fn contact_map_image(contact_map: &ContactMap, max_size: u32) -> Image {
+    let n = contact_map.residue_count();
+    let block = n.div_ceil(max_size.max(1) as usize);
+    let size = n.div_ceil(block);
+
+    let mut distances = vec![f32::INFINITY; size * size];
+    for row in 0..n {
+        for column in 0..n {
+            let distance = contact_map.distance(row, column).unwrap_or(f32::INFINITY);
+            let pixel = &mut distances[row / block * size + column / block];
+            *pixel = pixel.min(distance);
+        }
+    }
+    let data = distances.into_iter().flat_map(distance_color).collect();
+
+    let mut image = Image::new(
+        Extent3d {
+            width: size as u32,
+            height: size as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+impl ContactMapPlugin {
+    fn spawn_panel(
+        mut commands: Commands,
+        protein_assets_map: Res<ProteinAssetsMap>,
+        protein_assets: Res<Assets<ProteinAsset>>,
+        mut images: ResMut<Assets<Image>>,
+        render_device: Option<Res<RenderDevice>>,
+    ) {
+        let Some(protein_asset) = protein_assets.get(&protein_assets_map.primary_protein) else {
+            return;
+        };
+
+        let contact_map =
+            ContactMap::from_pdb(&protein_asset.pdb, DistanceMode::CAlpha, DISTANCE_CUTOFF);
+        if contact_map.residue_count() == 0 {
+            return;
+        }
+
+        let max_size = render_device.map_or(DEFAULT_MAX_TEXTURE_SIZE, |render_device| {
+            render_device.limits().max_texture_dimension_2d
+        });
+        let image = images.add(contact_map_image(&contact_map, max_size));
+
+        commands.spawn((
+            ImageBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    width: Val::Px(PANEL_SIZE),
+                    height: Val::Px(PANEL_SIZE),
+                    ..default()
+                },
+                image: UiImage::new(image),
+                ..default()
+            },
+            Interaction::default(),
+            RelativeCursorPosition::default(),
+            ContactMapPanel {
+                protein: protein_assets_map.primary_protein.id(),
+                contact_map,
+            },
+        ));
+    }
+
+    fn select_on_click(
+        panels: Query<
+            (&Interaction, &RelativeCursorPosition, &ContactMapPanel),
+            Changed<Interaction>,
+        >,
+        mut cameras: Query<&mut OrbitCameraController, With<MainCamera>>,
+        mut selection: ResMut<ResidueSelection>,
+    ) {
+        for (interaction, cursor, panel) in panels.iter() {
+            let (Interaction::Pressed, Some(normalized)) = (interaction, cursor.normalized) else {
+                continue;
+            };
+
+            let contact_map = &panel.contact_map;
+            let n = contact_map.residue_count();
+            let row = ((normalized.y * n as f32) as usize).min(n - 1);
+            let column = ((normalized.x * n as f32) as usize).min(n - 1);
+
+            let (Some(a), Some(b)) = (
+                contact_map.residue_index(row),
+                contact_map.residue_index(column),
+            ) else {
+                continue;
+            };
+            let (Some(ca_a), Some(ca_b)) = (
+                contact_map.ca_position(row),
+                contact_map.ca_position(column),
+            ) else {
+                continue;
+            };
+
+            let selection = selection.get_mut(panel.protein);
+            selection.selected.clear();
+            selection.selected.extend([a, b]);
+
+            let midpoint = 0.5 * (ca_a + ca_b);
+            for mut camera in cameras.iter_mut().filter(|camera| camera.enabled) {
+                // Each camera moves from its own centre, so it is moved directly rather than by an event.
+                let dr = midpoint - camera.target.center;
+                camera.recentre(dr);
+            }
+        }
+    }
+}
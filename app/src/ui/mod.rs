@@ -0,0 +1,4 @@
+pub mod contact_map;
+pub mod molecule_toggles;
+pub mod ramachandran;
+pub mod sequence;
@@ -0,0 +1,113 @@
+//! A column of buttons toggling the visibility of each kind of molecule (polymer, ligands,
+//! water, ions, carbohydrates).
+
+use bevy::prelude::*;
+use bevy_protein::molecule_kind::{MoleculeKind, MoleculeKindVisibility};
+
+use crate::AppState;
+
+const BUTTON_WIDTH: f32 = 110.;
+const FONT_SIZE: f32 = 14.;
+const SHOWN_COLOR: Color = Color::rgb(0.25, 0.45, 0.3);
+const HIDDEN_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+
+pub struct MoleculeTogglesPlugin;
+
+impl Plugin for MoleculeTogglesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Main), Self::spawn_toggles)
+            .add_systems(
+                Update,
+                (
+                    Self::toggle_on_click,
+                    Self::show_state.run_if(resource_changed::<MoleculeKindVisibility>),
+                )
+                    .run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct MoleculeToggle {
+    pub kind: MoleculeKind,
+}
+
+fn button_color(visible: bool) -> Color {
+    if visible {
+        SHOWN_COLOR
+    } else {
+        HIDDEN_COLOR
+    }
+}
+
+impl MoleculeTogglesPlugin {
+    fn spawn_toggles(
+        mut commands: Commands,
+        molecule_kind_visibility: Res<MoleculeKindVisibility>,
+    ) {
+        commands
+            .spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.),
+                    top: Val::Percent(40.),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                for kind in MoleculeKind::ALL {
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(BUTTON_WIDTH),
+                                    padding: UiRect::all(Val::Px(4.)),
+                                    justify_content: JustifyContent::Center,
+                                    ..default()
+                                },
+                                background_color: button_color(
+                                    molecule_kind_visibility.is_visible(kind),
+                                )
+                                .into(),
+                                ..default()
+                            },
+                            MoleculeToggle { kind },
+                        ))
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section(
+                                kind.label(),
+                                TextStyle {
+                                    font_size: FONT_SIZE,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            ));
+                        });
+                }
+            });
+    }
+
+    fn toggle_on_click(
+        toggles: Query<(&Interaction, &MoleculeToggle), Changed<Interaction>>,
+        mut molecule_kind_visibility: ResMut<MoleculeKindVisibility>,
+    ) {
+        for (interaction, toggle) in toggles.iter() {
+            if *interaction == Interaction::Pressed {
+                molecule_kind_visibility.toggle(toggle.kind);
+            }
+        }
+    }
+
+    fn show_state(
+        molecule_kind_visibility: Res<MoleculeKindVisibility>,
+        mut toggles: Query<(&MoleculeToggle, &mut BackgroundColor)>,
+    ) {
+        for (toggle, mut background_color) in toggles.iter_mut() {
+            *background_color =
+                button_color(molecule_kind_visibility.is_visible(toggle.kind)).into();
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! A Ramachandran (φ/ψ) plot of the primary protein. Clicking a point selects its residue
+//! and recentres the orbit camera on it.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use bevy_cameras::pan_orbit_camera::OrbitCameraController;
+use bevy_protein::{
+    polypeptide::backbone_torsion::{RamachandranRegion, ResidueKind},
+    protein_asset_loader::ProteinAsset,
+    selection::{ResidueSelection, SELECTED_COLOR},
+};
+
+use crate::{AppState, MainCamera, ProteinAssetsMap};
+
+const PLOT_SIZE: f32 = 280.;
+const POINT_SIZE: f32 = 5.;
+// Resolution of the background region map, in pixels per side (2° per pixel).
+const BACKGROUND_RESOLUTION: u32 = 180;
+
+pub struct RamachandranPlotPlugin;
+
+impl Plugin for RamachandranPlotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Main), Self::spawn_plot)
+            .add_systems(
+                Update,
+                (
+                    Self::select_on_click,
+                    Self::highlight_selected.run_if(resource_changed::<ResidueSelection>),
+                )
+                    .run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct RamachandranPlot;
+
+#[derive(Component)]
+pub struct RamachandranPoint {
+    pub protein: AssetId<ProteinAsset>,
+    pub residue_index: usize,
+    pub ca_position: Vec3,
+    pub color: Color,
+}
+
+fn region_color(region: RamachandranRegion) -> Color {
+    match region {
+        RamachandranRegion::Favoured => Color::rgb(0.1, 0.2, 0.6),
+        RamachandranRegion::Allowed => Color::rgb(0.2, 0.5, 0.2),
+        RamachandranRegion::Outlier => Color::RED,
+    }
+}
+
+fn region_background_color(region: RamachandranRegion) -> Color {
+    match region {
+        RamachandranRegion::Favoured => Color::rgb(0.55, 0.7, 0.95),
+        RamachandranRegion::Allowed => Color::rgb(0.82, 0.88, 0.98),
+        RamachandranRegion::Outlier => Color::rgb(0.97, 0.97, 0.97),
+    }
+}
+
+/// Maps (φ, ψ) in degrees to a pixel offset from the top left of the plot.
+fn plot_position(φ: f32, ψ: f32) -> Vec2 {
+    Vec2::new((φ + 180.) / 360. * PLOT_SIZE, (180. - ψ) / 360. * PLOT_SIZE)
+}
+
+/// Paints the general-case favoured/allowed regions into an image used as the plot background.
+fn region_background() -> Image {
+    let n = BACKGROUND_RESOLUTION;
+    let step = 360. / n as f32;
+
+    let mut data = Vec::with_capacity((n * n * 4) as usize);
+    for row in 0..n {
+        let ψ = 180. - (row as f32 + 0.5) * step;
+        for column in 0..n {
+            let φ = -180. + (column as f32 + 0.5) * step;
+            let region = RamachandranRegion::classify(φ, ψ, ResidueKind::General);
+            data.extend_from_slice(&region_background_color(region).as_rgba_u8());
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: n,
+            height: n,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+impl RamachandranPlotPlugin {
+    fn spawn_plot(
+        mut commands: Commands,
+        protein_assets_map: Res<ProteinAssetsMap>,
+        protein_assets: Res<Assets<ProteinAsset>>,
+        mut images: ResMut<Assets<Image>>,
+    ) {
+        let Some(protein_asset) = protein_assets.get(&protein_assets_map.primary_protein) else {
+            return;
+        };
+
+        let background = images.add(region_background());
+
+        commands
+            .spawn((
+                ImageBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        right: Val::Px(10.),
+                        bottom: Val::Px(10.),
+                        width: Val::Px(PLOT_SIZE),
+                        height: Val::Px(PLOT_SIZE),
+                        ..default()
+                    },
+                    image: UiImage::new(background),
+                    ..default()
+                },
+                RamachandranPlot,
+            ))
+            .with_children(|parent| {
+                // φ = 0 and ψ = 0 axes
+                let centre = plot_position(0., 0.);
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(centre.x),
+                        width: Val::Px(1.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: Color::GRAY.into(),
+                    ..default()
+                });
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(centre.y),
+                        width: Val::Percent(100.),
+                        height: Val::Px(1.),
+                        ..default()
+                    },
+                    background_color: Color::GRAY.into(),
+                    ..default()
+                });
+
+                for torsion in protein_asset.backbone_torsions.0.iter() {
+                    let (Some(φ), Some(ψ), Some(region)) = (torsion.φ, torsion.ψ, torsion.region())
+                    else {
+                        continue;
+                    };
+
+                    let position = plot_position(φ, ψ);
+                    let color = region_color(region);
+
+                    parent.spawn((
+                        ButtonBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(position.x - 0.5 * POINT_SIZE),
+                                top: Val::Px(position.y - 0.5 * POINT_SIZE),
+                                width: Val::Px(POINT_SIZE),
+                                height: Val::Px(POINT_SIZE),
+                                ..default()
+                            },
+                            background_color: color.into(),
+                            ..default()
+                        },
+                        RamachandranPoint {
+                            protein: protein_assets_map.primary_protein.id(),
+                            residue_index: torsion.residue_index,
+                            ca_position: torsion.ca_position,
+                            color,
+                        },
+                    ));
+                }
+            });
+    }
+
+    fn select_on_click(
+        points: Query<(&Interaction, &RamachandranPoint), Changed<Interaction>>,
+        mut cameras: Query<&mut OrbitCameraController, With<MainCamera>>,
+        mut selection: ResMut<ResidueSelection>,
+    ) {
+        for (interaction, point) in points.iter() {
+            match interaction {
+                Interaction::Pressed => {
+                    selection
+                        .get_mut(point.protein)
+                        .select_only(point.residue_index);
+                    for mut camera in cameras.iter_mut().filter(|camera| camera.enabled) {
+                        // Each camera moves from its own centre, so it is moved directly rather than by an event.
+                        let dr = point.ca_position - camera.target.center;
+                        camera.recentre(dr);
+                    }
+                }
+                Interaction::Hovered => selection.hover(Some((point.protein, point.residue_index))),
+                Interaction::None => {
+                    if selection.is_hovered(point.protein, point.residue_index) {
+                        selection.hover(None);
+                    }
+                }
+            }
+        }
+    }
+
+    fn highlight_selected(
+        selection: Res<ResidueSelection>,
+        mut points: Query<(&RamachandranPoint, &mut BackgroundColor)>,
+    ) {
+        for (point, mut background_color) in points.iter_mut() {
+            *background_color = if selection.is_selected(point.protein, point.residue_index) {
+                SELECTED_COLOR.into()
+            } else {
+                point.color.into()
+            };
+        }
+    }
+}
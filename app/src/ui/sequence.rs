@@ -0,0 +1,310 @@
+//! A horizontally scrolling sequence track per chain: residue numbering, secondary structure,
+//! one-letter codes and a pLDDT colour bar. Click selects a residue, shift-click extends the
+//! selection to a range, and residues hovered in the 3D view are highlighted here.
+
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    ui::RelativeCursorPosition,
+};
+use bevy_cameras::api::CapturesMouseWheel;
+use bevy_protein::{
+    polypeptide::{
+        secondary_structure::SecondaryStructure,
+        sequence::{ChainSequence, SequenceResidue},
+    },
+    protein_asset_loader::ProteinAsset,
+    selection::{ResidueSelection, HOVERED_COLOR, SELECTED_COLOR},
+};
+
+use crate::{AppState, ProteinAssetsMap};
+
+const CELL_WIDTH: f32 = 12.;
+const LABEL_WIDTH: f32 = 30.;
+const FONT_SIZE: f32 = 12.;
+// Pixels scrolled per line of mouse wheel movement.
+const SCROLL_SPEED: f32 = 3. * CELL_WIDTH;
+
+pub struct SequenceViewerPlugin;
+
+impl Plugin for SequenceViewerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Main), Self::spawn_viewer)
+            .add_systems(
+                Update,
+                (
+                    Self::scroll,
+                    Self::select_on_click,
+                    Self::highlight_selected.run_if(resource_changed::<ResidueSelection>),
+                )
+                    .run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+/// The clipped, scrollable area of a chain's track.
+#[derive(Component)]
+pub struct SequenceViewport;
+
+#[derive(Component, Default)]
+pub struct SequenceTrack {
+    pub scroll: f32,
+}
+
+#[derive(Component)]
+pub struct SequenceCell {
+    pub protein: AssetId<ProteinAsset>,
+    pub residue_index: usize,
+}
+
+/// AlphaFold's pLDDT colour scheme.
+fn plddt_color(plddt: f32) -> Color {
+    if plddt > 90. {
+        Color::rgb_u8(0, 83, 214)
+    } else if plddt > 70. {
+        Color::rgb_u8(101, 203, 243)
+    } else if plddt > 50. {
+        Color::rgb_u8(255, 219, 19)
+    } else {
+        Color::rgb_u8(255, 125, 69)
+    }
+}
+
+fn secondary_structure_color(secondary_structure: SecondaryStructure) -> Color {
+    match secondary_structure {
+        SecondaryStructure::Helix => Color::rgb(0.9, 0.2, 0.3),
+        SecondaryStructure::Strand => Color::rgb(0.95, 0.8, 0.1),
+        SecondaryStructure::Coil => Color::GRAY,
+    }
+}
+
+fn text(value: impl Into<String>, color: Color) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size: FONT_SIZE,
+            color,
+            ..default()
+        },
+    )
+}
+
+fn spawn_cell(
+    parent: &mut ChildBuilder,
+    protein: AssetId<ProteinAsset>,
+    residue: &SequenceResidue,
+) {
+    let numbering = if residue.serial_number % 10 == 0 {
+        residue.serial_number.to_string()
+    } else {
+        String::new()
+    };
+
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(CELL_WIDTH),
+                    flex_shrink: 0.,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    overflow: Overflow::visible(),
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            SequenceCell {
+                protein,
+                residue_index: residue.residue_index,
+            },
+        ))
+        .with_children(|cell| {
+            cell.spawn(text(numbering, Color::GRAY).with_no_wrap());
+            cell.spawn(text(
+                residue.secondary_structure.glyph(),
+                secondary_structure_color(residue.secondary_structure),
+            ));
+            cell.spawn(text(residue.one_letter_code, Color::WHITE));
+            cell.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Px(4.),
+                    ..default()
+                },
+                background_color: plddt_color(residue.b_factor).into(),
+                ..default()
+            });
+        });
+}
+
+impl SequenceViewerPlugin {
+    fn spawn_viewer(
+        mut commands: Commands,
+        protein_assets_map: Res<ProteinAssetsMap>,
+        protein_assets: Res<Assets<ProteinAsset>>,
+    ) {
+        let protein = protein_assets_map.primary_protein.id();
+        let Some(protein_asset) = protein_assets.get(protein) else {
+            return;
+        };
+
+        let sequences =
+            ChainSequence::from_pdb(&protein_asset.pdb, &protein_asset.secondary_structures);
+
+        commands
+            .spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            })
+            .with_children(|parent| {
+                for sequence in sequences.iter() {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.),
+                                flex_direction: FlexDirection::Row,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            row.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(LABEL_WIDTH),
+                                    flex_shrink: 0.,
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|label| {
+                                label.spawn(text(sequence.chain_id.clone(), Color::WHITE));
+                            });
+
+                            row.spawn((
+                                NodeBundle {
+                                    style: Style {
+                                        flex_grow: 1.,
+                                        overflow: Overflow::clip_x(),
+                                        ..default()
+                                    },
+                                    ..default()
+                                },
+                                SequenceViewport,
+                                RelativeCursorPosition::default(),
+                                // Scrolling the track doesn't zoom the 3D view.
+                                CapturesMouseWheel,
+                            ))
+                            .with_children(|viewport| {
+                                viewport
+                                    .spawn((
+                                        NodeBundle {
+                                            style: Style {
+                                                flex_direction: FlexDirection::Row,
+                                                ..default()
+                                            },
+                                            ..default()
+                                        },
+                                        SequenceTrack::default(),
+                                    ))
+                                    .with_children(|track| {
+                                        for residue in sequence.residues.iter() {
+                                            spawn_cell(track, protein, residue);
+                                        }
+                                    });
+                            });
+                        });
+                }
+            });
+    }
+
+    fn scroll(
+        mut mouse_wheel_events: EventReader<MouseWheel>,
+        viewports: Query<(&RelativeCursorPosition, &Node, &Children), With<SequenceViewport>>,
+        mut tracks: Query<(&mut SequenceTrack, &mut Style, &Node)>,
+    ) {
+        let mut delta = 0.;
+        for event in mouse_wheel_events.read() {
+            let scroll = event.x + event.y;
+            delta += match event.unit {
+                MouseScrollUnit::Line => scroll * SCROLL_SPEED,
+                MouseScrollUnit::Pixel => scroll,
+            };
+        }
+        if delta == 0. {
+            return;
+        }
+
+        for (cursor, viewport, children) in viewports.iter() {
+            if !cursor.mouse_over() {
+                continue;
+            }
+            for child in children.iter() {
+                let Ok((mut track, mut style, node)) = tracks.get_mut(*child) else {
+                    continue;
+                };
+                let max_scroll = (node.size().x - viewport.size().x).max(0.);
+                track.scroll = (track.scroll - delta).clamp(0., max_scroll);
+                style.left = Val::Px(-track.scroll);
+            }
+        }
+    }
+
+    fn select_on_click(
+        cells: Query<(&Interaction, &SequenceCell), Changed<Interaction>>,
+        keyboard: Res<ButtonInput<KeyCode>>,
+        mut selection: ResMut<ResidueSelection>,
+        mut anchor: Local<Option<usize>>,
+    ) {
+        let extend = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+        for (interaction, cell) in cells.iter() {
+            match interaction {
+                Interaction::Pressed => match *anchor {
+                    Some(start) if extend => {
+                        selection.get_mut(cell.protein).select_range(
+                            start.min(cell.residue_index)..=start.max(cell.residue_index),
+                        );
+                    }
+                    _ => {
+                        selection
+                            .get_mut(cell.protein)
+                            .select_only(cell.residue_index);
+                        *anchor = Some(cell.residue_index);
+                    }
+                },
+                Interaction::Hovered => selection.hover(Some((cell.protein, cell.residue_index))),
+                Interaction::None => {
+                    if selection.is_hovered(cell.protein, cell.residue_index) {
+                        selection.hover(None);
+                    }
+                }
+            }
+        }
+    }
+
+    fn highlight_selected(
+        selection: Res<ResidueSelection>,
+        mut cells: Query<(&SequenceCell, &mut BackgroundColor)>,
+    ) {
+        for (cell, mut background_color) in cells.iter_mut() {
+            *background_color = if selection.is_hovered(cell.protein, cell.residue_index) {
+                HOVERED_COLOR.into()
+            } else if selection.is_selected(cell.protein, cell.residue_index) {
+                SELECTED_COLOR.into()
+            } else {
+                Color::NONE.into()
+            };
+        }
+    }
+}
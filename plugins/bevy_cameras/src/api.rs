@@ -1,5 +1,26 @@
-use bevy::{prelude::*, render::camera::Camera};
+use crate::mode::CameraModes;
+use bevy::{prelude::*, render::camera::Camera, ui::RelativeCursorPosition};
 
+/// Where a camera is, along with the point it looks at: what carries over when a camera switches between controllers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewPose {
+    pub transform: Transform,
+    pub focus: Vec3,
+}
+
+impl ViewPose {
+    /// The pose of a camera at `transform`, looking at the point `focus_distance` in front of it.
+    pub fn from_transform(transform: Transform, focus_distance: f32) -> Self {
+        Self {
+            transform,
+            focus: transform.translation + focus_distance * *transform.forward(),
+        }
+    }
+}
+
+/// The cameras whose controller of type `C` changed, with their transform for the controller to update.
+pub type ChangedControllerQuery<'w, 's, 'a, C> =
+    Query<'w, 's, (&'a C, &'a mut Transform), (Changed<C>, With<Camera>)>;
 
 pub trait CameraController: Component
 where
@@ -8,37 +29,48 @@ where
     fn update_camera_transform_system(
         query: Query<(&Self, &mut Transform), (Changed<Self>, With<Camera>)>,
     );
+    fn view_pose(&self) -> ViewPose;
+    /// Moves the controller as close to `pose` as it can go, e.g. when its camera switches to it from another one.
+    fn set_view_pose(&mut self, pose: &ViewPose);
+    fn is_enabled(&self) -> bool;
+    fn set_enabled(&mut self, enabled: bool);
 }
 
 pub trait CameraMode: Resource + Default + PartialEq + Eq {
     fn is_locked(&self) -> bool;
     fn lock(&mut self);
     fn unlock(&mut self);
+    fn mode(&self) -> &CameraModes;
+    fn set_mode(&mut self, mode: CameraModes);
 }
 
-// We may have several cameras : how do we switch between active cameras?
+/// Keeps the controllers of a camera from reacting to the mouse and keyboard, e.g. while the cursor is over another
+/// camera of a [`CameraRig`].
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct IgnoreInput;
 
-pub trait CameraRig: Resource {
-    fn add_camera(&mut self, camera: Camera);
-    fn remove_camera(&mut self, camera: &Camera);
-    fn update(&mut self, cameras: &mut Query<&mut Camera>);
-    //add/remove controller marker components? We want there to be only one controller component per camera bundle?
-}
+/// Marks a UI node that scrolls with the mouse wheel, e.g. a long list: while the cursor is over it, as told by its
+/// [`RelativeCursorPosition`], the cameras don't zoom.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct CapturesMouseWheel;
 
-pub struct DefaultCameraRig {
-    cameras: Vec<Entity>,
-}
+/// The UI nodes taking the mouse wheel from the cameras.
+pub type WheelCapturingNodes<'w, 's> =
+    Query<'w, 's, &'static RelativeCursorPosition, With<CapturesMouseWheel>>;
 
-impl Default for DefaultCameraRig {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Whether the cursor is over a node taking the mouse wheel from the cameras.
+pub fn is_wheel_captured(nodes: &WheelCapturingNodes) -> bool {
+    nodes.iter().any(RelativeCursorPosition::mouse_over)
 }
 
-impl DefaultCameraRig {
-    pub fn new() -> Self {
-        Self {
-            cameras: Vec::new(),
-        }
-    }
+/// Several cameras sharing a window: which of them are drawn where, and which one is active.
+pub trait CameraRig: Resource {
+    fn add_camera(&mut self, camera: Entity);
+    fn remove_camera(&mut self, camera: Entity);
+    fn cameras(&self) -> &[Entity];
+    /// The camera that gets the input while the cursor is over none of them.
+    fn active_camera(&self) -> Option<Entity>;
+    fn cycle_active_camera(&mut self);
+    /// Lays the cameras out in a window of `window_size` physical pixels.
+    fn update(&mut self, window_size: UVec2, cameras: &mut Query<&mut Camera>);
 }
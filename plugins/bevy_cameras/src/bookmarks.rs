@@ -0,0 +1,652 @@
+//! Named views of the [`OrbitCameraController`]s, saved to and loaded from RON or JSON files, and keyframed paths
+//! through them for the [`CinematicCameraController`](crate::cinematic_camera::CinematicCameraController) to play.
+
+use crate::{
+    api::IgnoreInput,
+    pan_orbit_camera::{OrbitCameraController, OrbitPose},
+};
+use bevy::{prelude::*, render::camera::Camera};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+use thiserror::Error;
+
+/// Possible errors that can be produced by [`CameraBookmarks`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum BookmarkError {
+    #[error("Could not read or write bookmarks: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse Ron: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("Could not write Ron: {0}")]
+    RonError(#[from] ron::Error),
+    #[error("Could not parse or write Json: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("No view is named {0:?}")]
+    UnknownView(String),
+}
+
+/// The part of a camera's projection a view restores.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ViewProjection {
+    /// With its vertical field of view, in radians.
+    Perspective {
+        fov: f32,
+    },
+    Orthographic {
+        scale: f32,
+    },
+}
+
+impl ViewProjection {
+    pub fn from_projection(projection: &Projection) -> Self {
+        match projection {
+            Projection::Perspective(perspective) => Self::Perspective {
+                fov: perspective.fov,
+            },
+            Projection::Orthographic(orthographic) => Self::Orthographic {
+                scale: orthographic.scale,
+            },
+        }
+    }
+
+    /// Sets the field of view or the scale of `projection`, switching it to the other kind if need be.
+    pub fn apply(&self, projection: &mut Projection) {
+        match (self, projection) {
+            (Self::Perspective { fov }, Projection::Perspective(perspective)) => {
+                perspective.fov = *fov;
+            }
+            (Self::Orthographic { scale }, Projection::Orthographic(orthographic)) => {
+                orthographic.scale = *scale;
+            }
+            (Self::Perspective { fov }, projection) => {
+                *projection = Projection::Perspective(PerspectiveProjection {
+                    fov: *fov,
+                    ..default()
+                });
+            }
+            (Self::Orthographic { scale }, projection) => {
+                *projection = Projection::Orthographic(OrthographicProjection {
+                    scale: *scale,
+                    ..default()
+                });
+            }
+        }
+    }
+
+    /// The field of view or the scale.
+    fn value(&self) -> f32 {
+        match *self {
+            Self::Perspective { fov } => fov,
+            Self::Orthographic { scale } => scale,
+        }
+    }
+
+    fn with_value(&self, value: f32) -> Self {
+        match self {
+            Self::Perspective { .. } => Self::Perspective { fov: value },
+            Self::Orthographic { .. } => Self::Orthographic { scale: value },
+        }
+    }
+
+    fn same_kind(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    /// Projections of different kinds don't blend: the result switches from one to the other at the end.
+    pub fn lerp(&self, other: &Self, s: f32) -> Self {
+        if !self.same_kind(other) {
+            return if s < 1. { *self } else { *other };
+        }
+        self.with_value(self.value() + (other.value() - self.value()) * s)
+    }
+}
+
+/// Everything needed to put an orbit camera back where it was.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedView {
+    pub pose: OrbitPose,
+    pub projection: ViewProjection,
+}
+
+impl SavedView {
+    /// The view of `camera`, where it is headed rather than where it lags behind.
+    pub fn of(camera: &OrbitCameraController, projection: &Projection) -> Self {
+        Self {
+            pose: camera
+                .transition
+                .map_or(camera.target, |transition| transition.to),
+            projection: ViewProjection::from_projection(projection),
+        }
+    }
+}
+
+/// How a segment of a [`CameraPath`] speeds up and slows down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Easing {
+    /// At a constant pace, e.g. for a steady orbit.
+    #[default]
+    Linear,
+    /// Starting and stopping smoothly.
+    SmoothStep,
+    /// Like `SmoothStep`, but lingering longer at either end.
+    CubicInOut,
+}
+
+impl Easing {
+    /// Maps the fraction `s` of a segment's time to the fraction of its way covered.
+    pub fn apply(&self, s: f32) -> f32 {
+        let s = s.clamp(0., 1.);
+        match self {
+            Self::Linear => s,
+            Self::SmoothStep => s * s * (3. - 2. * s),
+            Self::CubicInOut if s < 0.5 => 4. * s * s * s,
+            Self::CubicInOut => 1. - 4. * (1. - s).powi(3),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub view: SavedView,
+    /// From the start of the path, in seconds.
+    pub time: f32,
+    /// Of the segment to the next keyframe.
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+/// A camera move through keyframed views, interpolated with a Catmull-Rom spline so that it doesn't jolt at the
+/// keyframes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    /// In order of time.
+    pub keyframes: Vec<Keyframe>,
+    /// Whether the path starts over once it ends. For a seamless loop, end it on the view it starts from, e.g. a
+    /// whole turn later.
+    #[serde(default)]
+    pub looping: bool,
+}
+
+/// The spline interpolates these: θ, ψ, ρ and the centre.
+type PoseVector = [f32; 6];
+
+fn pose_vector(pose: &OrbitPose) -> PoseVector {
+    [
+        pose.θ,
+        pose.ψ,
+        pose.ρ,
+        pose.center.x,
+        pose.center.y,
+        pose.center.z,
+    ]
+}
+
+fn pose_from_vector(v: PoseVector) -> OrbitPose {
+    OrbitPose {
+        θ: v[0],
+        ψ: v[1],
+        ρ: v[2],
+        center: Vec3::new(v[3], v[4], v[5]),
+    }
+}
+
+/// The cubic Hermite curve from `values[1]` at `times[1]` to `values[2]` at `times[2]`, with the Catmull-Rom tangents
+/// of their neighbours `values[0]` and `values[3]`, at the fraction `u` of the way.
+fn catmull_rom(values: [f32; 4], times: [f32; 4], u: f32) -> f32 {
+    let span = times[2] - times[1];
+    let m1 = (values[2] - values[0]) / (times[2] - times[0]) * span;
+    let m2 = (values[3] - values[1]) / (times[3] - times[1]) * span;
+    let (u2, u3) = (u * u, u * u * u);
+    (2. * u3 - 3. * u2 + 1.) * values[1]
+        + (u3 - 2. * u2 + u) * m1
+        + (-2. * u3 + 3. * u2) * values[2]
+        + (u3 - u2) * m2
+}
+
+impl CameraPath {
+    /// Goes through `views` one after the other, `segment_duration` seconds apart. Each view is turned the short
+    /// way round from the previous one.
+    pub fn through(
+        views: impl IntoIterator<Item = SavedView>,
+        segment_duration: f32,
+        easing: Easing,
+    ) -> Self {
+        let mut keyframes: Vec<Keyframe> = Vec::new();
+        for view in views {
+            let (view, time) = match keyframes.last() {
+                Some(previous) => (
+                    SavedView {
+                        pose: view.pose.unwrapped_towards(&previous.view.pose),
+                        ..view
+                    },
+                    previous.time + segment_duration,
+                ),
+                None => (view, 0.),
+            };
+            keyframes.push(Keyframe { view, time, easing });
+        }
+        Self {
+            keyframes,
+            looping: false,
+        }
+    }
+
+    /// A whole turn of ψ around the centre of `view`, in `duration` seconds, over and over.
+    pub fn orbit(view: SavedView, duration: f32) -> Self {
+        let quarter_turn = std::f32::consts::FRAC_PI_2;
+        let keyframes = (0..=4)
+            .map(|i| Keyframe {
+                view: SavedView {
+                    pose: OrbitPose {
+                        ψ: view.pose.ψ + i as f32 * quarter_turn,
+                        ..view.pose
+                    },
+                    ..view
+                },
+                time: i as f32 * duration / 4.,
+                easing: Easing::Linear,
+            })
+            .collect();
+        Self {
+            keyframes,
+            looping: true,
+        }
+    }
+
+    /// In seconds.
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.,
+        }
+    }
+
+    /// The view `time` seconds into the path, or `None` if it has no keyframes. Out of a looping path's span, the
+    /// time wraps around; otherwise the path holds its first or last view.
+    pub fn sample(&self, time: f32) -> Option<SavedView> {
+        let keyframes = &self.keyframes;
+        let (first, last) = (keyframes.first()?, keyframes.last()?);
+        let n = keyframes.len();
+        let duration = self.duration();
+
+        let time = if self.looping && duration > 0. {
+            first.time + (time - first.time).rem_euclid(duration)
+        } else {
+            time.clamp(first.time, last.time)
+        };
+        let i = keyframes
+            .iter()
+            .rposition(|keyframe| keyframe.time <= time)
+            .unwrap_or(0)
+            .min(n.saturating_sub(2));
+        let (k1, k2) = (&keyframes[i], &keyframes[(i + 1).min(n - 1)]);
+        let span = k2.time - k1.time;
+        if span <= 0. {
+            return Some(k2.view);
+        }
+
+        // The neighbours that set the tangents at either end of the segment. Past the ends of a loop, the path
+        // carries on from its other end, shifted by how far the loop moves the camera (e.g. a whole turn).
+        let p1 = pose_vector(&k1.view.pose);
+        let p2 = pose_vector(&k2.view.pose);
+        let (p0, t0) = if i > 0 {
+            (
+                pose_vector(&keyframes[i - 1].view.pose),
+                keyframes[i - 1].time,
+            )
+        } else if self.looping && n > 2 {
+            let before = &keyframes[n - 2];
+            let (p_before, p_last) = (pose_vector(&before.view.pose), pose_vector(&last.view.pose));
+            (
+                std::array::from_fn(|j| p_before[j] - p_last[j] + p1[j]),
+                k1.time - (last.time - before.time),
+            )
+        } else {
+            (std::array::from_fn(|j| 2. * p1[j] - p2[j]), k1.time - span)
+        };
+        let (p3, t3) = if i + 2 < n {
+            (
+                pose_vector(&keyframes[i + 2].view.pose),
+                keyframes[i + 2].time,
+            )
+        } else if self.looping && n > 2 {
+            let after = &keyframes[1];
+            let (p_after, p_first) = (pose_vector(&after.view.pose), pose_vector(&first.view.pose));
+            (
+                std::array::from_fn(|j| p_after[j] - p_first[j] + p2[j]),
+                k2.time + (after.time - first.time),
+            )
+        } else {
+            (std::array::from_fn(|j| 2. * p2[j] - p1[j]), k2.time + span)
+        };
+
+        let u = k1.easing.apply((time - k1.time) / span);
+        let times = [t0, k1.time, k2.time, t3];
+        let pose = pose_from_vector(std::array::from_fn(|j| {
+            catmull_rom([p0[j], p1[j], p2[j], p3[j]], times, u)
+        }));
+        Some(SavedView {
+            pose: OrbitPose {
+                ρ: pose.ρ.max(0.),
+                ..pose
+            },
+            projection: k1.view.projection.lerp(&k2.view.projection, u),
+        })
+    }
+}
+
+/// Named views and paths, shared by every camera.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmarks {
+    pub views: BTreeMap<String, SavedView>,
+    #[serde(default)]
+    pub paths: BTreeMap<String, CameraPath>,
+}
+
+impl CameraBookmarks {
+    /// Reads the bookmarks from a JSON file if `path` ends in `.json`, and from a RON file otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BookmarkError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        if is_json(path) {
+            Ok(serde_json::from_str(&contents)?)
+        } else {
+            Ok(ron::from_str(&contents)?)
+        }
+    }
+
+    /// Writes the bookmarks to a JSON file if `path` ends in `.json`, and to a RON file otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BookmarkError> {
+        let path = path.as_ref();
+        let contents = if is_json(path) {
+            serde_json::to_string_pretty(self)?
+        } else {
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// A path through the views named `names`, in order: see [`CameraPath::through`].
+    pub fn path_through<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+        segment_duration: f32,
+        easing: Easing,
+    ) -> Result<CameraPath, BookmarkError> {
+        let views = names
+            .into_iter()
+            .map(|name| {
+                self.views
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| BookmarkError::UnknownView(name.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CameraPath::through(views, segment_duration, easing))
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Saves the view of an orbit camera in the [`CameraBookmarks`] as `name`, replacing any view of that name.
+#[derive(Event, Clone, Debug)]
+pub struct SaveView {
+    pub name: String,
+    /// The camera to save the view of, or the orbit camera that takes the input if `None`.
+    pub camera: Option<Entity>,
+}
+
+/// Moves orbit cameras to the view named `name` in the [`CameraBookmarks`].
+#[derive(Event, Clone, Debug)]
+pub struct RestoreView {
+    pub name: String,
+    /// The camera to move, or every orbit camera if `None`.
+    pub camera: Option<Entity>,
+    /// Of the transition, in seconds.
+    pub duration: f32,
+}
+
+/// An animated change of a camera's field of view or scale, alongside the [`OrbitTransition`] of a restored view.
+///
+/// [`OrbitTransition`]: crate::pan_orbit_camera::OrbitTransition
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct ProjectionTransition {
+    pub from: ViewProjection,
+    pub to: ViewProjection,
+    /// In seconds.
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+impl ProjectionTransition {
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn projection(&self) -> ViewProjection {
+        if self.is_finished() {
+            return self.to;
+        }
+        let s = self.elapsed / self.duration;
+        self.from.lerp(&self.to, s * s * (3. - 2. * s))
+    }
+}
+
+#[derive(Default)]
+pub struct CameraBookmarksPlugin;
+
+impl Plugin for CameraBookmarksPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraBookmarks>()
+            .add_event::<SaveView>()
+            .add_event::<RestoreView>()
+            .add_systems(Update, (Self::save_views, Self::animate_projections))
+            // After the cameras switch controllers, which would cancel the transitions, e.g. when a view is restored
+            // from another mode.
+            .add_systems(Last, Self::restore_views);
+    }
+}
+
+type SavedCamera<'a> = (
+    Entity,
+    &'a OrbitCameraController,
+    &'a Projection,
+    Has<IgnoreInput>,
+);
+
+type RestoredCamera<'a> = (Entity, &'a mut OrbitCameraController, &'a Projection);
+
+impl CameraBookmarksPlugin {
+    pub fn save_views(
+        mut events: EventReader<SaveView>,
+        mut bookmarks: ResMut<CameraBookmarks>,
+        cameras: Query<SavedCamera, With<Camera>>,
+    ) {
+        for event in events.read() {
+            let camera =
+                cameras
+                    .iter()
+                    .find(|(entity, camera, _, ignores_input)| match event.camera {
+                        Some(camera) => camera == *entity,
+                        None => camera.enabled && !ignores_input,
+                    });
+            if let Some((_, camera, projection, _)) = camera {
+                bookmarks
+                    .views
+                    .insert(event.name.clone(), SavedView::of(camera, projection));
+            } else {
+                warn!("No orbit camera to save the view {:?} of", event.name);
+            }
+        }
+    }
+
+    pub fn restore_views(
+        mut commands: Commands,
+        mut events: EventReader<RestoreView>,
+        bookmarks: Res<CameraBookmarks>,
+        mut cameras: Query<RestoredCamera, With<Camera>>,
+    ) {
+        for event in events.read() {
+            let Some(view) = bookmarks.views.get(&event.name) else {
+                warn!("{}", BookmarkError::UnknownView(event.name.clone()));
+                continue;
+            };
+
+            for (entity, mut camera, projection) in cameras.iter_mut() {
+                if event.camera.is_some_and(|camera| camera != entity) || !camera.enabled {
+                    continue;
+                }
+                camera.transition_to(view.pose, event.duration);
+                commands.entity(entity).insert(ProjectionTransition {
+                    from: ViewProjection::from_projection(projection),
+                    to: view.projection,
+                    duration: event.duration,
+                    elapsed: 0.,
+                });
+            }
+        }
+    }
+
+    pub fn animate_projections(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut cameras: Query<(Entity, &mut ProjectionTransition, &mut Projection)>,
+    ) {
+        for (entity, mut transition, mut projection) in cameras.iter_mut() {
+            transition.elapsed += time.delta_seconds();
+            transition.projection().apply(&mut projection);
+            if transition.is_finished() {
+                commands.entity(entity).remove::<ProjectionTransition>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(θ: f32, ψ: f32, ρ: f32, center: Vec3, fov: f32) -> SavedView {
+        SavedView {
+            pose: OrbitPose { θ, ψ, ρ, center },
+            projection: ViewProjection::Perspective { fov },
+        }
+    }
+
+    fn views() -> [SavedView; 3] {
+        [
+            view(1., 0., 10., Vec3::ZERO, 0.8),
+            view(1.2, 1., 15., Vec3::new(2., 0., -1.), 0.6),
+            view(0.9, 2.5, 8., Vec3::new(-1., 3., 0.), 0.9),
+        ]
+    }
+
+    fn assert_views_near(a: SavedView, b: SavedView) {
+        let (a_pose, b_pose) = (pose_vector(&a.pose), pose_vector(&b.pose));
+        assert!(
+            a_pose.iter().zip(b_pose).all(|(a, b)| (a - b).abs() < 1e-4),
+            "{a:?} != {b:?}"
+        );
+        assert!((a.projection.value() - b.projection.value()).abs() < 1e-4);
+    }
+
+    fn tangent(path: &CameraPath, from: f32, to: f32) -> PoseVector {
+        let (from_pose, to_pose) = (
+            pose_vector(&path.sample(from).unwrap().pose),
+            pose_vector(&path.sample(to).unwrap().pose),
+        );
+        std::array::from_fn(|j| (to_pose[j] - from_pose[j]) / (to - from))
+    }
+
+    #[test]
+    fn sample_hits_each_keyframe() {
+        for easing in [Easing::Linear, Easing::SmoothStep, Easing::CubicInOut] {
+            let path = CameraPath::through(views(), 2., easing);
+            assert_eq!(path.duration(), 4.);
+            for keyframe in &path.keyframes {
+                assert_views_near(path.sample(keyframe.time).unwrap(), keyframe.view);
+            }
+        }
+    }
+
+    #[test]
+    fn looping_is_seamless_at_the_wrap() {
+        let [a, b, c] = views();
+        let mut path = CameraPath::through([a, b, c, a], 1., Easing::Linear);
+        path.looping = true;
+        let duration = path.duration();
+        let h = 1e-3;
+
+        assert_views_near(path.sample(duration).unwrap(), a);
+        assert_views_near(path.sample(duration - h).unwrap(), path.sample(-h).unwrap());
+        assert_views_near(
+            path.sample(duration + 0.5).unwrap(),
+            path.sample(0.5).unwrap(),
+        );
+
+        // One-sided differences, off by about h times the curvature from the tangent at the wrap.
+        let before = tangent(&path, duration - h, duration);
+        let after = tangent(&path, 0., h);
+        for (before, after) in before.iter().zip(after) {
+            assert!((before - after).abs() < 5e-2, "{before} != {after}");
+        }
+    }
+
+    #[test]
+    fn non_looping_path_clamps_at_both_ends() {
+        let path = CameraPath::through(views(), 1., Easing::SmoothStep);
+        let [first, _, last] = views();
+        assert_views_near(path.sample(-5.).unwrap(), first);
+        assert_views_near(path.sample(path.duration() + 5.).unwrap(), last);
+        assert_eq!(CameraPath::default().sample(0.), None);
+    }
+
+    #[test]
+    fn easings_keep_the_ends_and_the_middle() {
+        for easing in [Easing::Linear, Easing::SmoothStep, Easing::CubicInOut] {
+            assert_eq!(easing.apply(0.), 0., "{easing:?}");
+            assert_eq!(easing.apply(0.5), 0.5, "{easing:?}");
+            assert_eq!(easing.apply(1.), 1., "{easing:?}");
+        }
+    }
+
+    #[test]
+    fn bookmarks_round_trip_through_ron_and_json() {
+        let [a, b, c] = views();
+        let mut bookmarks = CameraBookmarks::default();
+        bookmarks.views.insert("a".into(), a);
+        bookmarks.views.insert(
+            "b".into(),
+            SavedView {
+                projection: ViewProjection::Orthographic { scale: 0.05 },
+                ..b
+            },
+        );
+        bookmarks.views.insert("c".into(), c);
+        bookmarks.paths.insert(
+            "tour".into(),
+            bookmarks
+                .path_through(["a", "b", "c"], 1.5, Easing::CubicInOut)
+                .unwrap(),
+        );
+        bookmarks
+            .paths
+            .insert("orbit".into(), CameraPath::orbit(a, 8.));
+
+        for extension in ["ron", "json"] {
+            let path = std::env::temp_dir().join(format!(
+                "camera_bookmarks_{}.{extension}",
+                std::process::id()
+            ));
+            bookmarks.save(&path).unwrap();
+            let loaded = CameraBookmarks::load(&path);
+            fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.unwrap(), bookmarks, "{extension}");
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! A scripted camera playing a [`CameraPath`], e.g. the same orbit around a binding site for every presentation.
+
+use crate::{
+    api::{CameraController, CameraMode, ChangedControllerQuery, ViewPose},
+    bookmarks::{CameraPath, ViewProjection},
+    pan_orbit_camera::OrbitPose,
+};
+use bevy::{prelude::*, render::camera::Camera};
+
+#[derive(Default)]
+pub struct CinematicCameraControllerPlugin<T: CameraMode>(pub T);
+
+impl<T: CameraMode + Send + Sync + 'static> Plugin for CinematicCameraControllerPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, Self::play_paths)
+            .add_systems(Last, Self::update_camera_transform_system);
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct CinematicCameraController {
+    pub path: CameraPath,
+    /// How far into the path the camera is, in seconds.
+    pub elapsed: f32,
+    /// How many seconds of the path play each second: 0.0 pauses it, and negative values play it backwards.
+    pub speed: f32,
+    /// Where the camera is, which holds still while the path is empty or over.
+    pub pose: OrbitPose,
+    /// Set from the path, for the projection of the camera to follow.
+    pub projection: Option<ViewProjection>,
+    pub enabled: bool,
+}
+
+impl Default for CinematicCameraController {
+    fn default() -> Self {
+        CinematicCameraController {
+            path: CameraPath::default(),
+            elapsed: 0.,
+            speed: 1.,
+            pose: OrbitPose {
+                θ: std::f32::consts::FRAC_PI_2,
+                ψ: 0.,
+                ρ: 100.,
+                center: Vec3::ZERO,
+            },
+            projection: None,
+            enabled: true,
+        }
+    }
+}
+
+impl CinematicCameraController {
+    /// Moves the camera to the start of `path`, to play it from there at [`speed`](Self::speed).
+    pub fn play(&mut self, path: CameraPath) {
+        self.path = path;
+        self.elapsed = self
+            .path
+            .keyframes
+            .first()
+            .map_or(0., |keyframe| keyframe.time);
+        self.update(0.);
+    }
+
+    /// Whether playing on would leave the camera where it is.
+    pub fn is_finished(&self) -> bool {
+        let Some((first, last)) = self.path.keyframes.first().zip(self.path.keyframes.last())
+        else {
+            return true;
+        };
+        if self.speed == 0. {
+            return true;
+        }
+        if self.path.looping {
+            return false;
+        }
+        (self.speed > 0. && self.elapsed >= last.time)
+            || (self.speed < 0. && self.elapsed <= first.time)
+    }
+
+    /// Plays the path on for `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed += self.speed * dt;
+        if let Some(view) = self.path.sample(self.elapsed) {
+            self.pose = view.pose;
+            self.projection = Some(view.projection);
+        }
+    }
+}
+
+impl CameraController for CinematicCameraController {
+    fn update_camera_transform_system(
+        mut query: Query<
+            (&CinematicCameraController, &mut Transform),
+            (Changed<CinematicCameraController>, With<Camera>),
+        >,
+    ) {
+        for (camera, mut transform) in query.iter_mut() {
+            if camera.enabled {
+                let cinematic = camera.pose.transform();
+                transform.translation = cinematic.translation;
+                transform.rotation = cinematic.rotation;
+            }
+        }
+    }
+
+    fn view_pose(&self) -> ViewPose {
+        ViewPose {
+            transform: self.pose.transform(),
+            focus: self.pose.center,
+        }
+    }
+
+    /// Holds the camera at `pose` until the path plays on.
+    fn set_view_pose(&mut self, pose: &ViewPose) {
+        self.pose = OrbitPose::from_view_pose(pose);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+impl<T: CameraMode> CinematicCameraControllerPlugin<T> {
+    pub fn update_camera_transform_system(
+        query: ChangedControllerQuery<CinematicCameraController>,
+    ) {
+        CinematicCameraController::update_camera_transform_system(query);
+    }
+
+    pub fn play_paths(
+        time: Res<Time>,
+        mut query: Query<(&mut CinematicCameraController, &mut Projection), With<Camera>>,
+    ) {
+        for (mut camera, mut projection) in query.iter_mut() {
+            if !camera.enabled {
+                continue;
+            }
+            // Paused and finished cameras aren't touched, so that their transform isn't recomputed.
+            if !camera.is_finished() {
+                camera.update(time.delta_seconds());
+            }
+            if let Some(view_projection) = camera.projection {
+                if ViewProjection::from_projection(&projection) != view_projection {
+                    view_projection.apply(&mut projection);
+                }
+            }
+        }
+    }
+}
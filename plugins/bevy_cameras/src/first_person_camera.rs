@@ -0,0 +1,183 @@
+//! A free-flying camera: WASD to move, Q and E to go down and up, Shift to go faster, and the mouse to look around.
+
+use crate::{
+    api::{CameraController, CameraMode, ChangedControllerQuery, IgnoreInput, ViewPose},
+    pan_orbit_camera::run_criteria,
+};
+use bevy::{prelude::*, render::camera::Camera};
+use bevy_mod_picking::pointer::InputMove;
+
+/// The pitch stops short of looking straight up or down, where the yaw would be undefined.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Default)]
+pub struct FirstPersonCameraControllerPlugin<T: CameraMode>(pub T);
+
+impl<T: CameraMode + Send + Sync + 'static> Plugin for FirstPersonCameraControllerPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (Self::look, Self::fly).run_if(run_criteria::<T>))
+            .add_systems(Last, Self::update_camera_transform_system);
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct FirstPersonCameraController {
+    pub position: Vec3,
+    /// Around the Y axis, in radians. 0.0 looks towards -Z.
+    pub yaw: f32,
+    /// Up from the horizon, in radians.
+    pub pitch: f32,
+    /// In world units per second.
+    pub speed: f32,
+    /// How many times faster the camera goes while Shift is held.
+    pub boost: f32,
+    /// In radians per pixel of pointer motion.
+    pub look_sensitivity: f32,
+    /// Held to look around.
+    pub look_button: MouseButton,
+    /// How far ahead the camera is taken to look, for the modes that look at a point.
+    pub focus_distance: f32,
+    pub enabled: bool,
+}
+
+impl Default for FirstPersonCameraController {
+    fn default() -> Self {
+        FirstPersonCameraController {
+            position: Vec3::ZERO,
+            yaw: 0.,
+            pitch: 0.,
+            speed: 20.,
+            boost: 4.,
+            look_sensitivity: 0.005,
+            look_button: MouseButton::Left,
+            focus_distance: 100.,
+            enabled: true,
+        }
+    }
+}
+
+impl FirstPersonCameraController {
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.)
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.position).with_rotation(self.rotation())
+    }
+
+    /// Turns the camera by a pointer motion of `delta` pixels.
+    pub fn look(&mut self, delta: Vec2) {
+        self.yaw -= delta.x * self.look_sensitivity;
+        self.pitch = (self.pitch - delta.y * self.look_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves the camera for `dt` seconds along `direction`, in its own frame: X to the right, Y up and -Z ahead.
+    pub fn fly(&mut self, direction: Vec3, dt: f32, boosted: bool) {
+        let speed = if boosted {
+            self.speed * self.boost
+        } else {
+            self.speed
+        };
+        self.position += self.rotation() * direction.normalize_or_zero() * speed * dt;
+    }
+}
+
+impl CameraController for FirstPersonCameraController {
+    fn update_camera_transform_system(
+        mut query: Query<
+            (&FirstPersonCameraController, &mut Transform),
+            (Changed<FirstPersonCameraController>, With<Camera>),
+        >,
+    ) {
+        for (camera, mut transform) in query.iter_mut() {
+            if camera.enabled {
+                transform.translation = camera.position;
+                transform.rotation = camera.rotation();
+            }
+        }
+    }
+
+    fn view_pose(&self) -> ViewPose {
+        ViewPose::from_transform(self.transform(), self.focus_distance)
+    }
+
+    fn set_view_pose(&mut self, pose: &ViewPose) {
+        let direction = pose.focus - pose.transform.translation;
+        // Roll is dropped: the camera always keeps the horizon level.
+        let (yaw, pitch, _) = match direction.try_normalize() {
+            Some(direction) => Transform::IDENTITY
+                .looking_to(direction, Vec3::Y)
+                .rotation
+                .to_euler(EulerRot::YXZ),
+            None => pose.transform.rotation.to_euler(EulerRot::YXZ),
+        };
+        self.position = pose.transform.translation;
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        if direction.length() > 0. {
+            self.focus_distance = direction.length();
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+impl<T: CameraMode> FirstPersonCameraControllerPlugin<T> {
+    pub fn update_camera_transform_system(
+        query: ChangedControllerQuery<FirstPersonCameraController>,
+    ) {
+        FirstPersonCameraController::update_camera_transform_system(query);
+    }
+
+    pub fn look(
+        mut pointer_motion_events: EventReader<InputMove>,
+        pointer_button_input: Res<ButtonInput<MouseButton>>,
+        mut query: Query<&mut FirstPersonCameraController, (With<Camera>, Without<IgnoreInput>)>,
+    ) {
+        let delta: Vec2 = pointer_motion_events.read().map(|event| event.delta).sum();
+        if delta == Vec2::ZERO {
+            return;
+        }
+
+        for mut camera in query.iter_mut() {
+            if camera.enabled && pointer_button_input.pressed(camera.look_button) {
+                camera.look(delta);
+            }
+        }
+    }
+
+    pub fn fly(
+        time: Res<Time>,
+        keyboard: Res<ButtonInput<KeyCode>>,
+        mut query: Query<&mut FirstPersonCameraController, (With<Camera>, Without<IgnoreInput>)>,
+    ) {
+        let direction: Vec3 = [
+            (KeyCode::KeyW, Vec3::NEG_Z),
+            (KeyCode::KeyS, Vec3::Z),
+            (KeyCode::KeyA, Vec3::NEG_X),
+            (KeyCode::KeyD, Vec3::X),
+            (KeyCode::KeyQ, Vec3::NEG_Y),
+            (KeyCode::KeyE, Vec3::Y),
+        ]
+        .into_iter()
+        .filter(|(key, _)| keyboard.pressed(*key))
+        .map(|(_, direction)| direction)
+        .sum();
+        if direction == Vec3::ZERO {
+            return;
+        }
+        let boosted = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+        for mut camera in query.iter_mut() {
+            if camera.enabled {
+                camera.fly(direction, time.delta_seconds(), boosted);
+            }
+        }
+    }
+}
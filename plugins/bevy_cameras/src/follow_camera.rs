@@ -0,0 +1,158 @@
+//! A camera keeping an offset from a target entity and looking at it, catching up with it after a lag.
+
+use crate::api::{CameraController, CameraMode, ChangedControllerQuery, ViewPose};
+use bevy::{prelude::*, render::camera::Camera, transform::TransformSystem};
+
+#[derive(Default)]
+pub struct FollowCameraControllerPlugin<T: CameraMode>(pub T);
+
+impl<T: CameraMode + Send + Sync + 'static> Plugin for FollowCameraControllerPlugin<T> {
+    fn build(&self, app: &mut App) {
+        // The target has to be in its place for the frame before the camera follows it.
+        app.add_systems(
+            PostUpdate,
+            Self::follow_targets.after(TransformSystem::TransformPropagate),
+        )
+        .add_systems(Last, Self::update_camera_transform_system);
+    }
+}
+
+/// The frame the offset of a [`FollowCameraController`] is in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FollowFrame {
+    /// The camera keeps its direction from the target whichever way the target turns.
+    #[default]
+    World,
+    /// The camera turns with the target, e.g. to stay behind it.
+    Target,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct FollowCameraController {
+    pub target: Entity,
+    /// From the target to the camera, in `frame`.
+    pub offset: Vec3,
+    pub frame: FollowFrame,
+    /// The time the camera takes to cover about 63% of the way to where it should be, in seconds. 0.0 keeps it
+    /// rigidly attached to the target.
+    pub lag: f32,
+    /// Where the camera is, lagging behind the target.
+    pub position: Vec3,
+    /// The point the camera looks at, lagging behind the target.
+    pub focus: Vec3,
+    pub enabled: bool,
+    /// Set by [`CameraController::set_view_pose`], and turned into an offset once the target is known.
+    pending_pose: Option<ViewPose>,
+}
+
+impl FollowCameraController {
+    /// Follows `target` from `offset` in world space.
+    pub fn following(target: Entity, offset: Vec3) -> Self {
+        Self {
+            target,
+            offset,
+            frame: FollowFrame::World,
+            lag: 0.3,
+            position: Vec3::ZERO,
+            focus: Vec3::ZERO,
+            enabled: true,
+            pending_pose: None,
+        }
+    }
+
+    /// Follows `target` from `offset` in its own frame, e.g. behind and above it.
+    pub fn third_person(target: Entity, offset: Vec3) -> Self {
+        Self {
+            frame: FollowFrame::Target,
+            ..Self::following(target, offset)
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.position).looking_at(self.focus, Vec3::Y)
+    }
+
+    /// Moves the camera `dt` seconds closer to where it should be around `target`.
+    pub fn follow(&mut self, target: &Transform, dt: f32) {
+        if let Some(pose) = self.pending_pose.take() {
+            let offset = pose.transform.translation - target.translation;
+            self.offset = match self.frame {
+                FollowFrame::World => offset,
+                FollowFrame::Target => target.rotation.inverse() * offset,
+            };
+            self.position = pose.transform.translation;
+            self.focus = pose.focus;
+        }
+
+        let offset = match self.frame {
+            FollowFrame::World => self.offset,
+            FollowFrame::Target => target.rotation * self.offset,
+        };
+        let s = if self.lag > 0. {
+            1. - f32::exp(-dt / self.lag)
+        } else {
+            1.
+        };
+        self.position = self.position.lerp(target.translation + offset, s);
+        self.focus = self.focus.lerp(target.translation, s);
+    }
+}
+
+impl CameraController for FollowCameraController {
+    fn update_camera_transform_system(
+        mut query: Query<
+            (&FollowCameraController, &mut Transform),
+            (Changed<FollowCameraController>, With<Camera>),
+        >,
+    ) {
+        for (camera, mut transform) in query.iter_mut() {
+            if camera.enabled && camera.pending_pose.is_none() {
+                let follow = camera.transform();
+                transform.translation = follow.translation;
+                transform.rotation = follow.rotation;
+            }
+        }
+    }
+
+    fn view_pose(&self) -> ViewPose {
+        self.pending_pose.unwrap_or(ViewPose {
+            transform: self.transform(),
+            focus: self.focus,
+        })
+    }
+
+    /// Keeps the camera where `pose` puts it relative to the target, which it then turns to look at.
+    fn set_view_pose(&mut self, pose: &ViewPose) {
+        self.pending_pose = Some(*pose);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+impl<T: CameraMode> FollowCameraControllerPlugin<T> {
+    pub fn update_camera_transform_system(query: ChangedControllerQuery<FollowCameraController>) {
+        FollowCameraController::update_camera_transform_system(query);
+    }
+
+    pub fn follow_targets(
+        time: Res<Time>,
+        targets: Query<&GlobalTransform>,
+        mut query: Query<&mut FollowCameraController, With<Camera>>,
+    ) {
+        for mut camera in query.iter_mut() {
+            if !camera.enabled {
+                continue;
+            }
+            let Ok(target) = targets.get(camera.target) else {
+                continue;
+            };
+            camera.follow(&target.compute_transform(), time.delta_seconds());
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Framing entities or points in the view of the [`OrbitCameraController`]s: the camera is centred on their
+//! bounding sphere and moved back until the sphere fits its projection.
+
+use crate::{api::IgnoreInput, pan_orbit_camera::OrbitCameraController};
+use bevy::{
+    math::bounding::{BoundingSphere, BoundingVolume},
+    prelude::*,
+    render::{
+        camera::{Camera, CameraUpdateSystem},
+        primitives::Aabb,
+        view::VisibilitySystems,
+    },
+    transform::TransformSystem,
+};
+use bevy_mod_picking::prelude::{Click, Pointer, PointerButton};
+
+/// The longest time between the two clicks of a double-click, in seconds.
+const DOUBLE_CLICK_TIME: f32 = 0.3;
+
+/// How long the camera takes to frame a double-clicked object, in seconds.
+const DOUBLE_CLICK_FIT_DURATION: f32 = 0.5;
+
+/// How much larger than the bounding sphere the framed region is, so that it doesn't touch the edges of the view.
+const FIT_MARGIN: f32 = 1.1;
+
+/// What to fit in the view.
+#[derive(Clone, Debug)]
+pub enum FitTarget {
+    /// The entities and their descendants, by their [`Aabb`]s. Entities without one only count through their
+    /// descendants.
+    Entities(Vec<Entity>),
+    /// Points in world space, e.g. atom positions.
+    Points(Vec<Vec3>),
+}
+
+/// Moves orbit cameras so that a target fills their view, keeping the direction they look from.
+#[derive(Event, Clone, Debug)]
+pub struct FitToBounds {
+    pub target: FitTarget,
+    /// The camera to move, or every orbit camera if `None`.
+    pub camera: Option<Entity>,
+    /// Of the transition, in seconds.
+    pub duration: f32,
+}
+
+impl FitToBounds {
+    pub fn entities(entities: Vec<Entity>, duration: f32) -> Self {
+        Self {
+            target: FitTarget::Entities(entities),
+            camera: None,
+            duration,
+        }
+    }
+
+    pub fn points(points: Vec<Vec3>, duration: f32) -> Self {
+        Self {
+            target: FitTarget::Points(points),
+            camera: None,
+            duration,
+        }
+    }
+}
+
+/// The distance from its centre at which a sphere of `radius` fits the narrowest field of view of `projection`.
+pub fn fit_distance(projection: &PerspectiveProjection, radius: f32) -> f32 {
+    let vertical_fov = projection.fov;
+    let horizontal_fov = 2. * f32::atan(f32::tan(0.5 * vertical_fov) * projection.aspect_ratio);
+    radius * FIT_MARGIN / f32::sin(0.5 * vertical_fov.min(horizontal_fov))
+}
+
+/// The scale at which a sphere of `radius` fits the narrowest side of the area `projection` shows, or `None`
+/// before the area is known.
+pub fn fit_scale(projection: &OrthographicProjection, radius: f32) -> Option<f32> {
+    let side = projection.area.width().min(projection.area.height());
+    (side > 0.).then(|| projection.scale * 2. * radius * FIT_MARGIN / side)
+}
+
+#[derive(Default)]
+pub struct CameraFramingPlugin;
+
+impl Plugin for CameraFramingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FitToBounds>()
+            .add_systems(Update, Self::fit_on_double_click)
+            // The bounds of freshly spawned entities, and the aspect ratio of freshly spawned cameras, are only known
+            // once they are calculated.
+            .add_systems(
+                PostUpdate,
+                Self::fit_to_bounds
+                    .after(VisibilitySystems::CalculateBounds)
+                    .after(TransformSystem::TransformPropagate)
+                    .after(CameraUpdateSystem),
+            );
+    }
+}
+
+type BoundedEntity<'a> = (&'a GlobalTransform, Option<&'a Aabb>, Option<&'a Children>);
+
+impl CameraFramingPlugin {
+    /// The bounding sphere of `target`, if it has any extent to frame.
+    pub fn bounding_sphere(
+        target: &FitTarget,
+        entities: &Query<BoundedEntity>,
+    ) -> Option<BoundingSphere> {
+        match target {
+            FitTarget::Points(points) if points.is_empty() => None,
+            FitTarget::Points(points) => Some(BoundingSphere::from_point_cloud(
+                Vec3::ZERO,
+                Quat::IDENTITY,
+                points,
+            )),
+            FitTarget::Entities(roots) => {
+                let mut sphere: Option<BoundingSphere> = None;
+                let mut stack = roots.clone();
+                while let Some(entity) = stack.pop() {
+                    let Ok((transform, aabb, children)) = entities.get(entity) else {
+                        continue;
+                    };
+                    if let Some(aabb) = aabb {
+                        let (scale, _, _) = transform.to_scale_rotation_translation();
+                        let entity_sphere = BoundingSphere::new(
+                            transform.transform_point(aabb.center.into()),
+                            scale.max_element() * Vec3::from(aabb.half_extents).length(),
+                        );
+                        sphere = Some(match sphere {
+                            Some(sphere) => sphere.merge(&entity_sphere),
+                            None => entity_sphere,
+                        });
+                    }
+                    if let Some(children) = children {
+                        stack.extend(children.iter());
+                    }
+                }
+                sphere
+            }
+        }
+    }
+
+    pub fn fit_to_bounds(
+        mut events: EventReader<FitToBounds>,
+        entities: Query<BoundedEntity>,
+        mut cameras: Query<(Entity, &mut OrbitCameraController, &mut Projection), With<Camera>>,
+    ) {
+        for event in events.read() {
+            let Some(sphere) = Self::bounding_sphere(&event.target, &entities) else {
+                continue;
+            };
+
+            for (entity, mut camera, mut projection) in cameras.iter_mut() {
+                if event.camera.is_some_and(|camera| camera != entity) {
+                    continue;
+                }
+
+                let ρ = match projection.as_mut() {
+                    Projection::Perspective(perspective) => {
+                        fit_distance(perspective, sphere.radius())
+                    }
+                    Projection::Orthographic(orthographic) => {
+                        if let Some(scale) = fit_scale(orthographic, sphere.radius()) {
+                            orthographic.scale = scale;
+                        }
+                        // The distance doesn't change the size of the view, as long as the sphere is in front.
+                        2. * sphere.radius()
+                    }
+                };
+                camera.focus_on(sphere.center, ρ, event.duration);
+            }
+        }
+    }
+
+    /// Frames the object double-clicked with the primary button, in the cameras that take the input.
+    pub fn fit_on_double_click(
+        time: Res<Time>,
+        mut clicks: EventReader<Pointer<Click>>,
+        mut last_click: Local<Option<(Entity, f32)>>,
+        cameras: Query<Entity, (With<OrbitCameraController>, Without<IgnoreInput>)>,
+        mut fits: EventWriter<FitToBounds>,
+    ) {
+        for click in clicks.read() {
+            if click.event.button != PointerButton::Primary {
+                continue;
+            }
+
+            let now = time.elapsed_seconds();
+            match *last_click {
+                Some((entity, at)) if entity == click.target && now - at <= DOUBLE_CLICK_TIME => {
+                    for camera in cameras.iter() {
+                        fits.send(FitToBounds {
+                            camera: Some(camera),
+                            ..FitToBounds::entities(vec![click.target], DOUBLE_CLICK_FIT_DURATION)
+                        });
+                    }
+                    *last_click = None;
+                }
+                _ => *last_click = Some((click.target, now)),
+            }
+        }
+    }
+}
@@ -1,5 +1,12 @@
 pub mod api;
+pub mod bookmarks;
+pub mod cinematic_camera;
+pub mod first_person_camera;
+pub mod follow_camera;
+pub mod framing;
 pub mod mode;
 pub mod pan_orbit_camera;
+pub mod rig;
+pub mod top_down_camera;
 
 pub use api::{CameraController, CameraMode, CameraRig};
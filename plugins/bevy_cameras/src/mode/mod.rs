@@ -1,7 +1,18 @@
-use crate::CameraMode;
-use bevy::prelude::*;
+use crate::{
+    api::{CameraController, ViewPose},
+    cinematic_camera::{CinematicCameraController, CinematicCameraControllerPlugin},
+    first_person_camera::{FirstPersonCameraController, FirstPersonCameraControllerPlugin},
+    follow_camera::{FollowCameraController, FollowCameraControllerPlugin, FollowFrame},
+    pan_orbit_camera::OrbitCameraController,
+    top_down_camera::{TopDownCameraController, TopDownCameraControllerPlugin},
+    CameraMode,
+};
+use bevy::{ecs::world::Mut, prelude::*, render::camera::Camera};
 
-#[derive(Debug, PartialEq, Eq)]
+/// How far ahead a camera without an enabled controller is taken to look.
+const FALLBACK_FOCUS_DISTANCE: f32 = 100.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CameraModes {
     //     Orbiting: The camera rotates around a target object or point of interest. The camera's movement is constrained to a certain distance from the target and a fixed angle of inclination.
     Orbiting {
@@ -21,7 +32,9 @@ pub enum CameraModes {
         // pitch: f32,
     },
     // Third-person: The camera is positioned behind the player and follows the player's movements. The camera's movement is generally limited to a certain distance and angle from the player.
-    ThirdPerson {},
+    ThirdPerson {
+        target: Entity,
+    },
     // Top-down: The camera is positioned directly above the scene and provides a bird's-eye view of the action. The camera's movement is generally limited to panning and zooming.
     TopDown,
     // Cinematic: The camera is used to create a cinematic effect, such as a cutscene or dramatic reveal. The camera's movement is generally scripted and may include special effects such as depth of field or motion blur.
@@ -52,4 +65,176 @@ impl CameraMode for CameraModeImpl {
     fn unlock(&mut self) {
         self.locked = false
     }
+
+    fn mode(&self) -> &CameraModes {
+        &self.mode
+    }
+
+    fn set_mode(&mut self, mode: CameraModes) {
+        self.mode = mode;
+    }
+}
+
+/// Backs every [`CameraModes`] with a controller, and moves the cameras to the controller of the current mode when it
+/// changes, from where they are.
+/// The orbit controller comes from the [`OrbitCameraControllerPlugin`](crate::pan_orbit_camera::OrbitCameraControllerPlugin).
+#[derive(Default)]
+pub struct CameraModesPlugin<T: CameraMode>(pub T);
+
+impl<T: CameraMode + Send + Sync + 'static> Plugin for CameraModesPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<T>()
+            .add_plugins((
+                FirstPersonCameraControllerPlugin::<T>::default(),
+                TopDownCameraControllerPlugin::<T>::default(),
+                FollowCameraControllerPlugin::<T>::default(),
+                CinematicCameraControllerPlugin::<T>::default(),
+            ))
+            .add_systems(
+                PostUpdate,
+                Self::switch_controllers.run_if(resource_changed::<T>),
+            );
+    }
+}
+
+type ControlledCamera<'a> = (
+    Entity,
+    &'a Transform,
+    Option<&'a mut OrbitCameraController>,
+    Option<&'a mut FirstPersonCameraController>,
+    Option<&'a mut TopDownCameraController>,
+    Option<&'a mut FollowCameraController>,
+    Option<&'a mut CinematicCameraController>,
+);
+
+type WithController = (
+    With<Camera>,
+    Or<(
+        With<OrbitCameraController>,
+        With<FirstPersonCameraController>,
+        With<TopDownCameraController>,
+        With<FollowCameraController>,
+        With<CinematicCameraController>,
+    )>,
+);
+
+impl<T: CameraMode> CameraModesPlugin<T> {
+    /// Enables the controller of the current mode on every camera with a controller, inserting it if the camera has
+    /// none yet, and disables the others. The new controller starts from the pose of the one it replaces.
+    pub fn switch_controllers(
+        mut commands: Commands,
+        mode: Res<T>,
+        mut last_mode: Local<Option<CameraModes>>,
+        targets: Query<&GlobalTransform>,
+        mut cameras: Query<ControlledCamera, WithController>,
+    ) {
+        // The resource also changes when it is locked or unlocked.
+        let mode = *mode.mode();
+        if last_mode.replace(mode) == Some(mode) {
+            return;
+        }
+
+        for (entity, transform, orbit, first_person, top_down, follow, cinematic) in
+            cameras.iter_mut()
+        {
+            let pose = [
+                enabled_pose(&orbit),
+                enabled_pose(&first_person),
+                enabled_pose(&top_down),
+                enabled_pose(&follow),
+                enabled_pose(&cinematic),
+            ]
+            .into_iter()
+            .flatten()
+            .next()
+            .unwrap_or_else(|| ViewPose::from_transform(*transform, FALLBACK_FOCUS_DISTANCE));
+
+            let mut orbit = disable(orbit);
+            let mut first_person = disable(first_person);
+            let mut top_down = disable(top_down);
+            let mut follow = disable(follow);
+            let mut cinematic = disable(cinematic);
+
+            match mode {
+                CameraModes::Orbiting { target } => {
+                    let mut pose = pose;
+                    if let Some(target) = target.and_then(|target| targets.get(target).ok()) {
+                        pose.focus = target.translation();
+                    }
+                    enable(&mut commands, entity, orbit.as_mut(), &pose, || {
+                        OrbitCameraController::default()
+                    });
+                }
+                CameraModes::FirstPerson {} => {
+                    enable(&mut commands, entity, first_person.as_mut(), &pose, || {
+                        FirstPersonCameraController::default()
+                    });
+                }
+                CameraModes::TopDown => {
+                    enable(&mut commands, entity, top_down.as_mut(), &pose, || {
+                        TopDownCameraController::default()
+                    });
+                }
+                CameraModes::Following { target } | CameraModes::ThirdPerson { target } => {
+                    let frame = match mode {
+                        CameraModes::ThirdPerson { .. } => FollowFrame::Target,
+                        _ => FollowFrame::World,
+                    };
+                    if let Some(follow) = follow.as_mut() {
+                        follow.target = target;
+                        follow.frame = frame;
+                    }
+                    enable(&mut commands, entity, follow.as_mut(), &pose, || {
+                        let mut follow = FollowCameraController::following(target, Vec3::ZERO);
+                        follow.frame = frame;
+                        follow
+                    });
+                }
+                // Holds the camera where it is until a path is played.
+                CameraModes::Cinematic => {
+                    enable(&mut commands, entity, cinematic.as_mut(), &pose, || {
+                        CinematicCameraController::default()
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn enabled_pose<C: CameraController>(controller: &Option<Mut<C>>) -> Option<ViewPose> {
+    controller
+        .as_deref()
+        .filter(|controller| controller.is_enabled())
+        .map(C::view_pose)
+}
+
+fn disable<C: CameraController>(controller: Option<Mut<C>>) -> Option<Mut<C>> {
+    controller.map(|mut controller| {
+        if controller.is_enabled() {
+            controller.set_enabled(false);
+        }
+        controller
+    })
+}
+
+/// Moves `controller` to `pose` and enables it, or inserts a new one there if the camera has none.
+fn enable<C: CameraController>(
+    commands: &mut Commands,
+    entity: Entity,
+    controller: Option<&mut Mut<C>>,
+    pose: &ViewPose,
+    new: impl FnOnce() -> C,
+) {
+    match controller {
+        Some(controller) => {
+            controller.set_view_pose(pose);
+            controller.set_enabled(true);
+        }
+        None => {
+            let mut controller = new();
+            controller.set_view_pose(pose);
+            controller.set_enabled(true);
+            commands.entity(entity).insert(controller);
+        }
+    }
 }
@@ -1,4 +1,10 @@
-use crate::api::{CameraController, CameraMode};
+use crate::{
+    api::{
+        is_wheel_captured, CameraController, CameraMode, IgnoreInput, ViewPose, WheelCapturingNodes,
+    },
+    bookmarks::CameraBookmarksPlugin,
+    framing::CameraFramingPlugin,
+};
 use bevy::{
     input::{
         keyboard::KeyboardInput,
@@ -11,9 +17,10 @@ use bevy::{
     render::camera::Camera,
 };
 use bevy_mod_picking::{
-    pointer::{InputMove},
+    pointer::InputMove,
     prelude::{Click, Down, Drag, DragEnd, DragStart, Pointer, Up},
 };
+use serde::{Deserialize, Serialize};
 use std::ops::RangeInclusive;
 
 #[derive(Default)]
@@ -21,7 +28,8 @@ pub struct OrbitCameraControllerPlugin<T: CameraMode>(pub T);
 
 impl<T: CameraMode + Send + Sync + 'static> Plugin for OrbitCameraControllerPlugin<T> {
     fn build(&self, app: &mut App) {
-        app.add_systems(First, Self::init_camera_state)
+        app.add_plugins((CameraFramingPlugin, CameraBookmarksPlugin))
+            .add_systems(First, Self::init_camera_state)
             .add_event::<OrbitCameraControllerEvents>()
             .add_systems(
                 PostUpdate,
@@ -39,20 +47,30 @@ impl<T: CameraMode + Send + Sync + 'static> Plugin for OrbitCameraControllerPlug
             .add_systems(
                 Last,
                 (
-                    Self::consume_pan_and_orbit_events,
-                    Self::consume_zoom_events,
-                    Self::consume_recentre_events,
+                    (
+                        Self::consume_pan_and_orbit_events,
+                        Self::consume_zoom_events,
+                        Self::consume_recentre_events,
+                    )
+                        .run_if(on_event::<OrbitCameraControllerEvents>()),
+                    // The camera keeps moving after the input stops, until it settles on its target.
+                    Self::update_camera_state,
                     Self::update_camera_transform_system,
                 )
-                    .chain()
-                    .run_if(on_event::<OrbitCameraControllerEvents>()),
+                    .chain(),
             );
     }
 }
 
-const LINE_TO_PIXEL_RATIO: f32 = 0.1;
+pub(crate) const LINE_TO_PIXEL_RATIO: f32 = 0.1;
+
+/// The closest the camera gets to its centre.
+const MIN_RADIUS: f32 = 0.1;
 
-fn run_criteria<T: CameraMode>(mode: Res<T>) -> bool {
+/// Below this, velocities and the distance between the pose and its target are taken as zero.
+const REST_EPSILON: f32 = 1e-4;
+
+pub(crate) fn run_criteria<T: CameraMode>(mode: Res<T>) -> bool {
     !(*mode).is_locked()
 }
 
@@ -62,6 +80,105 @@ pub enum OrbitCameraControllerEvents {
     Pan(Vec2),
     Zoom(f32),
     Recentre(Vec3),
+    /// Moves the centre to `centre` and the camera to `radius` from it over `duration` seconds, keeping its angles.
+    FocusOn {
+        centre: Vec3,
+        radius: f32,
+        duration: f32,
+    },
+}
+
+/// The position of an [`OrbitCameraController`]: its spherical coordinates around its centre.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrbitPose {
+    #[serde(rename = "theta")]
+    pub θ: f32,
+    #[serde(rename = "psi")]
+    pub ψ: f32,
+    #[serde(rename = "rho")]
+    pub ρ: f32,
+    pub center: Vec3,
+}
+
+impl OrbitPose {
+    /// The pose of a camera at the place and looking at the focus of `pose`. The roll of `pose` is dropped.
+    pub fn from_view_pose(pose: &ViewPose) -> OrbitPose {
+        let offset = pose.transform.translation - pose.focus;
+        // The inverse of `transform`, whose offset from the centre is (-sin θ sin ψ, cos θ, -sin θ cos ψ) ρ.
+        let direction = offset.try_normalize().unwrap_or(Vec3::Y);
+        OrbitPose {
+            θ: f32::acos(direction.y.clamp(-1., 1.)),
+            ψ: f32::atan2(-direction.x, -direction.z),
+            ρ: offset.length().max(MIN_RADIUS),
+            center: pose.focus,
+        }
+    }
+
+    /// The transform of a camera at this pose, looking at the centre.
+    pub fn transform(&self) -> Transform {
+        let rot = Quat::from_axis_angle(Vec3::Y, self.ψ) * Quat::from_axis_angle(-Vec3::X, self.θ);
+        Transform::from_translation((rot * Vec3::Y) * self.ρ + self.center)
+            .looking_at(self.center, Vec3::Y)
+    }
+
+    /// The same pose, with ψ moved by whole turns to within half a turn of `other`, so that interpolating between
+    /// them takes the short way round.
+    pub fn unwrapped_towards(&self, other: &OrbitPose) -> OrbitPose {
+        let turn = 2. * std::f32::consts::PI;
+        OrbitPose {
+            ψ: self.ψ - turn * ((self.ψ - other.ψ) / turn).round(),
+            ..*self
+        }
+    }
+
+    pub fn lerp(&self, other: &OrbitPose, s: f32) -> OrbitPose {
+        OrbitPose {
+            θ: self.θ + (other.θ - self.θ) * s,
+            ψ: self.ψ + (other.ψ - self.ψ) * s,
+            ρ: self.ρ + (other.ρ - self.ρ) * s,
+            center: self.center.lerp(other.center, s),
+        }
+    }
+
+    fn abs_diff_eq(&self, other: &OrbitPose, max_abs_diff: f32) -> bool {
+        (self.θ - other.θ).abs() <= max_abs_diff
+            && (self.ψ - other.ψ).abs() <= max_abs_diff
+            && (self.ρ - other.ρ).abs() <= max_abs_diff
+            && self.center.abs_diff_eq(other.center, max_abs_diff)
+    }
+}
+
+/// The motion an [`OrbitCameraController`] keeps after the mouse is released.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrbitVelocity {
+    /// Of ψ and θ, in radians per second.
+    pub orbit: Vec2,
+    /// Of the centre, in world units per second.
+    pub pan: Vec3,
+}
+
+/// An animated move of an [`OrbitCameraController`], eased in and out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitTransition {
+    pub from: OrbitPose,
+    pub to: OrbitPose,
+    /// In seconds.
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+impl OrbitTransition {
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn pose(&self) -> OrbitPose {
+        if self.is_finished() {
+            return self.to;
+        }
+        let s = self.elapsed / self.duration;
+        self.from.lerp(&self.to, s * s * (3. - 2. * s))
+    }
 }
 
 #[derive(Component)]
@@ -79,20 +196,36 @@ pub struct OrbitCameraController {
     pub rotate_button: MouseButton,
     pub pan_button: MouseButton,
     pub enabled: bool,
+    /// The pose the camera moves towards. Input and transitions move the target, and θ, ψ, ρ and the centre follow it.
+    pub target: OrbitPose,
+    /// How quickly the camera catches up with its target: each second, it covers all but `exp(-damping)` of the
+    /// remaining way. `f32::INFINITY` disables the smoothing.
+    pub damping: f32,
+    /// How quickly the camera stops once the mouse is released: each second, its velocity is multiplied by
+    /// `exp(-inertia_decay)`. `f32::INFINITY` disables the inertia.
+    pub inertia_decay: f32,
+    pub velocity: OrbitVelocity,
+    pub transition: Option<OrbitTransition>,
 }
 
 // There are many conventions on ψ and θ: we'll take the default one from Riley, Hobson, Bence
 impl Default for OrbitCameraController {
     fn default() -> Self {
-        OrbitCameraController {
+        let pose = OrbitPose {
             // The initial rotation around the Y axis (in radians). 0.0 means facing directly towards the north or forward direction.
             θ: 0.0,
             ψ: std::f32::consts::FRAC_PI_2,
-            θ_range: 0.01..=std::f32::consts::PI,
-            ψ_range: 0.01..=2. * std::f32::consts::PI,
             ρ: 100.,
             // The point in world space that the camera orbits around. Vec3::ZERO is the origin (0, 0, 0).
             center: Vec3::ZERO,
+        };
+        OrbitCameraController {
+            θ: pose.θ,
+            ψ: pose.ψ,
+            θ_range: 0.01..=std::f32::consts::PI,
+            ψ_range: 0.01..=2. * std::f32::consts::PI,
+            ρ: pose.ρ,
+            center: pose.center,
             // Sensitivity of the camera rotation. Lower values make the camera rotate slower.
             rotate_sensitivity: 0.4,
             // Sensitivity of the camera panning. Lower values make the camera pan slower.
@@ -107,16 +240,161 @@ impl Default for OrbitCameraController {
             pan_button: MouseButton::Right,
             // Whether the camera controller is enabled. If false, the camera won't respond to input.
             enabled: true,
+            target: pose,
+            // The camera covers about 95% of the way to its target in a quarter of a second.
+            damping: 12.,
+            // The camera coasts for about half a second after the mouse is released.
+            inertia_decay: 6.,
+            velocity: OrbitVelocity::default(),
+            transition: None,
         }
     }
 }
 
 impl OrbitCameraController {
     pub fn new(dist: f32, center: Vec3) -> OrbitCameraController {
-        OrbitCameraController {
+        let mut camera = Self::default();
+        camera.set_pose(OrbitPose {
             ρ: dist,
             center,
-            ..Self::default()
+            ..camera.pose()
+        });
+        camera.target = camera.pose();
+        camera
+    }
+
+    /// Where the camera is, which lags behind its [`target`](Self::target).
+    pub fn pose(&self) -> OrbitPose {
+        let &Self {
+            θ, ψ, ρ, center, ..
+        } = self;
+        OrbitPose { θ, ψ, ρ, center }
+    }
+
+    pub fn set_pose(&mut self, pose: OrbitPose) {
+        let OrbitPose { θ, ψ, ρ, center } = pose;
+        self.θ = θ;
+        self.ψ = ψ;
+        self.ρ = ρ;
+        self.center = center;
+    }
+
+    /// The transform of a camera at the current pose, looking at the centre.
+    pub fn transform(&self) -> Transform {
+        self.pose().transform()
+    }
+
+    /// Whether [`update`](Self::update) would leave the camera as it is.
+    pub fn is_at_rest(&self) -> bool {
+        self.transition.is_none()
+            && self.velocity == OrbitVelocity::default()
+            && self.pose() == self.target
+    }
+
+    /// Spins the camera by a pointer motion of `delta` pixels per frame, until the next motion or the inertia stops it.
+    pub fn orbit(&mut self, delta: Vec2) {
+        self.transition = None;
+        self.velocity.orbit = -delta * self.rotate_sensitivity;
+    }
+
+    /// Slides the centre across the view by a pointer motion of `delta` pixels per frame, like [`orbit`](Self::orbit).
+    pub fn pan(&mut self, delta: Vec2) {
+        self.transition = None;
+        let rotation = self.transform().rotation;
+        let right_dir = rotation * -Vec3::X;
+        let up_dir = rotation * Vec3::Y;
+        self.velocity.pan = (delta.x * right_dir + delta.y * up_dir) * self.pan_sensitivity;
+    }
+
+    pub fn zoom(&mut self, delta: f32) {
+        self.transition = None;
+        self.target.ρ = f32::clamp(
+            self.target.ρ + self.zoom_sensitivity * delta,
+            MIN_RADIUS,
+            f32::MAX,
+        );
+    }
+
+    pub fn recentre(&mut self, dr: Vec3) {
+        self.transition = None;
+        self.target.center += dr;
+    }
+
+    /// Starts a transition to `centre`, with the camera `radius` away from it. Input cancels the transition where
+    /// it is.
+    pub fn focus_on(&mut self, centre: Vec3, radius: f32, duration: f32) {
+        self.transition_to(
+            OrbitPose {
+                ρ: radius,
+                center: centre,
+                ..self.target
+            },
+            duration,
+        );
+    }
+
+    /// Starts a transition to `pose`, the short way round. Input cancels the transition where it is. Without a
+    /// duration, the camera jumps there.
+    pub fn transition_to(&mut self, pose: OrbitPose, duration: f32) {
+        let from = self.pose();
+        let to = OrbitPose {
+            θ: pose.θ.clamp(*self.θ_range.start(), *self.θ_range.end()),
+            ρ: pose.ρ.max(MIN_RADIUS),
+            ..pose.unwrapped_towards(&from)
+        };
+        self.velocity = OrbitVelocity::default();
+        if duration > 0. {
+            self.transition = Some(OrbitTransition {
+                from,
+                to,
+                duration,
+                elapsed: 0.,
+            });
+        } else {
+            self.transition = None;
+            self.target = to;
+            self.set_pose(to);
+        }
+    }
+
+    /// Advances the camera by `dt` seconds: along its transition if it has one, otherwise the velocity moves the
+    /// target and the pose eases towards it.
+    pub fn update(&mut self, dt: f32) {
+        if dt <= 0. {
+            return;
+        }
+
+        if let Some(transition) = &mut self.transition {
+            transition.elapsed += dt;
+            let pose = transition.pose();
+            if transition.is_finished() {
+                self.transition = None;
+            }
+            self.target = pose;
+            self.set_pose(pose);
+            return;
+        }
+
+        self.target.ψ += self.velocity.orbit.x * dt;
+        self.target.θ = (self.target.θ + self.velocity.orbit.y * dt)
+            .clamp(*self.θ_range.start(), *self.θ_range.end());
+        self.target.center += self.velocity.pan * dt;
+
+        let decay = f32::exp(-self.inertia_decay * dt);
+        self.velocity.orbit *= decay;
+        self.velocity.pan *= decay;
+        if self.velocity.orbit.length() < REST_EPSILON && self.velocity.pan.length() < REST_EPSILON
+        {
+            self.velocity = OrbitVelocity::default();
+        }
+
+        let pose = self
+            .pose()
+            .lerp(&self.target, 1. - f32::exp(-self.damping * dt));
+        if pose.abs_diff_eq(&self.target, REST_EPSILON) {
+            self.set_pose(self.target);
+        } else {
+            self.set_pose(pose);
         }
     }
     pub fn ρ_basis_vector(&self) -> Vec3 {
@@ -157,13 +435,38 @@ impl CameraController for OrbitCameraController {
     ) {
         for (camera, mut transform) in query.iter_mut() {
             if camera.enabled {
-                let rot = Quat::from_axis_angle(Vec3::Y, camera.ψ)
-                    * Quat::from_axis_angle(-Vec3::X, camera.θ);
-                transform.translation = (rot * Vec3::Y) * camera.ρ + camera.center;
-                transform.look_at(camera.center, Vec3::Y);
+                let orbit = camera.transform();
+                transform.translation = orbit.translation;
+                transform.rotation = orbit.rotation;
             }
         }
     }
+
+    fn view_pose(&self) -> ViewPose {
+        ViewPose {
+            transform: self.transform(),
+            focus: self.center,
+        }
+    }
+
+    fn set_view_pose(&mut self, pose: &ViewPose) {
+        let pose = OrbitPose::from_view_pose(pose);
+        self.set_pose(OrbitPose {
+            θ: pose.θ.clamp(*self.θ_range.start(), *self.θ_range.end()),
+            ..pose
+        });
+        self.target = self.pose();
+        self.velocity = OrbitVelocity::default();
+        self.transition = None;
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
 }
 
 impl<T: CameraMode> OrbitCameraControllerPlugin<T> {
@@ -184,111 +487,126 @@ impl<T: CameraMode> OrbitCameraControllerPlugin<T> {
         mut events: EventWriter<OrbitCameraControllerEvents>,
         mut pointer_motion_events: EventReader<InputMove>,
         pointer_button_input: Res<ButtonInput<MouseButton>>,
-        mut query: Query<&OrbitCameraController>,
+        query: Query<&OrbitCameraController, Without<IgnoreInput>>,
     ) {
         let mut delta = Vec2::ZERO;
         for event in pointer_motion_events.read() {
             delta += event.delta;
         }
-        for camera in query.iter_mut() {
-            if camera.enabled {
-                if pointer_button_input.pressed(camera.rotate_button) {
-                    events.send(OrbitCameraControllerEvents::Orbit(delta));
-                }
+        // One event per input, which every camera taking input consumes: the buttons are those of the first one.
+        let Some(camera) = query.iter().find(|camera| camera.enabled) else {
+            return;
+        };
+        if pointer_button_input.pressed(camera.rotate_button) {
+            events.send(OrbitCameraControllerEvents::Orbit(delta));
+        }
 
-                if pointer_button_input.pressed(camera.pan_button) {
-                    events.send(OrbitCameraControllerEvents::Pan(delta));
-                }
-            }
+        if pointer_button_input.pressed(camera.pan_button) {
+            events.send(OrbitCameraControllerEvents::Pan(delta));
         }
     }
 
     pub fn emit_keyboard_events(
         // Input
         mut keyboard_presses: EventReader<KeyboardInput>,
+        keyboard: Res<ButtonInput<KeyCode>>,
         // Output
         mut camera_cmd_events: EventWriter<OrbitCameraControllerEvents>,
-        mut query: Query<&OrbitCameraController>,
+        query: Query<&OrbitCameraController, Without<IgnoreInput>>,
     ) {
-        for camera in query.iter_mut() {
-            if camera.enabled {
-                for kbd in keyboard_presses.read() {
-                    match kbd.key_code {
-                        KeyCode::ArrowDown | KeyCode::KeyS => {
-                            camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
-                                -camera.change_centre_sensitivity * camera.ψ_basis_vector(),
-                            ));
-                        }
-                        KeyCode::ArrowUp | KeyCode::KeyW => {
-                            camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
-                                camera.change_centre_sensitivity * camera.ψ_basis_vector(),
-                            ));
-                        }
-                        KeyCode::ArrowLeft | KeyCode::KeyA => {
-                            camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
-                                -camera.change_centre_sensitivity * camera.θ_basis_vector(),
-                            ));
-                        }
-                        KeyCode::ArrowRight | KeyCode::KeyD => {
-                            camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
-                                camera.change_centre_sensitivity * camera.θ_basis_vector(),
-                            ));
-                        }
-                        _ => {}
-                    }
+        let presses: Vec<_> = keyboard_presses.read().collect();
+        // Leave shortcuts such as Ctrl+S to the app.
+        let modifiers = [
+            KeyCode::ControlLeft,
+            KeyCode::ControlRight,
+            KeyCode::SuperLeft,
+            KeyCode::SuperRight,
+        ];
+        if keyboard.any_pressed(modifiers) {
+            return;
+        }
+        let Some(camera) = query.iter().find(|camera| camera.enabled) else {
+            return;
+        };
+        for kbd in presses {
+            match kbd.key_code {
+                KeyCode::ArrowDown | KeyCode::KeyS => {
+                    camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
+                        -camera.change_centre_sensitivity * camera.ψ_basis_vector(),
+                    ));
+                }
+                KeyCode::ArrowUp | KeyCode::KeyW => {
+                    camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
+                        camera.change_centre_sensitivity * camera.ψ_basis_vector(),
+                    ));
+                }
+                KeyCode::ArrowLeft | KeyCode::KeyA => {
+                    camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
+                        -camera.change_centre_sensitivity * camera.θ_basis_vector(),
+                    ));
                 }
+                KeyCode::ArrowRight | KeyCode::KeyD => {
+                    camera_cmd_events.send(OrbitCameraControllerEvents::Recentre(
+                        camera.change_centre_sensitivity * camera.θ_basis_vector(),
+                    ));
+                }
+                _ => {}
             }
         }
     }
 
     pub fn consume_pan_and_orbit_events(
-        time: Res<Time>,
         mut events: EventReader<OrbitCameraControllerEvents>,
-        mut query: Query<(&mut OrbitCameraController, &mut Transform, &mut Camera)>,
+        mut query: Query<&mut OrbitCameraController, Without<IgnoreInput>>,
     ) {
-        for (mut camera, transform, _) in query.iter_mut() {
+        // Read once for all the cameras: a second `read` would see none of them.
+        let events: Vec<_> = events.read().collect();
+        for mut camera in query.iter_mut() {
             if !camera.enabled {
                 continue;
             }
 
-            for event in events.read() {
+            for event in &events {
                 match event {
-                    OrbitCameraControllerEvents::Orbit(delta) => {
-                        camera.ψ -= delta.x * camera.rotate_sensitivity * time.delta_seconds();
-                        camera.θ -= delta.y * camera.rotate_sensitivity * time.delta_seconds();
-                        camera.θ = camera
-                            .θ
-                            .max(*camera.θ_range.start())
-                            .min(*camera.θ_range.end());
-                    }
-                    OrbitCameraControllerEvents::Pan(delta) => {
-                        let right_dir = transform.rotation * -Vec3::X;
-                        let up_dir = transform.rotation * Vec3::Y;
-                        let pan_vector = (delta.x * right_dir + delta.y * up_dir)
-                            * camera.pan_sensitivity
-                            * time.delta_seconds();
-                        camera.center += pan_vector;
-                    }
+                    OrbitCameraControllerEvents::Orbit(delta) => camera.orbit(*delta),
+                    OrbitCameraControllerEvents::Pan(delta) => camera.pan(*delta),
                     _ => {}
                 }
             }
         }
     }
 
+    pub fn update_camera_state(
+        time: Res<Time>,
+        mut query: Query<&mut OrbitCameraController, With<Camera>>,
+    ) {
+        for mut camera in query.iter_mut() {
+            // Settled cameras aren't touched, so that their transform isn't recomputed.
+            if camera.enabled && !camera.is_at_rest() {
+                camera.update(time.delta_seconds());
+            }
+        }
+    }
+
     pub fn consume_recentre_events(
         mut events: EventReader<OrbitCameraControllerEvents>,
-        mut query: Query<(&mut OrbitCameraController, &mut Transform, &mut Camera)>,
+        mut query: Query<&mut OrbitCameraController, Without<IgnoreInput>>,
     ) {
-        for (mut camera, transform, _) in query.iter_mut() {
+        // Read once for all the cameras: a second `read` would see none of them.
+        let events: Vec<_> = events.read().collect();
+        for mut camera in query.iter_mut() {
             if !camera.enabled {
                 continue;
             }
 
-            for event in events.read() {
+            for event in &events {
                 match event {
-                    OrbitCameraControllerEvents::Recentre(dr) => {
-                        camera.center += *dr;
-                    }
+                    OrbitCameraControllerEvents::Recentre(dr) => camera.recentre(*dr),
+                    OrbitCameraControllerEvents::FocusOn {
+                        centre,
+                        radius,
+                        duration,
+                    } => camera.focus_on(*centre, *radius, *duration),
 
                     _ => {}
                 }
@@ -299,8 +617,13 @@ impl<T: CameraMode> OrbitCameraControllerPlugin<T> {
     pub fn emit_zoom_events(
         mut events: EventWriter<OrbitCameraControllerEvents>,
         mut mouse_wheel_events: EventReader<MouseWheel>,
-        mut query: Query<&OrbitCameraController>,
+        query: Query<&OrbitCameraController, Without<IgnoreInput>>,
+        wheel_capturing_nodes: WheelCapturingNodes,
     ) {
+        if is_wheel_captured(&wheel_capturing_nodes) {
+            mouse_wheel_events.clear();
+            return;
+        }
         let mut total = 0.0;
         for event in mouse_wheel_events.read() {
             total += event.y
@@ -310,28 +633,146 @@ impl<T: CameraMode> OrbitCameraControllerPlugin<T> {
                 };
         }
 
-        if total != 0.0 {
-            for camera in query.iter_mut() {
-                if camera.enabled {
-                    events.send(OrbitCameraControllerEvents::Zoom(total));
-                }
-            }
+        // One event, which every camera taking input consumes.
+        if total != 0.0 && query.iter().any(|camera| camera.enabled) {
+            events.send(OrbitCameraControllerEvents::Zoom(total));
         }
     }
 
     pub fn consume_zoom_events(
-        mut query: Query<&mut OrbitCameraController, With<Camera>>,
+        mut query: Query<&mut OrbitCameraController, (With<Camera>, Without<IgnoreInput>)>,
         mut events: EventReader<OrbitCameraControllerEvents>,
     ) {
+        // Read once for all the cameras: a second `read` would see none of them.
+        let events: Vec<_> = events.read().collect();
         for mut camera in query.iter_mut() {
-            for event in events.read() {
-                if camera.enabled {
-                    if let OrbitCameraControllerEvents::Zoom(ρ) = event {
-                        camera.ρ += camera.zoom_sensitivity * (*ρ);
-                        camera.ρ = f32::clamp(camera.ρ, 0.1, f32::MAX)
-                    }
+            if !camera.enabled {
+                continue;
+            }
+            for event in &events {
+                if let OrbitCameraControllerEvents::Zoom(ρ) = event {
+                    camera.zoom(*ρ);
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const FRAME: f32 = 1. / 60.;
+
+    fn step(camera: &mut OrbitCameraController, frames: usize) {
+        for _ in 0..frames {
+            camera.update(FRAME);
+        }
+    }
+
+    #[test]
+    fn pose_converges_exponentially() {
+        let mut camera = OrbitCameraController::new(10., Vec3::ZERO);
+        camera.target.ρ = 20.;
+        camera.target.center = Vec3::new(0., 5., 0.);
+        step(&mut camera, 6);
+
+        let remaining = f32::exp(-camera.damping * 6. * FRAME);
+        assert!((camera.ρ - (20. - 10. * remaining)).abs() < 1e-4);
+        assert!((camera.center.y - (5. - 5. * remaining)).abs() < 1e-4);
+
+        step(&mut camera, 120);
+        assert_eq!(camera.pose(), camera.target);
+        assert!(camera.is_at_rest());
+    }
+
+    #[test]
+    fn inertia_decays_then_stops() {
+        let mut camera = OrbitCameraController::new(10., Vec3::ZERO);
+        camera.velocity.orbit = Vec2::new(1., 0.);
+        step(&mut camera, 6);
+        let decayed = f32::exp(-camera.inertia_decay * 6. * FRAME);
+        assert!((camera.velocity.orbit.x - decayed).abs() < 1e-5);
+        assert!(camera.target.ψ > camera.ψ);
+
+        // 1 rad/s falls below `REST_EPSILON` after about ln(1e4) / 6 ≈ 1.5 s.
+        step(&mut camera, 120);
+        assert_eq!(camera.velocity, OrbitVelocity::default());
+        step(&mut camera, 120);
+        assert!(camera.is_at_rest());
+    }
+
+    #[test]
+    fn focus_on_arrives_at_the_duration() {
+        let mut camera = OrbitCameraController::new(10., Vec3::ZERO);
+        let centre = Vec3::new(1., 2., 3.);
+        camera.focus_on(centre, 30., 0.5);
+
+        step(&mut camera, 15);
+        assert!(camera.transition.is_some());
+        assert!(camera.ρ > 10. && camera.ρ < 30.);
+
+        step(&mut camera, 15);
+        assert!(camera.transition.is_none());
+        assert_eq!(camera.center, centre);
+        assert_eq!(camera.ρ, 30.);
+        assert_eq!(camera.pose(), camera.target);
+    }
+
+    #[test]
+    fn focus_on_without_a_duration_jumps() {
+        let mut camera = OrbitCameraController::new(10., Vec3::ZERO);
+        let centre = Vec3::new(1., 2., 3.);
+        camera.focus_on(centre, 30., 0.);
+        assert!(camera.is_at_rest());
+        assert_eq!(camera.center, centre);
+        assert_eq!(camera.ρ, 30.);
+    }
+
+    #[test]
+    fn transitions_take_the_short_way_round() {
+        let mut camera = OrbitCameraController::new(10., Vec3::ZERO);
+        camera.set_pose(OrbitPose {
+            ψ: 0.1,
+            ..camera.pose()
+        });
+        camera.transition_to(
+            OrbitPose {
+                ψ: 2. * PI - 0.1,
+                ..camera.pose()
+            },
+            1.,
+        );
+        for _ in 0..60 {
+            camera.update(FRAME);
+            assert!(camera.ψ.abs() <= 0.1 + 1e-6, "ψ went round to {}", camera.ψ);
+        }
+        assert!((camera.ψ + 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn θ_stays_within_its_range() {
+        let mut camera = OrbitCameraController::new(10., Vec3::ZERO);
+        let range = camera.θ_range.clone();
+        camera.velocity.orbit = Vec2::new(0., 100.);
+        for _ in 0..120 {
+            camera.update(FRAME);
+            assert!(range.contains(&camera.target.θ) && range.contains(&camera.θ));
+        }
+        assert_eq!(camera.θ, *range.end());
+
+        camera.velocity.orbit = Vec2::new(0., -100.);
+        step(&mut camera, 120);
+        assert_eq!(camera.θ, *range.start());
+
+        camera.transition_to(
+            OrbitPose {
+                θ: 4.,
+                ..camera.pose()
+            },
+            0.,
+        );
+        assert_eq!(camera.θ, *range.end());
+    }
+}
@@ -0,0 +1,296 @@
+//! Several cameras in the primary window: full-window, side by side, stacked or as picture-in-picture insets.
+//! Only the camera under the cursor reacts to input, and a hotkey cycles the active camera.
+//!
+//! To compare two structures side by side, give each camera and its structure their own `RenderLayers`.
+
+use crate::api::{CameraRig, IgnoreInput};
+use bevy::{
+    prelude::*,
+    render::camera::{Camera, CameraUpdateSystem, ClearColorConfig, Viewport},
+    window::PrimaryWindow,
+};
+
+/// How the cameras of a [`DefaultCameraRig`] share the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RigLayout {
+    /// The active camera fills the window, and the others aren't drawn.
+    #[default]
+    Single,
+    /// In columns of equal width, in the order the cameras were added.
+    SideBySide,
+    /// In rows of equal height, in the order the cameras were added.
+    Stacked,
+    /// The active camera fills the window, and the others are insets down its right edge.
+    PictureInPicture {
+        /// The size of the insets, as a fraction of the window.
+        inset_size: f32,
+    },
+}
+
+/// Gap between the insets of [`RigLayout::PictureInPicture`] and the edges of the window, in physical pixels.
+const INSET_MARGIN: u32 = 16;
+
+/// The rig takes over the viewport, order, activity and clear colour of its cameras: cameras drawn over others
+/// don't clear the window.
+#[derive(Resource, Debug)]
+pub struct DefaultCameraRig {
+    cameras: Vec<Entity>,
+    active: usize,
+    pub layout: RigLayout,
+    /// Cycles the active camera.
+    pub cycle_key: KeyCode,
+}
+
+impl Default for DefaultCameraRig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DefaultCameraRig {
+    pub fn new() -> Self {
+        Self {
+            cameras: Vec::new(),
+            active: 0,
+            layout: RigLayout::default(),
+            cycle_key: KeyCode::Tab,
+        }
+    }
+
+    pub fn with_layout(mut self, layout: RigLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_cycle_key(mut self, cycle_key: KeyCode) -> Self {
+        self.cycle_key = cycle_key;
+        self
+    }
+
+    pub fn set_active_camera(&mut self, camera: Entity) {
+        if let Some(index) = self.cameras.iter().position(|&entity| entity == camera) {
+            self.active = index;
+        }
+    }
+
+    /// The viewport of each camera, `None` for the whole window, in the order they are drawn.
+    fn viewports(&self, window_size: UVec2) -> Vec<(Entity, Option<Viewport>)> {
+        let viewport = |physical_position, physical_size| Viewport {
+            physical_position,
+            physical_size,
+            ..default()
+        };
+        let count = self.cameras.len() as u32;
+        let active = self.active_camera();
+
+        match self.layout {
+            RigLayout::Single => active.map(|camera| (camera, None)).into_iter().collect(),
+            RigLayout::SideBySide => {
+                let width = window_size.x / count.max(1);
+                (self.cameras.iter().zip(0..))
+                    .map(|(&camera, i)| {
+                        let position = UVec2::new(i * width, 0);
+                        // The last column takes what is left of the rounding.
+                        let size = if i + 1 == count {
+                            UVec2::new(window_size.x - position.x, window_size.y)
+                        } else {
+                            UVec2::new(width, window_size.y)
+                        };
+                        (camera, Some(viewport(position, size)))
+                    })
+                    .collect()
+            }
+            RigLayout::Stacked => {
+                let height = window_size.y / count.max(1);
+                (self.cameras.iter().zip(0..))
+                    .map(|(&camera, i)| {
+                        let position = UVec2::new(0, i * height);
+                        let size = if i + 1 == count {
+                            UVec2::new(window_size.x, window_size.y - position.y)
+                        } else {
+                            UVec2::new(window_size.x, height)
+                        };
+                        (camera, Some(viewport(position, size)))
+                    })
+                    .collect()
+            }
+            RigLayout::PictureInPicture { inset_size } => {
+                let size = (window_size.as_vec2() * inset_size.clamp(0., 1.)).as_uvec2();
+                let insets = self
+                    .cameras
+                    .iter()
+                    .filter(|&&camera| Some(camera) != active)
+                    .zip(0..)
+                    .map(|(&camera, i)| {
+                        let position = UVec2::new(
+                            window_size.x.saturating_sub(size.x + INSET_MARGIN),
+                            INSET_MARGIN + i * (size.y + INSET_MARGIN),
+                        );
+                        (camera, Some(viewport(position, size)))
+                    })
+                    // Insets that don't fit in the window aren't drawn.
+                    .filter(|(_, viewport)| {
+                        viewport.as_ref().is_some_and(|viewport| {
+                            viewport.physical_position.y + viewport.physical_size.y <= window_size.y
+                        })
+                    });
+                active
+                    .map(|camera| (camera, None))
+                    .into_iter()
+                    .chain(insets)
+                    .collect()
+            }
+        }
+    }
+}
+
+impl CameraRig for DefaultCameraRig {
+    fn add_camera(&mut self, camera: Entity) {
+        if !self.cameras.contains(&camera) {
+            self.cameras.push(camera);
+        }
+    }
+
+    fn remove_camera(&mut self, camera: Entity) {
+        if let Some(index) = self.cameras.iter().position(|&entity| entity == camera) {
+            self.cameras.remove(index);
+            if self.active > index || self.active >= self.cameras.len() {
+                self.active = self.active.saturating_sub(1);
+            }
+        }
+    }
+
+    fn cameras(&self) -> &[Entity] {
+        &self.cameras
+    }
+
+    fn active_camera(&self) -> Option<Entity> {
+        self.cameras.get(self.active).copied()
+    }
+
+    fn cycle_active_camera(&mut self) {
+        if !self.cameras.is_empty() {
+            self.active = (self.active + 1) % self.cameras.len();
+        }
+    }
+
+    fn update(&mut self, window_size: UVec2, cameras: &mut Query<&mut Camera>) {
+        let viewports = self.viewports(window_size);
+        for &entity in &self.cameras {
+            let Ok(mut camera) = cameras.get_mut(entity) else {
+                continue;
+            };
+            let drawn = viewports
+                .iter()
+                .zip(0..)
+                .find(|((camera, _), _)| *camera == entity);
+
+            // Only what changes is written, so that the projections aren't recomputed every frame.
+            let is_active = drawn.is_some();
+            if camera.is_active != is_active {
+                camera.is_active = is_active;
+            }
+            let Some(((_, viewport), order)) = drawn else {
+                continue;
+            };
+            let same_viewport = match (&camera.viewport, viewport) {
+                (None, None) => true,
+                (Some(current), Some(viewport)) => {
+                    current.physical_position == viewport.physical_position
+                        && current.physical_size == viewport.physical_size
+                }
+                _ => false,
+            };
+            if !same_viewport {
+                camera.viewport = viewport.clone();
+            }
+            if camera.order != order {
+                camera.order = order;
+                camera.clear_color = if order == 0 {
+                    ClearColorConfig::Default
+                } else {
+                    ClearColorConfig::None
+                };
+            }
+        }
+    }
+}
+
+/// Lays out the cameras added to the [`DefaultCameraRig`], which can be inserted beforehand to configure it.
+#[derive(Default)]
+pub struct CameraRigPlugin;
+
+impl Plugin for CameraRigPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DefaultCameraRig>()
+            // Before the controllers read the input of the frame.
+            .add_systems(PreUpdate, Self::route_input)
+            .add_systems(Update, Self::cycle_active_camera)
+            .add_systems(PostUpdate, Self::layout_cameras.before(CameraUpdateSystem));
+    }
+}
+
+impl CameraRigPlugin {
+    pub fn cycle_active_camera(
+        keyboard: Res<ButtonInput<KeyCode>>,
+        mut rig: ResMut<DefaultCameraRig>,
+    ) {
+        if keyboard.just_pressed(rig.cycle_key) {
+            rig.cycle_active_camera();
+        }
+    }
+
+    pub fn layout_cameras(
+        mut rig: ResMut<DefaultCameraRig>,
+        mut removed_cameras: RemovedComponents<Camera>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        mut cameras: Query<&mut Camera>,
+    ) {
+        for camera in removed_cameras.read() {
+            rig.remove_camera(camera);
+        }
+        let Ok(window) = windows.get_single() else {
+            return;
+        };
+        rig.update(
+            UVec2::new(window.physical_width(), window.physical_height()),
+            &mut cameras,
+        );
+    }
+
+    /// Lets only the topmost camera under the cursor react to input, or the active camera while the cursor is
+    /// outside the window.
+    pub fn route_input(
+        mut commands: Commands,
+        rig: Res<DefaultCameraRig>,
+        windows: Query<&Window, With<PrimaryWindow>>,
+        cameras: Query<(Entity, &Camera, Has<IgnoreInput>)>,
+    ) {
+        let cursor = windows
+            .get_single()
+            .ok()
+            .and_then(Window::physical_cursor_position);
+        let under_cursor = cursor.and_then(|cursor| {
+            cameras
+                .iter_many(rig.cameras())
+                .filter(|(_, camera, _)| camera.is_active)
+                .filter(|(_, camera, _)| {
+                    camera
+                        .physical_viewport_rect()
+                        .is_some_and(|rect| rect.as_rect().contains(cursor))
+                })
+                .max_by_key(|(_, camera, _)| camera.order)
+                .map(|(entity, _, _)| entity)
+        });
+        let focused = under_cursor.or(rig.active_camera());
+
+        for (entity, _, ignores_input) in cameras.iter_many(rig.cameras()) {
+            let ignore_input = Some(entity) != focused;
+            if ignore_input && !ignores_input {
+                commands.entity(entity).insert(IgnoreInput);
+            } else if !ignore_input && ignores_input {
+                commands.entity(entity).remove::<IgnoreInput>();
+            }
+        }
+    }
+}
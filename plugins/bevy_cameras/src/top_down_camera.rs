@@ -0,0 +1,187 @@
+//! A camera looking straight down: dragging pans it over the scene and the mouse wheel raises and lowers it.
+
+use crate::{
+    api::{
+        is_wheel_captured, CameraController, CameraMode, ChangedControllerQuery, IgnoreInput,
+        ViewPose, WheelCapturingNodes,
+    },
+    pan_orbit_camera::{run_criteria, LINE_TO_PIXEL_RATIO},
+};
+use bevy::{
+    input::mouse::{
+        MouseScrollUnit::{Line, Pixel},
+        MouseWheel,
+    },
+    prelude::*,
+    render::camera::Camera,
+};
+use bevy_mod_picking::pointer::InputMove;
+
+/// The lowest the camera goes above its centre.
+const MIN_HEIGHT: f32 = 0.1;
+
+#[derive(Default)]
+pub struct TopDownCameraControllerPlugin<T: CameraMode>(pub T);
+
+impl<T: CameraMode + Send + Sync + 'static> Plugin for TopDownCameraControllerPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (Self::pan, Self::zoom).run_if(run_criteria::<T>))
+            .add_systems(Last, Self::update_camera_transform_system);
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct TopDownCameraController {
+    /// The point the camera looks down at.
+    pub center: Vec3,
+    /// Of the camera above the centre.
+    pub height: f32,
+    /// Around the Y axis, in radians: 0.0 puts -Z at the top of the view.
+    pub heading: f32,
+    /// In heights per pixel of pointer motion.
+    pub pan_sensitivity: f32,
+    /// The fraction of the height one line of the mouse wheel changes it by.
+    pub zoom_sensitivity: f32,
+    /// Held to pan.
+    pub pan_button: MouseButton,
+    pub enabled: bool,
+}
+
+impl Default for TopDownCameraController {
+    fn default() -> Self {
+        TopDownCameraController {
+            center: Vec3::ZERO,
+            height: 100.,
+            heading: 0.,
+            pan_sensitivity: 0.001,
+            zoom_sensitivity: 0.1,
+            pan_button: MouseButton::Left,
+            enabled: true,
+        }
+    }
+}
+
+impl TopDownCameraController {
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.heading) * Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.center + self.height * Vec3::Y)
+            .with_rotation(self.rotation())
+    }
+
+    /// Drags the scene along with a pointer motion of `delta` pixels.
+    pub fn pan(&mut self, delta: Vec2) {
+        let rotation = self.rotation();
+        let right_dir = rotation * Vec3::X;
+        let up_dir = rotation * Vec3::Y;
+        self.center +=
+            (-delta.x * right_dir + delta.y * up_dir) * self.pan_sensitivity * self.height;
+    }
+
+    /// Raises the camera by `lines` of the mouse wheel, or lowers it if negative.
+    pub fn zoom(&mut self, lines: f32) {
+        self.height = (self.height * (1. + self.zoom_sensitivity * lines)).max(MIN_HEIGHT);
+    }
+}
+
+impl CameraController for TopDownCameraController {
+    fn update_camera_transform_system(
+        mut query: Query<
+            (&TopDownCameraController, &mut Transform),
+            (Changed<TopDownCameraController>, With<Camera>),
+        >,
+    ) {
+        for (camera, mut transform) in query.iter_mut() {
+            if camera.enabled {
+                let top_down = camera.transform();
+                transform.translation = top_down.translation;
+                transform.rotation = top_down.rotation;
+            }
+        }
+    }
+
+    fn view_pose(&self) -> ViewPose {
+        ViewPose {
+            transform: self.transform(),
+            focus: self.center,
+        }
+    }
+
+    /// Looks down at the focus of `pose` from as far away, with what was ahead of the camera at the top of the view.
+    fn set_view_pose(&mut self, pose: &ViewPose) {
+        let ahead = [*pose.transform.forward(), *pose.transform.up()]
+            .into_iter()
+            .map(|direction| Vec3::new(direction.x, 0., direction.z))
+            .find(|direction| direction.length() > 1e-3);
+        if let Some(ahead) = ahead {
+            self.heading = f32::atan2(-ahead.x, -ahead.z);
+        }
+        self.center = pose.focus;
+        self.height = pose
+            .transform
+            .translation
+            .distance(pose.focus)
+            .max(MIN_HEIGHT);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+impl<T: CameraMode> TopDownCameraControllerPlugin<T> {
+    pub fn update_camera_transform_system(query: ChangedControllerQuery<TopDownCameraController>) {
+        TopDownCameraController::update_camera_transform_system(query);
+    }
+
+    pub fn pan(
+        mut pointer_motion_events: EventReader<InputMove>,
+        pointer_button_input: Res<ButtonInput<MouseButton>>,
+        mut query: Query<&mut TopDownCameraController, (With<Camera>, Without<IgnoreInput>)>,
+    ) {
+        let delta: Vec2 = pointer_motion_events.read().map(|event| event.delta).sum();
+        if delta == Vec2::ZERO {
+            return;
+        }
+
+        for mut camera in query.iter_mut() {
+            if camera.enabled && pointer_button_input.pressed(camera.pan_button) {
+                camera.pan(delta);
+            }
+        }
+    }
+
+    pub fn zoom(
+        mut mouse_wheel_events: EventReader<MouseWheel>,
+        mut query: Query<&mut TopDownCameraController, (With<Camera>, Without<IgnoreInput>)>,
+        wheel_capturing_nodes: WheelCapturingNodes,
+    ) {
+        if is_wheel_captured(&wheel_capturing_nodes) {
+            mouse_wheel_events.clear();
+            return;
+        }
+        let mut total = 0.0;
+        for event in mouse_wheel_events.read() {
+            total += event.y
+                * match event.unit {
+                    Line => 1.0,
+                    Pixel => LINE_TO_PIXEL_RATIO,
+                };
+        }
+        if total == 0.0 {
+            return;
+        }
+
+        for mut camera in query.iter_mut() {
+            if camera.enabled {
+                camera.zoom(total);
+            }
+        }
+    }
+}
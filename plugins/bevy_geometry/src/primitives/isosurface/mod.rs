@@ -0,0 +1,250 @@
+use bevy::{
+    math::{primitives::Primitive3d, Mat3, UVec3, Vec3},
+    render::{
+        mesh::{Indices, Mesh, Meshable, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    utils::HashMap,
+};
+
+// Corners of a unit cube, indexed as in most marching cubes literature.
+const CUBE_CORNERS: [UVec3; 8] = [
+    UVec3::new(0, 0, 0),
+    UVec3::new(1, 0, 0),
+    UVec3::new(1, 1, 0),
+    UVec3::new(0, 1, 0),
+    UVec3::new(0, 0, 1),
+    UVec3::new(1, 0, 1),
+    UVec3::new(1, 1, 1),
+    UVec3::new(0, 1, 1),
+];
+
+// Six tetrahedra sharing the cube diagonal 0–6. Opposite faces of neighbouring cubes are split along the
+// same diagonal, so the surface is watertight.
+const CUBE_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 5, 1, 6],
+    [0, 1, 2, 6],
+    [0, 2, 3, 6],
+    [0, 3, 7, 6],
+    [0, 7, 4, 6],
+    [0, 4, 5, 6],
+];
+
+/// The surface where a scalar field sampled on a regular grid crosses `level`.
+///
+/// The field is considered to be inside the surface where it is above the level (as for electron density).
+/// Grid point `(i, j, k)` lies at `origin + basis * (i, j, k)`, so non-orthogonal (crystallographic) grids are supported.
+#[derive(Clone, Debug)]
+pub struct Isosurface {
+    /// Samples with `i` varying fastest, then `j`, then `k`.
+    values: Vec<f32>,
+    dimensions: UVec3,
+    origin: Vec3,
+    /// Columns are the steps between neighbouring grid points along each grid axis.
+    basis: Mat3,
+    level: f32,
+}
+
+impl Primitive3d for Isosurface {}
+
+impl Isosurface {
+    pub fn new(values: Vec<f32>, dimensions: UVec3, origin: Vec3, basis: Mat3, level: f32) -> Self {
+        assert_eq!(
+            values.len(),
+            (dimensions.x * dimensions.y * dimensions.z) as usize,
+            "isosurface grid has {} values but dimensions {}",
+            values.len(),
+            dimensions
+        );
+
+        Self {
+            values,
+            dimensions,
+            origin,
+            basis,
+            level,
+        }
+    }
+
+    fn index(&self, point: UVec3) -> usize {
+        (point.x + self.dimensions.x * (point.y + self.dimensions.y * point.z)) as usize
+    }
+
+    fn value(&self, point: UVec3) -> f32 {
+        self.values[self.index(point)]
+    }
+
+    fn position(&self, point: UVec3) -> Vec3 {
+        self.origin + self.basis * point.as_vec3()
+    }
+
+    /// Gradient of the field in grid units, by central differences (one-sided at the edges of the grid).
+    fn gradient(&self, point: UVec3) -> Vec3 {
+        let mut gradient = Vec3::ZERO;
+        for axis in 0..3 {
+            let mut lower = point;
+            let mut upper = point;
+            if point[axis] > 0 {
+                lower[axis] -= 1;
+            }
+            if point[axis] + 1 < self.dimensions[axis] {
+                upper[axis] += 1;
+            }
+            let span = (upper[axis] - lower[axis]).max(1) as f32;
+            gradient[axis] = (self.value(upper) - self.value(lower)) / span;
+        }
+        gradient
+    }
+}
+
+/// A builder used for creating a [`Mesh`] with an [`Isosurface`] shape, by marching tetrahedra: each grid
+/// cell is split into six tetrahedra, which needs no case table and has no ambiguous configurations.
+#[derive(Clone, Debug)]
+pub struct IsosurfaceMeshBuilder {
+    pub isosurface: Isosurface,
+}
+
+impl IsosurfaceMeshBuilder {
+    pub fn build(&self) -> Mesh {
+        let isosurface = &self.isosurface;
+        let level = isosurface.level;
+        // Maps grid gradients to world space normals.
+        let normal_matrix = isosurface.basis.inverse().transpose();
+
+        let mut vertices = EdgeVertices::default();
+
+        let dimensions = isosurface.dimensions;
+        if dimensions.cmplt(UVec3::splat(2)).any() {
+            return empty_mesh();
+        }
+
+        for k in 0..dimensions.z - 1 {
+            for j in 0..dimensions.y - 1 {
+                for i in 0..dimensions.x - 1 {
+                    let cell = UVec3::new(i, j, k);
+                    let corners = CUBE_CORNERS.map(|corner| cell + corner);
+                    let inside = corners.map(|corner| isosurface.value(corner) > level);
+
+                    if inside.iter().all(|inside| *inside) || inside.iter().all(|inside| !*inside) {
+                        continue;
+                    }
+
+                    for tetrahedron in CUBE_TETRAHEDRA {
+                        let (ins, outs): (Vec<usize>, Vec<usize>) =
+                            tetrahedron.iter().partition(|corner| inside[**corner]);
+
+                        let mut triangles = Vec::<[(UVec3, UVec3); 3]>::new();
+                        let edge = |a: usize, b: usize| (corners[a], corners[b]);
+                        match (ins.as_slice(), outs.as_slice()) {
+                            ([a], [b, c, d]) | ([b, c, d], [a]) => {
+                                triangles.push([edge(*a, *b), edge(*a, *c), edge(*a, *d)]);
+                            }
+                            ([a, b], [c, d]) => {
+                                triangles.push([edge(*a, *c), edge(*a, *d), edge(*b, *d)]);
+                                triangles.push([edge(*a, *c), edge(*b, *d), edge(*b, *c)]);
+                            }
+                            _ => {}
+                        }
+
+                        let centre = |subset: &[usize]| {
+                            subset
+                                .iter()
+                                .map(|corner| isosurface.position(corners[*corner]))
+                                .sum::<Vec3>()
+                                / subset.len() as f32
+                        };
+                        let outwards = centre(&outs) - centre(&ins);
+
+                        for triangle in triangles {
+                            let mut triangle = triangle
+                                .map(|(a, b)| vertices.get(isosurface, normal_matrix, a, b));
+                            let [p0, p1, p2] =
+                                triangle.map(|index| vertices.positions[index as usize]);
+                            // Wind counter-clockwise when seen from outside.
+                            if (p1 - p0).cross(p2 - p0).dot(outwards) < 0. {
+                                triangle.swap(1, 2);
+                            }
+                            vertices.indices.extend(triangle);
+                        }
+                    }
+                }
+            }
+        }
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_indices(Indices::U32(vertices.indices))
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vertices.normals)
+    }
+}
+
+/// Vertices lie on edges between two grid points, and are shared by every triangle crossing that edge.
+#[derive(Default)]
+struct EdgeVertices {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    indices: Vec<u32>,
+    edge_vertices: HashMap<(usize, usize), u32>,
+}
+
+impl EdgeVertices {
+    fn get(&mut self, isosurface: &Isosurface, normal_matrix: Mat3, a: UVec3, b: UVec3) -> u32 {
+        let (ia, ib) = (isosurface.index(a), isosurface.index(b));
+        let key = (ia.min(ib), ia.max(ib));
+        if let Some(index) = self.edge_vertices.get(&key) {
+            return *index;
+        }
+
+        let (va, vb) = (isosurface.values[ia], isosurface.values[ib]);
+        let t = if va == vb {
+            0.5
+        } else {
+            (isosurface.level - va) / (vb - va)
+        };
+        let position = isosurface.position(a).lerp(isosurface.position(b), t);
+        let gradient = isosurface.gradient(a).lerp(isosurface.gradient(b), t);
+        // The field increases inwards, so the outward normal is against the gradient.
+        let normal = -(normal_matrix * gradient).normalize_or_zero();
+
+        let index = self.positions.len() as u32;
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.edge_vertices.insert(key, index);
+        index
+    }
+}
+
+fn empty_mesh() -> Mesh {
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_indices(Indices::U32(vec![]))
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<Vec3>::new())
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, Vec::<Vec3>::new())
+}
+
+impl Meshable for Isosurface {
+    type Output = IsosurfaceMeshBuilder;
+
+    fn mesh(&self) -> Self::Output {
+        IsosurfaceMeshBuilder {
+            isosurface: self.clone(),
+        }
+    }
+}
+
+impl From<Isosurface> for Mesh {
+    fn from(isosurface: Isosurface) -> Self {
+        IsosurfaceMeshBuilder { isosurface }.build()
+    }
+}
+
+impl From<IsosurfaceMeshBuilder> for Mesh {
+    fn from(isosurface: IsosurfaceMeshBuilder) -> Self {
+        isosurface.build()
+    }
+}
@@ -1 +1,2 @@
-pub mod ribbon;
+pub mod isosurface;
+pub mod ribbon;
\ No newline at end of file
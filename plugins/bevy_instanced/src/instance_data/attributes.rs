@@ -0,0 +1,401 @@
+use bevy::{
+    core::Pod,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Added, Changed, Or, With},
+        removal_detection::RemovedComponents,
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+        world::Ref,
+    },
+    log::{error, warn},
+    prelude::{Deref, DerefMut, DetectChanges},
+    render::{
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, ShaderDefVal, VertexAttribute, VertexFormat,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        Extract,
+    },
+    utils::{hashbrown::hash_map::Entry, HashMap, HashSet},
+};
+
+use super::culling::{CullingBackend, InstanceCulling};
+use crate::render::render_pipeline::INSTANCE_ATTRIBUTES_LOCATION;
+
+/// The vertex attributes a pipeline can count on, e.g. on WebGPU (`max_vertex_attributes`).
+pub const MAX_VERTEX_ATTRIBUTES: usize = 16;
+
+/// A field of an [`InstanceLayout`], read by the vertex shader as `@location(#{INSTANCE_<NAME>_LOCATION})` when the
+/// shader def `INSTANCE_<NAME>` is set, `<NAME>` being `name` in upper case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceAttribute {
+    pub name: &'static str,
+    pub format: VertexFormat,
+}
+
+/// A `#[repr(C)]` struct of per-instance data, whose fields [`InstanceLayout::ATTRIBUTES`] lists in order.
+///
+/// Besides [`Instance`](super::instanced::Instance), entities can carry one struct of their own per instance in an
+/// [`InstanceAttributes`], e.g. selection flags or an emissive colour for a custom vertex shader to read:
+///
+/// ```ignore
+/// #[derive(Clone, Copy, Pod, Zeroable)]
+/// #[repr(C)]
+/// struct AtomAttributes {
+///     emissive: [f32; 4],
+///     atom_index: u32,
+/// }
+///
+/// impl InstanceLayout for AtomAttributes {
+///     const ATTRIBUTES: &'static [InstanceAttribute] = &[
+///         InstanceAttribute { name: "emissive", format: VertexFormat::Float32x4 },
+///         InstanceAttribute { name: "atom_index", format: VertexFormat::Uint32 },
+///     ];
+/// }
+/// ```
+pub trait InstanceLayout: Pod + Send + Sync {
+    const ATTRIBUTES: &'static [InstanceAttribute];
+}
+
+/// One vertex attribute per [`InstanceAttribute`], at consecutive shader locations starting from
+/// `first_shader_location`.
+pub fn vertex_attributes(
+    attributes: &[InstanceAttribute],
+    first_shader_location: u32,
+) -> Vec<VertexAttribute> {
+    let mut offset = 0;
+    attributes
+        .iter()
+        .zip(first_shader_location..)
+        .map(|(attribute, shader_location)| {
+            let vertex_attribute = VertexAttribute {
+                format: attribute.format,
+                offset,
+                shader_location,
+            };
+            offset += attribute.format.size();
+            vertex_attribute
+        })
+        .collect()
+}
+
+/// The shader defs matching [`vertex_attributes`]: `INSTANCE_<NAME>` and `INSTANCE_<NAME>_LOCATION` per attribute.
+pub fn shader_defs(
+    attributes: &[InstanceAttribute],
+    first_shader_location: u32,
+) -> Vec<ShaderDefVal> {
+    attributes
+        .iter()
+        .zip(first_shader_location..)
+        .flat_map(|(attribute, shader_location)| {
+            let name = format!("INSTANCE_{}", attribute.name.to_uppercase());
+            [
+                ShaderDefVal::Bool(name.clone(), true),
+                ShaderDefVal::UInt(format!("{name}_LOCATION"), shader_location),
+            ]
+        })
+        .collect()
+}
+
+/// Size of the attributes, which the struct laying them out must match, padding excluded.
+pub fn stride(attributes: &[InstanceAttribute]) -> usize {
+    attributes
+        .iter()
+        .map(|attribute| attribute.format.size() as usize)
+        .sum()
+}
+
+/// Panics unless the attributes of `T` describe every byte of it and fit in the vertex attributes left by the mesh and
+/// the [`Instance`](super::instanced::Instance).
+pub fn check_layout<T: InstanceLayout>() {
+    let name = std::any::type_name::<T>();
+    assert_eq!(
+        std::mem::size_of::<T>(),
+        stride(T::ATTRIBUTES),
+        "the attributes of {name} don't describe every byte of it"
+    );
+    let vertex_attributes = INSTANCE_ATTRIBUTES_LOCATION as usize + T::ATTRIBUTES.len();
+    assert!(
+        vertex_attributes <= MAX_VERTEX_ATTRIBUTES,
+        "{name} takes the pipelines to {vertex_attributes} vertex attributes, over {MAX_VERTEX_ATTRIBUTES}"
+    );
+}
+
+/// Custom data for each instance of the [`InstancesData`](super::instanced::InstancesData) of the entity, in the same
+/// order. Needs the [`InstanceAttributesPlugin`](crate::plugin::InstanceAttributesPlugin) of `T`.
+///
+/// The attributes follow CPU culling. The GPU culling pass doesn't carry them over, so entities with attributes are
+/// culled on the CPU instead, with a warning.
+///
+/// An entity has attributes of one layout only: the pipelines read them all from the same shader locations. Those
+/// of a second layout are removed, with an error.
+#[derive(Component, Deref, DerefMut)]
+pub struct InstanceAttributes<T: InstanceLayout>(pub Vec<T>);
+
+/// The layout of the [`InstanceAttributes`] of each entity that has some, by type name.
+#[derive(Resource, Default)]
+pub struct InstanceAttributeLayouts(HashMap<Entity, &'static str>);
+
+type GpuCulledAttributes<T> = (
+    With<InstanceAttributes<T>>,
+    Or<(Changed<InstanceCulling>, Added<InstanceAttributes<T>>)>,
+);
+
+impl<T: InstanceLayout> InstanceAttributes<T> {
+    /// Removes the attributes of `T` from the entities that already have attributes of another layout.
+    pub fn reject_second_layout(
+        mut commands: Commands,
+        mut layouts: ResMut<InstanceAttributeLayouts>,
+        added: Query<Entity, Added<InstanceAttributes<T>>>,
+        mut removed: RemovedComponents<InstanceAttributes<T>>,
+    ) {
+        let layout = std::any::type_name::<T>();
+        for entity in removed.read() {
+            if layouts.0.get(&entity) == Some(&layout) {
+                layouts.0.remove(&entity);
+            }
+        }
+
+        for entity in added.iter() {
+            match layouts.0.entry(entity) {
+                Entry::Vacant(entry) => {
+                    entry.insert(layout);
+                }
+                Entry::Occupied(entry) if *entry.get() != layout => {
+                    error!(
+                        "{entity:?} already has instance attributes of {}: removing those of {layout}",
+                        entry.get()
+                    );
+                    commands.entity(entity).remove::<InstanceAttributes<T>>();
+                }
+                Entry::Occupied(_) => {}
+            }
+        }
+    }
+
+    /// Moves the entities with attributes from GPU to CPU culling, which draws them with their attributes.
+    pub fn refuse_gpu_culling(
+        mut query: Query<(Entity, &mut InstanceCulling), GpuCulledAttributes<T>>,
+    ) {
+        for (entity, mut culling) in query.iter_mut() {
+            if culling.backend == CullingBackend::Gpu {
+                warn!("{entity:?} has instance attributes, which GPU culling ignores: culling it on the CPU");
+                culling.backend = CullingBackend::Cpu;
+            }
+        }
+    }
+}
+
+/// The render world counterpart of [`InstanceAttributes`], with the layout of the attributes for the pipelines.
+#[derive(Component)]
+pub struct ExtractedInstanceAttributes {
+    pub attributes: &'static [InstanceAttribute],
+    /// The attributes as bytes, when they changed since the last extraction.
+    bytes: Option<Vec<u8>>,
+}
+
+impl ExtractedInstanceAttributes {
+    pub fn extract<T: InstanceLayout>(
+        mut commands: Commands,
+        query: Extract<Query<(Entity, Ref<InstanceAttributes<T>>)>>,
+        mut mirrored: Local<HashSet<Entity>>,
+    ) {
+        let mut extracted = Vec::new();
+        let previously_mirrored = std::mem::take(&mut *mirrored);
+
+        // The render world keeps a copy of the attributes: send them again only when they changed.
+        for (entity, attributes) in query.iter() {
+            let bytes = (attributes.is_changed() || !previously_mirrored.contains(&entity))
+                .then(|| bytemuck::cast_slice(attributes.as_slice()).to_vec());
+            mirrored.insert(entity);
+
+            extracted.push((
+                entity,
+                ExtractedInstanceAttributes {
+                    attributes: T::ATTRIBUTES,
+                    bytes,
+                },
+            ));
+        }
+
+        commands.insert_or_spawn_batch(extracted);
+    }
+}
+
+pub struct GpuInstanceAttributes {
+    pub buffer: Buffer,
+    /// A copy of the buffer, from which CPU culling picks the attributes of the visible instances.
+    pub bytes: Vec<u8>,
+    pub stride: usize,
+    /// Bytes the buffer can hold.
+    capacity: usize,
+}
+
+impl GpuInstanceAttributes {
+    /// Number of instances the attributes are given for.
+    pub fn length(&self) -> usize {
+        self.bytes.len() / self.stride.max(1)
+    }
+}
+
+/// The attribute buffers of each entity, kept across frames.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct InstanceAttributesBuffers(HashMap<Entity, GpuInstanceAttributes>);
+
+impl InstanceAttributesBuffers {
+    /// Uploads the attributes that changed, reusing the entity's buffer when it is large enough.
+    pub fn prepare(
+        query: Query<(Entity, &ExtractedInstanceAttributes)>,
+        render_device: Res<RenderDevice>,
+        render_queue: Res<RenderQueue>,
+        mut buffers: ResMut<InstanceAttributesBuffers>,
+    ) {
+        buffers.retain(|entity, _| query.contains(*entity));
+
+        for (entity, extracted) in &query {
+            let Some(bytes) = &extracted.bytes else {
+                continue;
+            };
+            let stride = stride(extracted.attributes);
+
+            let reused = buffers
+                .get(&entity)
+                .is_some_and(|attributes| attributes.capacity >= bytes.len());
+            if !reused {
+                let capacity = buffers
+                    .get(&entity)
+                    .map_or(0, |attributes| 2 * attributes.capacity)
+                    .max(bytes.len())
+                    .max(stride);
+                buffers.insert(
+                    entity,
+                    GpuInstanceAttributes {
+                        buffer: render_device.create_buffer(&BufferDescriptor {
+                            label: Some("instance attributes buffer"),
+                            size: capacity as u64,
+                            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        }),
+                        bytes: Vec::new(),
+                        stride,
+                        capacity,
+                    },
+                );
+            }
+            let Some(attributes) = buffers.get_mut(&entity) else {
+                continue;
+            };
+
+            if !bytes.is_empty() {
+                render_queue.write_buffer(&attributes.buffer, 0, bytes);
+            }
+            attributes.bytes.clone_from(bytes);
+            attributes.stride = stride;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        app::{App, Update},
+        core::Zeroable,
+        ecs::schedule::IntoSystemConfigs,
+    };
+
+    use super::*;
+
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    #[repr(C)]
+    struct Emissive([f32; 4]);
+
+    impl InstanceLayout for Emissive {
+        const ATTRIBUTES: &'static [InstanceAttribute] = &[InstanceAttribute {
+            name: "emissive",
+            format: VertexFormat::Float32x4,
+        }];
+    }
+
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    #[repr(C)]
+    struct Index(u32);
+
+    impl InstanceLayout for Index {
+        const ATTRIBUTES: &'static [InstanceAttribute] = &[InstanceAttribute {
+            name: "index",
+            format: VertexFormat::Uint32,
+        }];
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<InstanceAttributeLayouts>().add_systems(
+            Update,
+            (
+                InstanceAttributes::<Emissive>::reject_second_layout,
+                InstanceAttributes::<Index>::reject_second_layout,
+            )
+                .chain(),
+        );
+        app
+    }
+
+    #[test]
+    fn a_second_layout_is_rejected() {
+        let mut app = app();
+        let both = app
+            .world
+            .spawn((
+                InstanceAttributes(vec![Emissive([1.; 4])]),
+                InstanceAttributes(vec![Index(0)]),
+            ))
+            .id();
+        let one = app.world.spawn(InstanceAttributes(vec![Index(0)])).id();
+        app.update();
+
+        assert!(app
+            .world
+            .get::<InstanceAttributes<Emissive>>(both)
+            .is_some());
+        assert!(app.world.get::<InstanceAttributes<Index>>(both).is_none());
+        assert!(app.world.get::<InstanceAttributes<Index>>(one).is_some());
+    }
+
+    #[test]
+    fn the_layout_can_be_replaced() {
+        let mut app = app();
+        let entity = app
+            .world
+            .spawn(InstanceAttributes(vec![Emissive([1.; 4])]))
+            .id();
+        app.update();
+
+        app.world
+            .entity_mut(entity)
+            .remove::<InstanceAttributes<Emissive>>();
+        app.update();
+        app.world
+            .entity_mut(entity)
+            .insert(InstanceAttributes(vec![Index(0)]));
+        app.update();
+        assert!(app.world.get::<InstanceAttributes<Index>>(entity).is_some());
+    }
+
+    #[test]
+    #[should_panic]
+    fn layouts_must_describe_every_byte() {
+        #[derive(Clone, Copy, Pod, Zeroable)]
+        #[repr(C)]
+        struct Padded([u32; 2]);
+
+        impl InstanceLayout for Padded {
+            const ATTRIBUTES: &'static [InstanceAttribute] = &[InstanceAttribute {
+                name: "padded",
+                format: VertexFormat::Uint32,
+            }];
+        }
+
+        check_layout::<Padded>();
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, Or, Without},
+        system::{Commands, Query, Res},
+    },
+    math::{Mat3, Vec3, Vec3A},
+    render::{mesh::Mesh, primitives::Aabb},
+};
+
+use super::{
+    imposter::Imposter,
+    instanced::{Instance, InstancesData},
+};
+
+/// The bounds of the instanced mesh in its own space, before the transform of any instance.
+/// Kept next to the [`Aabb`] of the entity so that instances can be culled one by one.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct InstancedMeshBounds(pub Aabb);
+
+type BoundsQuery = (
+    Entity,
+    &'static InstancesData,
+    &'static Handle<Mesh>,
+    Option<&'static Imposter>,
+    Option<&'static mut InstancedMeshBounds>,
+    Option<&'static mut Aabb>,
+);
+
+type OutdatedBounds = Or<(
+    Changed<InstancesData>,
+    Changed<Handle<Mesh>>,
+    Changed<Imposter>,
+    Without<InstancedMeshBounds>,
+)>;
+
+impl InstancedMeshBounds {
+    /// The bounds of the mesh once `instance` is applied to it.
+    pub fn instance_aabb(&self, instance: &Instance) -> Aabb {
+        let Aabb {
+            center,
+            half_extents,
+        } = self.0;
+        let rotation = Mat3::from_quat(instance.rotation());
+        let scale = instance.scale();
+        // The extents of a rotated box are those of the box projected on each axis.
+        let abs_rotation = Mat3::from_cols(
+            rotation.x_axis.abs(),
+            rotation.y_axis.abs(),
+            rotation.z_axis.abs(),
+        );
+
+        Aabb {
+            center: Vec3A::from(rotation * (Vec3::from(center) * scale) + instance.position()),
+            half_extents: Vec3A::from(abs_rotation * (Vec3::from(half_extents) * scale.abs())),
+        }
+    }
+
+    /// Radius of the sphere enclosing the mesh once `instance` is applied to it, around [`Self::instance_aabb`]'s centre.
+    pub fn instance_radius(&self, instance: &Instance) -> f32 {
+        (Vec3::from(self.0.half_extents) * instance.scale()).length()
+    }
+
+    /// The bounds enclosing every instance, or an empty box at the origin when there are none.
+    pub fn instances_aabb(&self, instances: &[Instance]) -> Aabb {
+        let mut min = Vec3A::splat(f32::MAX);
+        let mut max = Vec3A::splat(f32::MIN);
+        for instance in instances {
+            let aabb = self.instance_aabb(instance);
+            min = min.min(aabb.min());
+            max = max.max(aabb.max());
+        }
+
+        if instances.is_empty() {
+            Aabb::default()
+        } else {
+            Aabb::from_min_max(min.into(), max.into())
+        }
+    }
+
+    /// Gives every instanced entity an [`Aabb`] enclosing all of its instances, replacing the one Bevy computes from
+    /// the mesh alone, so that the entity is only culled when all of its instances are out of view.
+    ///
+    /// Entities whose mesh isn't loaded yet are retried on the following frames. The instances of an [`Imposter`] are
+    /// bounded by its shape rather than by the quad it is drawn on.
+    ///
+    /// Bounds the entity already has are updated in place, so that the visibility check of the same frame sees them.
+    pub fn update(
+        mut commands: Commands,
+        meshes: Res<Assets<Mesh>>,
+        mut query: Query<BoundsQuery, OutdatedBounds>,
+    ) {
+        for (entity, instances, mesh, imposter, mesh_bounds, aabb) in &mut query {
+            let mesh_aabb = match imposter {
+                Some(imposter) => Some(imposter.aabb()),
+                None => meshes.get(mesh).and_then(Mesh::compute_aabb),
+            };
+            let Some(mesh_aabb) = mesh_aabb else {
+                continue;
+            };
+            let bounds = InstancedMeshBounds(mesh_aabb);
+            let instances_aabb = bounds.instances_aabb(instances);
+
+            match mesh_bounds {
+                Some(mut mesh_bounds) => *mesh_bounds = bounds,
+                None => {
+                    commands.entity(entity).insert(bounds);
+                }
+            }
+            match aabb {
+                Some(mut aabb) => *aabb = instances_aabb,
+                None => {
+                    commands.entity(entity).insert(instances_aabb);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::{
+            system::{RunSystemOnce, SystemState},
+            world::World,
+        },
+        math::Vec3,
+    };
+
+    use super::*;
+
+    fn bounds(world: &World, entity: Entity) -> (Vec3A, Vec3A) {
+        let aabb = world.get::<Aabb>(entity).unwrap();
+        (aabb.min(), aabb.max())
+    }
+
+    #[test]
+    fn aabb_encloses_every_instance() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        let entity = world
+            .spawn((
+                InstancesData::new(vec![
+                    Instance::new(Vec3::ZERO, 1., [1.; 4]),
+                    Instance::new(Vec3::new(4., 0., -2.), 2., [1.; 4]),
+                ]),
+                Handle::<Mesh>::default(),
+                Imposter::Sphere,
+            ))
+            .id();
+
+        world.run_system_once(InstancedMeshBounds::update);
+        assert_eq!(
+            bounds(&world, entity),
+            (Vec3A::new(-0.5, -1., -3.), Vec3A::new(5., 1., 0.5))
+        );
+        assert!(world.get::<InstancedMeshBounds>(entity).is_some());
+    }
+
+    #[test]
+    fn existing_aabb_is_updated_without_waiting_for_commands() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        let entity = world
+            .spawn((
+                InstancesData::new(vec![Instance::new(Vec3::ZERO, 1., [1.; 4])]),
+                Handle::<Mesh>::default(),
+                Imposter::Sphere,
+            ))
+            .id();
+        world.run_system_once(InstancedMeshBounds::update);
+
+        world
+            .get_mut::<InstancesData>(entity)
+            .unwrap()
+            .data_mut()
+            .push(Instance::new(Vec3::new(0., 10., 0.), 1., [1.; 4]));
+        // Only what the system itself writes: no commands are applied before the bounds are read.
+        let mut system_state = SystemState::<(
+            Commands,
+            Res<Assets<Mesh>>,
+            Query<BoundsQuery, OutdatedBounds>,
+        )>::new(&mut world);
+        let (commands, meshes, query) = system_state.get_mut(&mut world);
+        InstancedMeshBounds::update(commands, meshes, query);
+        assert_eq!(bounds(&world, entity).1.y, 10.5);
+    }
+}
@@ -0,0 +1,591 @@
+use std::ops::Range;
+
+use bevy::{
+    asset::{AssetId, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+        world::Ref,
+    },
+    math::{Affine3A, Mat4, Vec3},
+    prelude::DetectChanges,
+    render::{
+        mesh::Mesh,
+        primitives::Frustum,
+        render_resource::{Buffer, BufferDescriptor, BufferUsages},
+        renderer::{RenderDevice, RenderQueue},
+        view::ExtractedView,
+        Extract,
+    },
+    transform::components::GlobalTransform,
+    utils::{HashMap, HashSet},
+};
+
+use super::{
+    attributes::InstanceAttributesBuffers,
+    bounds::InstancedMeshBounds,
+    instanced::{Instance, InstancesData},
+};
+
+/// A coarser mesh drawn for the instances that look small on screen.
+/// It must have the same vertex attributes as the mesh of the entity, e.g. an icosphere with fewer subdivisions.
+#[derive(Clone, Debug)]
+pub struct InstanceLod {
+    pub mesh: Handle<Mesh>,
+    /// The mesh is used for instances whose diameter on screen, as a fraction of the viewport height, is below this.
+    pub max_screen_size: f32,
+}
+
+/// Culls the instances of the entity against the frustum of each view (cameras and shadow maps) on the CPU, and draws
+/// each remaining instance with the coarsest of `lods` its size on screen allows (the mesh of the entity when none does).
+#[derive(Component, Clone, Debug, Default)]
+pub struct InstanceCulling {
+    pub lods: Vec<InstanceLod>,
+    /// Instances smaller than this on screen, as a fraction of the viewport height, aren't drawn.
+    pub min_screen_size: f32,
+    pub backend: CullingBackend,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CullingBackend {
+    /// The visible instances are selected on the CPU and uploaded for each view, every frame.
+    #[default]
+    Cpu,
+    /// A compute pass selects the visible instances and writes the arguments of indirect draws: the instances never
+    /// leave the GPU, and each entity is drawn with one draw call per LOD whatever its number of instances.
+    /// At most [`MAX_GPU_LODS`] LOD meshes are used. Entities with
+    /// [`InstanceAttributes`](super::attributes::InstanceAttributes) are culled on the CPU instead.
+    Gpu,
+}
+
+/// The number of [`InstanceLod`]s the GPU culling pass chooses from. Further ones are ignored.
+pub const MAX_GPU_LODS: usize = 4;
+
+impl InstanceCulling {
+    pub fn with_lods(mut self, lods: Vec<InstanceLod>) -> Self {
+        self.lods = lods;
+        self
+    }
+
+    pub fn with_min_screen_size(mut self, min_screen_size: f32) -> Self {
+        self.min_screen_size = min_screen_size;
+        self
+    }
+
+    pub fn on_gpu(mut self) -> Self {
+        self.backend = CullingBackend::Gpu;
+        self
+    }
+}
+
+/// The render world counterpart of [`InstanceCulling`], with what culling needs to know of the entity.
+#[derive(Component)]
+pub struct ExtractedInstanceCulling {
+    /// LOD meshes sorted from the coarsest.
+    pub lods: Vec<(AssetId<Mesh>, f32)>,
+    pub min_screen_size: f32,
+    pub backend: CullingBackend,
+    pub mesh: AssetId<Mesh>,
+    pub bounds: InstancedMeshBounds,
+    pub transform: Affine3A,
+    /// Every instance, when they changed since the last extraction. Only sent for CPU culling.
+    instances: Option<Vec<Instance>>,
+}
+
+type CullingQuery = (
+    Entity,
+    &'static InstanceCulling,
+    Ref<'static, InstancesData>,
+    &'static InstancedMeshBounds,
+    &'static Handle<Mesh>,
+    &'static GlobalTransform,
+);
+
+impl ExtractedInstanceCulling {
+    pub fn extract(
+        mut commands: Commands,
+        query: Extract<Query<CullingQuery>>,
+        mut mirrored: Local<HashSet<Entity>>,
+    ) {
+        let mut extracted = Vec::new();
+        let previously_mirrored = std::mem::take(&mut *mirrored);
+
+        for (entity, culling, instances, bounds, mesh, transform) in query.iter() {
+            let mut lods = culling
+                .lods
+                .iter()
+                .map(|lod| (lod.mesh.id(), lod.max_screen_size))
+                .collect::<Vec<_>>();
+            lods.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+            // For CPU culling, the render world keeps a copy of the instances: send them again only when they changed.
+            let instances = match culling.backend {
+                CullingBackend::Cpu => {
+                    let instances = (instances.is_changed()
+                        || !previously_mirrored.contains(&entity))
+                    .then(|| instances.to_vec());
+                    mirrored.insert(entity);
+                    instances
+                }
+                CullingBackend::Gpu => None,
+            };
+
+            extracted.push((
+                entity,
+                ExtractedInstanceCulling {
+                    lods,
+                    min_screen_size: culling.min_screen_size,
+                    backend: culling.backend,
+                    mesh: mesh.id(),
+                    bounds: *bounds,
+                    transform: transform.affine(),
+                    instances,
+                },
+            ));
+        }
+
+        commands.insert_or_spawn_batch(extracted);
+    }
+
+    /// Sorts the indices of the `instances` visible from `view` into `lod_instances`: one list for each of
+    /// [`lods`](Self::lods), then one for the mesh of the entity. Instances out of `frustum` or smaller than
+    /// [`min_screen_size`](Self::min_screen_size) are left out.
+    pub fn cull(
+        &self,
+        instances: &[Instance],
+        view: &ExtractedView,
+        frustum: &Frustum,
+        lod_instances: &mut Vec<Vec<usize>>,
+    ) {
+        let view_from_world = view.transform.compute_matrix().inverse();
+        let is_orthographic = view.projection.w_axis.w == 1.0;
+        let matrix = self.transform.matrix3;
+        let scale = matrix
+            .x_axis
+            .length()
+            .max(matrix.y_axis.length())
+            .max(matrix.z_axis.length());
+
+        lod_instances.resize_with(self.lods.len() + 1, Vec::new);
+        lod_instances.iter_mut().for_each(Vec::clear);
+
+        for (index, instance) in instances.iter().enumerate() {
+            let aabb = self.bounds.instance_aabb(instance);
+            if !frustum.intersects_obb(&aabb, &self.transform, true, true) {
+                continue;
+            }
+            let radius = scale * self.bounds.instance_radius(instance);
+            let centre = self.transform.transform_point3a(aabb.center);
+            let screen_size = screen_size(
+                &view.projection,
+                &view_from_world,
+                is_orthographic,
+                centre.into(),
+                radius,
+            );
+            if screen_size < self.min_screen_size {
+                continue;
+            }
+
+            let lod = self
+                .lods
+                .iter()
+                .position(|(_, max_screen_size)| screen_size < *max_screen_size)
+                .unwrap_or(self.lods.len());
+            lod_instances[lod].push(index);
+        }
+    }
+}
+
+/// Appends the attributes of the instances at `indices` to `gathered`, `stride` bytes each. Instances beyond the end of
+/// `attributes` get zeroes.
+fn gather_attributes(attributes: &[u8], stride: usize, indices: &[usize], gathered: &mut Vec<u8>) {
+    for &index in indices {
+        match attributes.get(index * stride..(index + 1) * stride) {
+            Some(bytes) => gathered.extend_from_slice(bytes),
+            None => gathered.resize(gathered.len() + stride, 0),
+        }
+    }
+}
+
+/// The instances of an entity left after culling for a view, in a buffer of their own, grouped by LOD.
+pub struct VisibleInstances {
+    pub buffer: Buffer,
+    capacity: usize,
+    /// The [`InstanceAttributes`](super::attributes::InstanceAttributes) of the visible instances, in the same order.
+    pub attributes: Option<Buffer>,
+    attribute_stride: usize,
+    /// The mesh of each group and the range of its instances in the buffer.
+    pub lods: Vec<(AssetId<Mesh>, Range<u32>)>,
+}
+
+/// The culled instances of each entity, by view and entity, kept across frames to reuse the buffers.
+#[derive(Resource, Default)]
+pub struct VisibleInstancesBuffers {
+    instances: HashMap<Entity, Vec<Instance>>,
+    visible: HashMap<(Entity, Entity), VisibleInstances>,
+}
+
+impl VisibleInstancesBuffers {
+    pub fn get(&self, view: Entity, entity: Entity) -> Option<&VisibleInstances> {
+        self.visible.get(&(view, entity))
+    }
+
+    /// Culls the instances of every [`ExtractedInstanceCulling`] entity against each view frustum, sorts them by LOD and
+    /// uploads them, along with their attributes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        views: Query<(Entity, &ExtractedView, &Frustum)>,
+        query: Query<(Entity, &ExtractedInstanceCulling)>,
+        attribute_buffers: Res<InstanceAttributesBuffers>,
+        render_device: Res<RenderDevice>,
+        render_queue: Res<RenderQueue>,
+        mut buffers: ResMut<VisibleInstancesBuffers>,
+        mut lod_instances: Local<Vec<Vec<usize>>>,
+        mut visible_attributes: Local<Vec<u8>>,
+    ) {
+        let VisibleInstancesBuffers { instances, visible } = &mut *buffers;
+        let culled_on_cpu = |entity: &Entity| {
+            query
+                .get(*entity)
+                .is_ok_and(|(_, culling)| culling.backend == CullingBackend::Cpu)
+        };
+        instances.retain(|entity, _| culled_on_cpu(entity));
+        visible.retain(|(view, entity), _| views.contains(*view) && culled_on_cpu(entity));
+
+        for (entity, culling) in &query {
+            if let Some(data) = &culling.instances {
+                instances.insert(entity, data.clone());
+            }
+        }
+
+        for (view_entity, view, frustum) in &views {
+            for (entity, culling) in &query {
+                // GPU culled entities have no copy of their instances.
+                let Some(data) = instances.get(&entity) else {
+                    continue;
+                };
+                let attributes = attribute_buffers.get(&entity);
+                let attribute_stride = attributes.map_or(0, |attributes| attributes.stride);
+
+                culling.cull(data, view, frustum, &mut lod_instances);
+
+                let length = lod_instances.iter().map(Vec::len).sum::<usize>();
+                let capacity = match visible.get(&(view_entity, entity)) {
+                    Some(visible_instances)
+                        if visible_instances.capacity >= length
+                            && visible_instances.attribute_stride == attribute_stride =>
+                    {
+                        None
+                    }
+                    previous => Some(
+                        previous
+                            .map_or(0, |visible_instances| 2 * visible_instances.capacity)
+                            .max(length)
+                            .max(1),
+                    ),
+                };
+                if let Some(capacity) = capacity {
+                    visible.insert(
+                        (view_entity, entity),
+                        VisibleInstances {
+                            buffer: render_device.create_buffer(&BufferDescriptor {
+                                label: Some("visible instance data buffer"),
+                                size: (capacity * std::mem::size_of::<Instance>()) as u64,
+                                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                                mapped_at_creation: false,
+                            }),
+                            capacity,
+                            attributes: (attribute_stride > 0).then(|| {
+                                render_device.create_buffer(&BufferDescriptor {
+                                    label: Some("visible instance attributes buffer"),
+                                    size: (capacity * attribute_stride) as u64,
+                                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                                    mapped_at_creation: false,
+                                })
+                            }),
+                            attribute_stride,
+                            lods: Vec::new(),
+                        },
+                    );
+                }
+                let Some(visible_instances) = visible.get_mut(&(view_entity, entity)) else {
+                    continue;
+                };
+
+                visible_instances.lods.clear();
+                let meshes = culling
+                    .lods
+                    .iter()
+                    .map(|(mesh, _)| *mesh)
+                    .chain([culling.mesh]);
+                let mut start = 0;
+                for (mesh, lod) in meshes.zip(lod_instances.iter()) {
+                    if lod.is_empty() {
+                        continue;
+                    }
+                    let lod_data: Vec<Instance> = lod.iter().map(|&index| data[index]).collect();
+                    render_queue.write_buffer(
+                        &visible_instances.buffer,
+                        (start * std::mem::size_of::<Instance>()) as u64,
+                        bytemuck::cast_slice(lod_data.as_slice()),
+                    );
+                    if let (Some(attributes), Some(buffer)) =
+                        (attributes, &visible_instances.attributes)
+                    {
+                        visible_attributes.clear();
+                        gather_attributes(
+                            &attributes.bytes,
+                            attribute_stride,
+                            lod,
+                            &mut visible_attributes,
+                        );
+                        render_queue.write_buffer(
+                            buffer,
+                            (start * attribute_stride) as u64,
+                            &visible_attributes,
+                        );
+                    }
+                    visible_instances
+                        .lods
+                        .push((mesh, start as u32..(start + lod.len()) as u32));
+                    start += lod.len();
+                }
+            }
+        }
+    }
+}
+
+/// Diameter on screen of a sphere, as a fraction of the viewport height.
+fn screen_size(
+    projection: &Mat4,
+    view_from_world: &Mat4,
+    is_orthographic: bool,
+    centre: Vec3,
+    radius: f32,
+) -> f32 {
+    // Normalised device coordinates span 2 units along the height of the viewport.
+    let projected_radius = radius * projection.y_axis.y;
+    if is_orthographic {
+        return projected_radius;
+    }
+
+    let depth = -view_from_world.transform_point3(centre).z;
+    if depth <= radius {
+        // The camera is inside or right next to the sphere.
+        f32::INFINITY
+    } else {
+        projected_radius / depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::{UVec4, Vec3A},
+        render::primitives::Aabb,
+        utils::default,
+    };
+
+    use super::*;
+    use crate::render::gpu_culling::GpuCullingLayout;
+
+    const FAR: f32 = 1000.;
+
+    /// A unit cube, whose instances have a bounding sphere of radius √3 times their scale.
+    fn culling(lods: &[f32], min_screen_size: f32) -> ExtractedInstanceCulling {
+        ExtractedInstanceCulling {
+            lods: lods
+                .iter()
+                .map(|max_screen_size| (AssetId::default(), *max_screen_size))
+                .collect(),
+            min_screen_size,
+            backend: CullingBackend::Cpu,
+            mesh: AssetId::default(),
+            bounds: InstancedMeshBounds(Aabb {
+                center: Vec3A::ZERO,
+                half_extents: Vec3A::ONE,
+            }),
+            transform: Affine3A::IDENTITY,
+            instances: None,
+        }
+    }
+
+    /// A view from the origin towards -Z.
+    fn view(projection: Mat4) -> (ExtractedView, Frustum) {
+        let frustum =
+            Frustum::from_view_projection_custom_far(&projection, &Vec3::ZERO, &Vec3::Z, FAR);
+        let view = ExtractedView {
+            projection,
+            transform: GlobalTransform::IDENTITY,
+            view_projection: None,
+            hdr: false,
+            viewport: UVec4::new(0, 0, 100, 100),
+            color_grading: default(),
+        };
+        (view, frustum)
+    }
+
+    /// With a 90° vertical field of view, so that an instance at depth `d` is `√3 / d` of the viewport height.
+    fn perspective_view() -> (ExtractedView, Frustum) {
+        view(Mat4::perspective_infinite_reverse_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.,
+            0.1,
+        ))
+    }
+
+    /// Showing 20 units vertically, so that every instance is √3 / 10 of the viewport height.
+    fn orthographic_view() -> (ExtractedView, Frustum) {
+        view(Mat4::orthographic_rh(-10., 10., -10., 10., FAR, 0.))
+    }
+
+    fn instances(positions: &[Vec3]) -> Vec<Instance> {
+        positions
+            .iter()
+            .map(|position| Instance::new(*position, 1., [1.; 4]))
+            .collect()
+    }
+
+    fn cull(
+        culling: &ExtractedInstanceCulling,
+        (view, frustum): &(ExtractedView, Frustum),
+        positions: &[Vec3],
+    ) -> Vec<Vec<usize>> {
+        let mut lod_instances = Vec::new();
+        culling.cull(&instances(positions), view, frustum, &mut lod_instances);
+        lod_instances
+    }
+
+    #[test]
+    fn instances_out_of_the_frustum_are_dropped() {
+        let positions = [
+            Vec3::new(0., 0., -10.),
+            // Behind the camera.
+            Vec3::new(0., 0., 10.),
+            // Off to the side, beyond the 45° half field of view.
+            Vec3::new(30., 0., -10.),
+        ];
+        assert_eq!(
+            cull(&culling(&[], 0.), &perspective_view(), &positions),
+            vec![vec![0]]
+        );
+    }
+
+    #[test]
+    fn instances_below_the_min_screen_size_are_dropped() {
+        // About 0.17 and 0.017 of the viewport height.
+        let positions = [Vec3::new(0., 0., -10.), Vec3::new(0., 0., -100.)];
+        assert_eq!(
+            cull(&culling(&[], 0.1), &perspective_view(), &positions),
+            vec![vec![0]]
+        );
+    }
+
+    #[test]
+    fn lods_are_chosen_by_screen_size() {
+        // About 0.017, 0.17 and 0.87 of the viewport height.
+        let positions = [
+            Vec3::new(0., 0., -10.),
+            Vec3::new(0., 0., -2.),
+            Vec3::new(0., 0., -100.),
+        ];
+        assert_eq!(
+            cull(&culling(&[0.05, 0.5], 0.), &perspective_view(), &positions),
+            vec![vec![2], vec![0], vec![1]]
+        );
+    }
+
+    #[test]
+    fn orthographic_screen_size_ignores_depth() {
+        let positions = [
+            Vec3::new(0., 0., -10.),
+            Vec3::new(0., 0., -500.),
+            // Beyond the right edge of the view.
+            Vec3::new(15., 0., -10.),
+        ];
+        assert_eq!(
+            cull(&culling(&[0.1, 0.2], 0.), &orthographic_view(), &positions),
+            vec![vec![], vec![0, 1], vec![]]
+        );
+    }
+
+    #[test]
+    fn camera_inside_the_bounding_sphere_uses_the_full_mesh() {
+        let (view, _) = perspective_view();
+        let view_from_world = view.transform.compute_matrix().inverse();
+        assert_eq!(
+            screen_size(
+                &view.projection,
+                &view_from_world,
+                false,
+                Vec3::new(0., 0., -1.),
+                3f32.sqrt()
+            ),
+            f32::INFINITY
+        );
+
+        let positions = [Vec3::new(0., 0., -1.)];
+        assert_eq!(
+            cull(&culling(&[0.05, 0.5], 0.9), &perspective_view(), &positions),
+            vec![vec![], vec![], vec![0]]
+        );
+    }
+
+    /// The indices of each LOD as the GPU culling pass leaves them, given the LOD it picks for each instance, read back
+    /// through the draw arguments.
+    fn gpu_cull(layout: &GpuCullingLayout, lod_instances: &[Vec<usize>]) -> Vec<Vec<usize>> {
+        let mut draw_args = layout.draw_args(1..=layout.lod_count as u32);
+        let mut visible_instances = vec![usize::MAX; layout.visible_instances_len()];
+        for (lod, indices) in lod_instances.iter().enumerate() {
+            for &index in indices {
+                visible_instances[layout.push_visible(&mut draw_args, lod)] = index;
+            }
+        }
+
+        (0..layout.lod_count)
+            .map(|lod| {
+                let args = &draw_args[layout.draw_args_offset(lod)..][..5];
+                assert_eq!(args, [lod as u32 + 1, args[1], 0, 0, 0]);
+                let start = layout.visible_instances_offset(lod);
+                visible_instances[start..start + args[1] as usize].to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gpu_layout_keeps_what_cpu_culling_keeps() {
+        let positions: Vec<_> = (1..40).map(|i| Vec3::new(0., 0., -(i as f32))).collect();
+        let culling = culling(&[0.05, 0.2, 0.5], 0.);
+        let lod_instances = cull(&culling, &perspective_view(), &positions);
+        assert!(lod_instances.iter().all(|lod| !lod.is_empty()));
+
+        for alignment in [1, 3, 64] {
+            let layout = GpuCullingLayout::new(lod_instances.len(), positions.len(), alignment);
+            assert_eq!(layout.lod_stride % alignment, 0);
+            assert!(layout.lod_stride >= positions.len());
+            assert_eq!(gpu_cull(&layout, &lod_instances), lod_instances);
+        }
+    }
+
+    #[test]
+    fn gpu_layout_fits_every_instance_in_one_lod() {
+        let positions: Vec<_> = (0..10).map(|i| Vec3::new(i as f32, 0., -10.)).collect();
+        let culling = culling(&[0.5], 0.);
+        let lod_instances = cull(&culling, &orthographic_view(), &positions);
+        assert_eq!(lod_instances[0].len(), positions.len());
+
+        let layout = GpuCullingLayout::new(lod_instances.len(), positions.len(), 64);
+        assert_eq!(layout.visible_instances_len(), 2 * 64);
+        assert_eq!(layout.draw_args_len(), 2 * 5);
+        assert_eq!(gpu_cull(&layout, &lod_instances), lod_instances);
+    }
+
+    #[test]
+    fn missing_attributes_are_zero_filled() {
+        let mut gathered = Vec::new();
+        gather_attributes(&[1, 2, 3, 4], 2, &[1, 0, 5], &mut gathered);
+        assert_eq!(gathered, [3, 4, 1, 2, 0, 0]);
+    }
+}
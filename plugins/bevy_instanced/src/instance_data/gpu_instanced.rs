@@ -2,38 +2,109 @@ use bevy::{
     ecs::{
         component::Component,
         entity::Entity,
-        system::{Commands, Query, Res},
+        system::{Commands, Query, Res, ResMut, Resource},
     },
+    prelude::{Deref, DerefMut},
     render::{
-        render_resource::{Buffer, BufferInitDescriptor, BufferUsages},
-        renderer::RenderDevice,
+        render_resource::{Buffer, BufferDescriptor, BufferUsages},
+        renderer::{RenderDevice, RenderQueue},
     },
+    utils::HashMap,
 };
 
-use super::instanced::InstancesData;
+use super::instanced::{ExtractedInstances, Instance, InstancesUpdate};
 
-#[derive(Component)]
+// Smallest buffer allocated, in instances, so that small instance counts growing one by one don't reallocate each time.
+const MIN_CAPACITY: usize = 64;
+
+#[derive(Component, Clone)]
 pub struct GpuInstancesData {
     pub buffer: Buffer,
+    /// Number of instances in use.
     pub length: usize,
+    /// Number of instances the buffer can hold.
+    pub capacity: usize,
 }
 
+/// Instance buffers kept across frames, by entity: render world entities are cleared every frame, the buffers aren't.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct GpuInstancesBuffers(HashMap<Entity, GpuInstancesData>);
+
 impl GpuInstancesData {
+    fn allocate(render_device: &RenderDevice, capacity: usize) -> Buffer {
+        render_device.create_buffer(&BufferDescriptor {
+            label: Some("instance data buffer"),
+            size: (capacity * std::mem::size_of::<Instance>()) as u64,
+            usage: BufferUsages::VERTEX | BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Uploads the instances that changed, reusing the entity's buffer when it is large enough and
+    /// doubling it when it is not.
     pub fn prepare(
         mut commands: Commands,
-        query: Query<(Entity, &InstancesData)>,
+        query: Query<(Entity, &ExtractedInstances)>,
         render_device: Res<RenderDevice>,
+        render_queue: Res<RenderQueue>,
+        mut buffers: ResMut<GpuInstancesBuffers>,
     ) {
-        for (entity, instance_data) in &query {
-            let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-                label: Some("instance data buffer"),
-                contents: bytemuck::cast_slice(instance_data.as_slice()),
-                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            });
-            commands.entity(entity).insert(GpuInstancesData {
-                buffer,
-                length: instance_data.len(),
-            });
+        buffers.retain(|entity, _| query.contains(*entity));
+
+        for (entity, extracted) in &query {
+            match &extracted.update {
+                InstancesUpdate::Unchanged => {}
+                InstancesUpdate::All(data) => {
+                    let capacity = match buffers.get(&entity) {
+                        Some(gpu_instances) if gpu_instances.capacity >= data.len() => None,
+                        previous => Some(
+                            previous
+                                .map_or(0, |gpu_instances| 2 * gpu_instances.capacity)
+                                .max(data.len())
+                                .max(MIN_CAPACITY),
+                        ),
+                    };
+                    if let Some(capacity) = capacity {
+                        buffers.insert(
+                            entity,
+                            GpuInstancesData {
+                                buffer: Self::allocate(&render_device, capacity),
+                                length: 0,
+                                capacity,
+                            },
+                        );
+                    }
+                    let Some(gpu_instances) = buffers.get_mut(&entity) else {
+                        continue;
+                    };
+
+                    if !data.is_empty() {
+                        render_queue.write_buffer(
+                            &gpu_instances.buffer,
+                            0,
+                            bytemuck::cast_slice(data.as_slice()),
+                        );
+                    }
+                    gpu_instances.length = data.len();
+                }
+                InstancesUpdate::Ranges(ranges) => {
+                    // Only sent when the length is unchanged, so the buffer exists and is large enough.
+                    let Some(gpu_instances) = buffers.get(&entity) else {
+                        continue;
+                    };
+                    for (start, data) in ranges {
+                        render_queue.write_buffer(
+                            &gpu_instances.buffer,
+                            (start * std::mem::size_of::<Instance>()) as u64,
+                            bytemuck::cast_slice(data.as_slice()),
+                        );
+                    }
+                }
+            }
+
+            if let Some(gpu_instances) = buffers.get(&entity) {
+                commands.entity(entity).insert(gpu_instances.clone());
+            }
         }
     }
 }
@@ -0,0 +1,142 @@
+//! Per-instance hover, selection and visibility states, drawn as tints and as the outline of
+//! [`InstanceOutline`](crate::render::outline::InstanceOutline) cameras.
+
+use bevy::{
+    core::{Pod, Zeroable},
+    ecs::component::Component,
+    render::{
+        color::Color,
+        extract_component::ExtractComponent,
+        render_resource::{ShaderDefVal, VertexFormat},
+    },
+};
+
+use super::attributes::{InstanceAttribute, InstanceAttributes, InstanceLayout};
+
+/// The states of an instance, as a bit set. Instances get them from the [`InstanceAttributes`] of `InstanceFlags` of
+/// their entity, which needs the [`InstanceHighlightPlugin`](crate::plugin::InstanceHighlightPlugin).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct InstanceFlags(pub u32);
+
+impl InstanceFlags {
+    pub const NONE: Self = Self(0);
+    /// Tinted with [`InstanceHighlight::hovered_tint`], which wins over the selected one.
+    pub const HOVERED: Self = Self(1);
+    /// Tinted with [`InstanceHighlight::selected_tint`].
+    pub const SELECTED: Self = Self(1 << 1);
+    /// Not drawn at all.
+    pub const HIDDEN: Self = Self(1 << 2);
+
+    pub fn contains(&self, flags: InstanceFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn set(&mut self, flags: InstanceFlags, value: bool) {
+        if value {
+            self.0 |= flags.0;
+        } else {
+            self.0 &= !flags.0;
+        }
+    }
+}
+
+impl std::ops::BitOr for InstanceFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl InstanceLayout for InstanceFlags {
+    const ATTRIBUTES: &'static [InstanceAttribute] = &[InstanceAttribute {
+        name: "flags",
+        format: VertexFormat::Uint32,
+    }];
+}
+
+impl InstanceAttributes<InstanceFlags> {
+    /// No flags for each of `length` instances.
+    pub fn flags(length: usize) -> Self {
+        Self(vec![InstanceFlags::NONE; length])
+    }
+
+    /// Sets or clears `flags` on the instances at `indices`, leaving the others as they are.
+    /// Indices beyond the instances are ignored.
+    pub fn set_flags(
+        &mut self,
+        indices: impl IntoIterator<Item = usize>,
+        flags: InstanceFlags,
+        value: bool,
+    ) {
+        for index in indices {
+            if let Some(instance_flags) = self.get_mut(index) {
+                instance_flags.set(flags, value);
+            }
+        }
+    }
+
+    /// Sets `flags` on the instances at `indices` and clears them on every other instance, e.g. to select the atoms
+    /// of a new selection.
+    pub fn set_flags_only(
+        &mut self,
+        indices: impl IntoIterator<Item = usize>,
+        flags: InstanceFlags,
+    ) {
+        self.clear_flags(flags);
+        self.set_flags(indices, flags, true);
+    }
+
+    pub fn clear_flags(&mut self, flags: InstanceFlags) {
+        for instance_flags in self.iter_mut() {
+            instance_flags.set(flags, false);
+        }
+    }
+}
+
+/// The tints of the hovered and selected instances of the entity. Each replaces the colour of the instance in the
+/// proportion of its alpha.
+///
+/// The flags are [`InstanceAttributes`], which GPU culling doesn't read: entities with them are culled on the CPU
+/// whatever their [`InstanceCulling`](super::culling::InstanceCulling) asks for.
+#[derive(Component, ExtractComponent, Clone, Copy, Debug)]
+pub struct InstanceHighlight {
+    pub hovered_tint: Color,
+    pub selected_tint: Color,
+}
+
+impl Default for InstanceHighlight {
+    fn default() -> Self {
+        InstanceHighlight {
+            hovered_tint: Color::ORANGE,
+            selected_tint: Color::YELLOW,
+        }
+    }
+}
+
+impl InstanceHighlight {
+    pub(crate) fn key(&self) -> HighlightKey {
+        HighlightKey {
+            hovered_tint: self.hovered_tint.as_rgba_u32(),
+            selected_tint: self.selected_tint.as_rgba_u32(),
+        }
+    }
+}
+
+/// The tints of an [`InstanceHighlight`] packed as RGBA8, for the shaders to get them as shader defs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HighlightKey {
+    hovered_tint: u32,
+    selected_tint: u32,
+}
+
+impl HighlightKey {
+    pub(crate) fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        vec![
+            "INSTANCE_HIGHLIGHT".into(),
+            ShaderDefVal::UInt("INSTANCE_HOVERED_TINT".into(), self.hovered_tint),
+            ShaderDefVal::UInt("INSTANCE_SELECTED_TINT".into(), self.selected_tint),
+        ]
+    }
+}
@@ -0,0 +1,258 @@
+//! Ray-cast imposters: each instance is drawn as a quad facing the camera, on which the fragment shader intersects
+//! the view ray with an analytic shape and writes the depth and normal of the hit.
+//!
+//! The intersection functions here are the CPU counterparts of those in `imposter_functions.wgsl`, which they
+//! mirror line for line: they also serve picking.
+
+use bevy::{
+    ecs::component::Component,
+    math::{primitives::Rectangle, Vec3},
+    render::{
+        extract_component::ExtractComponent, mesh::Mesh, primitives::Aabb,
+        render_resource::ShaderDefVal,
+    },
+};
+
+/// Draws the instances of the entity as ray-cast shapes rather than with its mesh, which must then be
+/// [`Imposter::quad`]. The shape is in instance space, before the instance's scale, rotation and position apply.
+///
+/// The imposter shaders read the bindings of `StandardMaterial`, so imposters are only drawn by
+/// `InstancedMaterialPlugin<StandardMaterial>`.
+#[derive(Component, ExtractComponent, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Imposter {
+    /// A sphere of unit diameter, like `Sphere::new(0.5)`. Its radius follows the largest scale of the instance.
+    Sphere,
+    /// A capped cylinder of unit diameter and height along the Y axis, like `Cylinder::new(0.5, 1.0)`.
+    /// Its radius follows the X scale of the instance, which is expected to equal the Z one.
+    Cylinder,
+}
+
+impl Imposter {
+    /// The mesh of imposter entities: the unit quad the vertex shader turns to face the camera and stretches over
+    /// each instance.
+    pub fn quad() -> Mesh {
+        Rectangle::new(1.0, 1.0).into()
+    }
+
+    /// The bounds of the shape, standing in for those of the mesh when culling.
+    pub fn aabb(&self) -> Aabb {
+        Aabb {
+            center: Default::default(),
+            half_extents: Vec3::splat(0.5).into(),
+        }
+    }
+
+    pub(crate) fn shader_def(&self) -> ShaderDefVal {
+        match self {
+            Imposter::Sphere => "IMPOSTER_SPHERE".into(),
+            Imposter::Cylinder => "IMPOSTER_CYLINDER".into(),
+        }
+    }
+}
+
+/// Where a ray meets the surface of a shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    /// Distance from the origin of the ray, in units of its direction.
+    pub distance: f32,
+    /// The outward unit normal of the surface.
+    pub normal: Vec3,
+}
+
+/// The first intersection ahead of `origin` of a ray along the unit vector `direction` with a sphere.
+/// A ray starting inside the sphere doesn't hit it.
+pub fn ray_sphere(origin: Vec3, direction: Vec3, centre: Vec3, radius: f32) -> Option<RayHit> {
+    let to_origin = origin - centre;
+    let b = to_origin.dot(direction);
+    let c = to_origin.length_squared() - radius * radius;
+    let h = b * b - c;
+    if h < 0.0 {
+        return None;
+    }
+
+    let distance = -b - h.sqrt();
+    (distance >= 0.0).then(|| RayHit {
+        distance,
+        normal: (to_origin + distance * direction) / radius,
+    })
+}
+
+/// The first intersection ahead of `origin` of a ray along the unit vector `direction` with a cylinder whose caps are
+/// discs centred on `start` and `end`. A ray starting inside the cylinder doesn't hit it.
+pub fn ray_capped_cylinder(
+    origin: Vec3,
+    direction: Vec3,
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+) -> Option<RayHit> {
+    let axis = end - start;
+    let to_origin = origin - start;
+    let axis_axis = axis.dot(axis);
+    let axis_direction = axis.dot(direction);
+    let axis_origin = axis.dot(to_origin);
+
+    // The distances at which the ray crosses the infinite cylinder solve k2 t² + 2 k1 t + k0 = 0.
+    let k2 = axis_axis - axis_direction * axis_direction;
+    let k1 = axis_axis * to_origin.dot(direction) - axis_origin * axis_direction;
+    let k0 = axis_axis * to_origin.dot(to_origin)
+        - axis_origin * axis_origin
+        - radius * radius * axis_axis;
+    let h = k1 * k1 - k2 * k0;
+    if h < 0.0 {
+        return None;
+    }
+
+    // The ray is parallel to the axis when k2 is 0: it can only enter through a cap.
+    let parallel = k2 <= 1e-6 * axis_axis;
+    let mut along_axis = 0.0;
+    if !parallel {
+        let distance = (-k1 - h.sqrt()) / k2;
+        along_axis = axis_origin + distance * axis_direction;
+        if distance >= 0.0 && along_axis > 0.0 && along_axis < axis_axis {
+            return Some(RayHit {
+                distance,
+                normal: (to_origin + distance * direction - axis * along_axis / axis_axis) / radius,
+            });
+        }
+    }
+
+    // Otherwise the ray enters through the cap on the side of the axis it crossed the infinite cylinder at, which it
+    // has to be heading into: from inside, it would leave through it.
+    let at_start = if parallel {
+        axis_direction > 0.0
+    } else {
+        along_axis <= 0.0
+    };
+    let faces_cap = if at_start {
+        axis_direction > 0.0
+    } else {
+        axis_direction < 0.0
+    };
+    let cap = if at_start { 0.0 } else { axis_axis };
+    let distance = (cap - axis_origin) / axis_direction;
+    let point = to_origin + distance * direction;
+    let radial = point - axis * (point.dot(axis) / axis_axis);
+    (faces_cap && distance >= 0.0 && radial.length_squared() <= radius * radius).then(|| RayHit {
+        distance,
+        normal: if at_start { -axis } else { axis } / axis_axis.sqrt(),
+    })
+}
+
+/// How much to enlarge a quad facing a perspective camera, through the centre of a shape within `radius` of it, so
+/// that it covers the shape on screen: the parts of the shape nearer to the camera than the quad look larger.
+pub fn perspective_scale(distance: f32, radius: f32) -> f32 {
+    // Within the shape, the quad can't cover it: make it as large as the near plane allows.
+    distance / (distance - radius).max(1e-3 * distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_hit(hit: Option<RayHit>, distance: f32, normal: Vec3) {
+        let hit = hit.expect("the ray should hit the shape");
+        assert!(
+            (hit.distance - distance).abs() < EPSILON,
+            "hit at {}, expected {distance}",
+            hit.distance
+        );
+        assert!(
+            hit.normal.abs_diff_eq(normal, EPSILON),
+            "normal {}, expected {normal}",
+            hit.normal
+        );
+    }
+
+    #[test]
+    fn sphere_hit() {
+        let centre = Vec3::new(1., 2., 3.);
+        assert_hit(
+            ray_sphere(centre - 5. * Vec3::X, Vec3::X, centre, 2.),
+            3.,
+            -Vec3::X,
+        );
+    }
+
+    #[test]
+    fn sphere_miss() {
+        // Passing beside it, and pointing away from it.
+        assert_eq!(
+            ray_sphere(Vec3::new(-5., 2.001, 0.), Vec3::X, Vec3::ZERO, 2.),
+            None
+        );
+        assert_eq!(ray_sphere(-5. * Vec3::X, -Vec3::X, Vec3::ZERO, 2.), None);
+    }
+
+    #[test]
+    fn sphere_ray_from_inside() {
+        assert_eq!(ray_sphere(Vec3::ZERO, Vec3::X, Vec3::ZERO, 2.), None);
+    }
+
+    /// Along the Y axis, from the origin to a height of 2, with a radius of 1.
+    fn cylinder(origin: Vec3, direction: Vec3) -> Option<RayHit> {
+        ray_capped_cylinder(origin, direction.normalize(), Vec3::ZERO, 2. * Vec3::Y, 1.)
+    }
+
+    #[test]
+    fn cylinder_body_hit() {
+        assert_hit(cylinder(Vec3::new(-5., 1., 0.), Vec3::X), 4., -Vec3::X);
+        assert_hit(cylinder(Vec3::new(0., 0.5, 5.), -Vec3::Z), 4., Vec3::Z);
+    }
+
+    #[test]
+    fn cylinder_ray_parallel_to_the_axis() {
+        // Through the start cap going up, and through the end cap going down.
+        assert_hit(cylinder(Vec3::new(0.3, -5., 0.), Vec3::Y), 5., -Vec3::Y);
+        assert_hit(cylinder(Vec3::new(0., 5., -0.5), -Vec3::Y), 3., Vec3::Y);
+        // Just beyond the rim.
+        assert_eq!(cylinder(Vec3::new(1.01, -5., 0.), Vec3::Y), None);
+    }
+
+    #[test]
+    fn cylinder_oblique_cap_hits() {
+        // The origins are inside the infinite cylinder, below and above the caps.
+        let direction = Vec3::new(0.5, 3., 0.);
+        assert_hit(
+            cylinder(Vec3::new(0., -3., 0.), direction),
+            direction.length(),
+            -Vec3::Y,
+        );
+        let direction = Vec3::new(0.5, -3., 0.);
+        assert_hit(
+            cylinder(Vec3::new(0., 5., 0.), direction),
+            direction.length(),
+            Vec3::Y,
+        );
+    }
+
+    #[test]
+    fn cylinder_miss() {
+        // Grazing the side, passing beside a cap, and pointing away.
+        assert_eq!(cylinder(Vec3::new(-5., 1., 1.001), Vec3::X), None);
+        assert_eq!(cylinder(Vec3::new(-5., 2.001, 0.), Vec3::X), None);
+        assert_eq!(cylinder(Vec3::new(-5., 1., 0.), -Vec3::X), None);
+    }
+
+    #[test]
+    fn cylinder_ray_from_inside() {
+        assert_eq!(cylinder(Vec3::Y, Vec3::X), None);
+        // Leaving through the side, and through a cap.
+        assert_eq!(
+            cylinder(Vec3::new(0., 1.5, 0.), Vec3::new(-1., 0.5, 0.)),
+            None
+        );
+        assert_eq!(cylinder(Vec3::Y, Vec3::new(0.1, 1., 0.)), None);
+        assert_eq!(cylinder(Vec3::Y, -Vec3::Y), None);
+    }
+
+    #[test]
+    fn perspective_scale_covers_the_shape() {
+        assert!((perspective_scale(10., 1.) - 10. / 9.).abs() < EPSILON);
+        // From within the shape the scale is bounded rather than infinite or negative.
+        assert!(perspective_scale(1., 2.).is_finite());
+        assert!(perspective_scale(1., 2.) > 0.);
+    }
+}
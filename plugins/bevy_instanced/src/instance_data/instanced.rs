@@ -1,48 +1,296 @@
+use std::ops::Range;
+
 use bevy::{
+    asset::Handle,
     core::{Pod, Zeroable},
-    ecs::{component::Component, query::QueryItem},
-    math::Vec3,
-    prelude::Deref,
-    render::extract_component::ExtractComponent,
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        entity::Entity,
+        query::QueryItem,
+        system::{Commands, Local, Query},
+        world::Ref,
+    },
+    math::{Quat, Vec3},
+    pbr::Material,
+    prelude::{Deref, DetectChanges},
+    render::{extract_component::ExtractComponent, render_resource::VertexFormat, Extract},
+    transform::components::Transform,
+    utils::HashMap,
 };
 
+use super::attributes::{InstanceAttribute, InstanceLayout};
+
+/// Where, how and in which colour a copy of the mesh is drawn: the mesh is scaled, then rotated, then moved
+/// to `position`, all within the frame of the instanced entity.
 #[derive(Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 pub struct Instance {
     position: Vec3,
-    scale: f32,
+    scale: Vec3,
+    // A quaternion stored as an array, as `Quat` may be 16-byte aligned which would add padding.
+    rotation: [f32; 4],
     color: [f32; 4],
 }
 
 impl Instance {
+    /// An unrotated instance scaled by the same amount along each axis.
     pub fn new(position: Vec3, scale: f32, color: [f32; 4]) -> Self {
+        Self::from_transform(
+            Transform::from_translation(position).with_scale(Vec3::splat(scale)),
+            color,
+        )
+    }
+
+    pub fn from_transform(transform: Transform, color: [f32; 4]) -> Self {
         Self {
-            position,
-            scale,
+            position: transform.translation,
+            scale: transform.scale,
+            rotation: transform.rotation.to_array(),
             color,
         }
     }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_array(self.rotation)
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.position,
+            rotation: self.rotation(),
+            scale: self.scale,
+        }
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.position = transform.translation;
+        self.scale = transform.scale;
+        self.rotation = transform.rotation.to_array();
+    }
+
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.color = color;
+    }
+}
+
+impl InstanceLayout for Instance {
+    const ATTRIBUTES: &'static [InstanceAttribute] = &[
+        InstanceAttribute {
+            name: "position",
+            format: VertexFormat::Float32x3,
+        },
+        InstanceAttribute {
+            name: "scale",
+            format: VertexFormat::Float32x3,
+        },
+        InstanceAttribute {
+            name: "rotation",
+            format: VertexFormat::Float32x4,
+        },
+        InstanceAttribute {
+            name: "color",
+            format: VertexFormat::Float32x4,
+        },
+    ];
 }
 
+/// The instances of an instanced mesh.
+///
+/// The GPU buffer is only written when this component changes, and then only the instances changed through
+/// [`get_mut`](Self::get_mut) or [`range_mut`](Self::range_mut); [`data_mut`](Self::data_mut) rewrites all of them.
 #[derive(Component, Deref)]
 pub struct InstancesData {
-    pub data: Vec<Instance>,
+    #[deref]
+    data: Vec<Instance>,
+    dirty_ranges: Vec<Range<usize>>,
+    /// Set by [`data_mut`](Self::data_mut), which may have changed any instance.
+    all_dirty: bool,
 }
 
 impl InstancesData {
     pub fn new(data: Vec<Instance>) -> Self {
-        Self { data }
+        Self {
+            data,
+            dirty_ranges: Vec::new(),
+            all_dirty: false,
+        }
+    }
+
+    /// Every instance, all of which are uploaded on the next update.
+    pub fn data_mut(&mut self) -> &mut Vec<Instance> {
+        self.all_dirty = true;
+        &mut self.data
+    }
+
+    /// The instances in `range`, which are uploaded on the next update. Panics if `range` is out of bounds.
+    pub fn range_mut(&mut self, range: Range<usize>) -> &mut [Instance] {
+        self.mark_dirty(range.clone());
+        &mut self.data[range]
+    }
+
+    /// The instance at `index`, which is uploaded on the next update.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Instance> {
+        let instance = self.data.get_mut(index)?;
+        if !self.all_dirty {
+            self.dirty_ranges.push(index..index + 1);
+        }
+        Some(instance)
+    }
+
+    /// Only upload the instances in `range` (and any other marked range) on the next update, rather than all of them.
+    /// Ignored if the number of instances changed since the last update.
+    pub fn mark_dirty(&mut self, range: Range<usize>) {
+        if !range.is_empty() && !self.all_dirty {
+            self.dirty_ranges.push(range);
+        }
+    }
+
+    /// The marked ranges, sorted, merged and clipped to the instances. Empty means everything changed.
+    pub fn dirty_ranges(&self) -> Vec<Range<usize>> {
+        if self.all_dirty {
+            return Vec::new();
+        }
+        let mut ranges: Vec<Range<usize>> = self
+            .dirty_ranges
+            .iter()
+            .map(|range| range.start.min(self.data.len())..range.end.min(self.data.len()))
+            .filter(|range| !range.is_empty())
+            .collect();
+        ranges.sort_unstable_by_key(|range| range.start);
+
+        let mut merged = Vec::<Range<usize>>::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// Forgets the marked ranges once they have been extracted, without flagging the component as changed.
+    pub fn clear_dirty_ranges(mut query: Query<&mut InstancesData>) {
+        for mut instances in query.iter_mut() {
+            if !instances.dirty_ranges.is_empty() || instances.all_dirty {
+                let instances = instances.bypass_change_detection();
+                instances.dirty_ranges.clear();
+                instances.all_dirty = false;
+            }
+        }
     }
 }
 
-impl ExtractComponent for InstancesData {
-    type QueryData = &'static InstancesData;
+/// The material the instances are shaded with. The colour of each instance multiplies its base colour.
+#[derive(Component, Deref)]
+pub struct InstancedMaterial<M: Material>(pub Handle<M>);
+
+impl<M: Material> Clone for InstancedMaterial<M> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<M: Material> ExtractComponent for InstancedMaterial<M> {
+    type QueryData = &'static Self;
     type QueryFilter = ();
     type Out = Self;
 
     fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
-        Some(InstancesData {
-            data: item.data.clone(),
-        })
+        Some(item.clone())
+    }
+}
+
+/// What changed in an [`InstancesData`] since it was last extracted.
+pub enum InstancesUpdate {
+    Unchanged,
+    All(Vec<Instance>),
+    /// Each run of instances, with the index of its first instance.
+    Ranges(Vec<(usize, Vec<Instance>)>),
+}
+
+/// The render world counterpart of [`InstancesData`], carrying only what changed.
+#[derive(Component)]
+pub struct ExtractedInstances {
+    pub length: usize,
+    pub update: InstancesUpdate,
+}
+
+impl ExtractedInstances {
+    pub fn extract(
+        mut commands: Commands,
+        query: Extract<Query<(Entity, Ref<InstancesData>)>>,
+        mut lengths: Local<HashMap<Entity, usize>>,
+    ) {
+        let mut extracted = Vec::new();
+        let mut previous_lengths = std::mem::take(&mut *lengths);
+
+        for (entity, instances) in query.iter() {
+            let length = instances.len();
+            let previous_length = previous_lengths.remove(&entity);
+            lengths.insert(entity, length);
+
+            let update = if instances.is_added() || previous_length != Some(length) {
+                InstancesUpdate::All(instances.data.clone())
+            } else if !instances.is_changed() {
+                InstancesUpdate::Unchanged
+            } else {
+                let ranges = instances.dirty_ranges();
+                if ranges.is_empty() {
+                    InstancesUpdate::All(instances.data.clone())
+                } else {
+                    InstancesUpdate::Ranges(
+                        ranges
+                            .into_iter()
+                            .map(|range| (range.start, instances.data[range].to_vec()))
+                            .collect(),
+                    )
+                }
+            };
+
+            extracted.push((entity, ExtractedInstances { length, update }));
+        }
+
+        commands.insert_or_spawn_batch(extracted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instances(count: usize) -> InstancesData {
+        InstancesData::new(vec![Instance::new(Vec3::ZERO, 1., [1.; 4]); count])
+    }
+
+    #[test]
+    fn accessors_mark_what_they_change() {
+        let mut instances = instances(10);
+        instances.get_mut(7).unwrap().set_color([0.; 4]);
+        instances.range_mut(2..4)[0].set_color([0.; 4]);
+        instances.range_mut(3..5);
+        assert!(instances.get_mut(10).is_none());
+        assert_eq!(instances.dirty_ranges(), vec![2..5, 7..8]);
+    }
+
+    #[test]
+    fn data_mut_marks_everything() {
+        let mut instances = instances(10);
+        instances.get_mut(7);
+        instances.data_mut()[0].set_color([0.; 4]);
+        instances.mark_dirty(1..2);
+        assert!(instances.dirty_ranges().is_empty());
     }
 }
@@ -1,2 +1,7 @@
+pub mod attributes;
+pub mod bounds;
+pub mod culling;
 pub mod gpu_instanced;
+pub mod highlight;
+pub mod imposter;
 pub mod instanced;
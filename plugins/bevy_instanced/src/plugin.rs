@@ -1,17 +1,51 @@
 use std::{hash::Hash, marker::PhantomData};
 
 use bevy::{
-    core_pipeline::core_3d::Transparent3d,
+    core_pipeline::{
+        core_3d::{
+            graph::{Core3d, Node3d},
+            AlphaMask3d, Opaque3d, Transmissive3d, Transparent3d,
+        },
+        prepass::{AlphaMask3dPrepass, Opaque3dPrepass},
+    },
+    pbr::{MeshPipeline, PrepassPipeline, Shadow},
     prelude::*,
     render::{
-        extract_component::ExtractComponentPlugin, render_phase::AddRenderCommand,
-        render_resource::*, Render, RenderApp, RenderSet,
+        batching::write_batched_instance_buffer,
+        extract_component::{ExtractComponentPlugin, UniformComponentPlugin},
+        graph::CameraDriverLabel,
+        render_graph::{RenderGraph, RenderGraphApp, ViewNodeRunner},
+        render_phase::AddRenderCommand,
+        render_resource::*,
+        view::VisibilitySystems,
+        ExtractSchedule, Render, RenderApp, RenderSet,
     },
 };
 
 use crate::{
-    instance_data::{gpu_instanced::GpuInstancesData, instanced::InstancesData},
+    instance_data::{
+        attributes::{
+            check_layout, ExtractedInstanceAttributes, InstanceAttributeLayouts,
+            InstanceAttributes, InstanceAttributesBuffers, InstanceLayout,
+        },
+        bounds::InstancedMeshBounds,
+        culling::{ExtractedInstanceCulling, VisibleInstancesBuffers},
+        gpu_instanced::{GpuInstancesBuffers, GpuInstancesData},
+        highlight::{InstanceFlags, InstanceHighlight},
+        imposter::Imposter,
+        instanced::{ExtractedInstances, InstancedMaterial, InstancesData},
+    },
     render::{
+        gpu_culling::{GpuCullingJobs, GpuCullingLabel, GpuCullingNode, GpuCullingPipeline},
+        mesh_index::MeshIndices,
+        outline::{
+            InstanceOutline, InstanceOutlineLabel, InstanceOutlineNode, InstanceOutlinePipeline,
+            ViewInstanceOutlinePipeline,
+        },
+        prepass::{
+            queue_instanced_prepass, queue_instanced_shadows, DrawInstancedPrepass,
+            InstancedPrepassPipeline,
+        },
         render_pipeline::{queue_instanced_material, DrawInstanced, InstancedRenderPipeline},
         shaders::load_instancing_shaders,
     },
@@ -28,16 +62,162 @@ where
     M::Data: PartialEq + Eq + Hash + Clone,
 {
     fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractComponentPlugin::<InstancesData>::default())
-            .init_resource::<InternalShaders>();
+        if !app.is_plugin_added::<InstancingPlugin>() {
+            app.add_plugins(InstancingPlugin);
+        }
+        app.add_plugins(ExtractComponentPlugin::<InstancedMaterial<M>>::default());
         app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawInstanced<M>>()
+            .add_render_command::<AlphaMask3d, DrawInstanced<M>>()
+            .add_render_command::<Transmissive3d, DrawInstanced<M>>()
             .add_render_command::<Transparent3d, DrawInstanced<M>>()
             .init_resource::<SpecializedMeshPipelines<InstancedRenderPipeline<M>>>()
             .add_systems(
                 Render,
+                queue_instanced_material::<M>.in_set(RenderSet::QueueMeshes),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        load_instancing_shaders(app);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.init_resource::<InstancedRenderPipeline<M>>();
+
+        // The prepass pipeline of the material only exists when `MaterialPlugin` enables the prepass or shadows.
+        if render_app.world.contains_resource::<PrepassPipeline<M>>() {
+            render_app
+                .add_render_command::<Shadow, DrawInstancedPrepass<M>>()
+                .add_render_command::<Opaque3dPrepass, DrawInstancedPrepass<M>>()
+                .add_render_command::<AlphaMask3dPrepass, DrawInstancedPrepass<M>>()
+                .init_resource::<InstancedPrepassPipeline<M>>()
+                .init_resource::<SpecializedMeshPipelines<InstancedPrepassPipeline<M>>>()
+                .add_systems(
+                    Render,
+                    (queue_instanced_prepass::<M>, queue_instanced_shadows::<M>)
+                        .in_set(RenderSet::QueueMeshes),
+                );
+        }
+    }
+}
+
+/// What the plugins of every material share: the extraction, culling and upload of the instances, added once.
+struct InstancingPlugin;
+
+impl Plugin for InstancingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<Imposter>::default())
+            .init_resource::<InternalShaders>()
+            .add_systems(First, InstancesData::clear_dirty_ranges)
+            .add_systems(
+                PostUpdate,
+                InstancedMeshBounds::update
+                    .after(VisibilitySystems::CalculateBounds)
+                    .before(VisibilitySystems::CheckVisibility),
+            );
+        app.sub_app_mut(RenderApp)
+            .init_resource::<GpuInstancesBuffers>()
+            .init_resource::<VisibleInstancesBuffers>()
+            .init_resource::<InstanceAttributesBuffers>()
+            .init_resource::<GpuCullingJobs>()
+            .add_systems(
+                ExtractSchedule,
+                (
+                    ExtractedInstances::extract,
+                    ExtractedInstanceCulling::extract,
+                ),
+            )
+            .add_systems(
+                Render,
+                (
+                    (
+                        GpuInstancesData::prepare,
+                        InstanceAttributesBuffers::prepare,
+                        VisibleInstancesBuffers::prepare.after(InstanceAttributesBuffers::prepare),
+                        GpuCullingJobs::prepare.after(GpuInstancesData::prepare),
+                    )
+                        .in_set(RenderSet::PrepareResources),
+                    MeshIndices::prepare
+                        .in_set(RenderSet::PrepareResourcesFlush)
+                        .before(write_batched_instance_buffer::<MeshPipeline>),
+                    GpuCullingJobs::prepare_bind_groups.in_set(RenderSet::PrepareBindGroups),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        load_instancing_shaders(app);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<GpuCullingPipeline>()
+            .init_resource::<MeshIndices>();
+
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node(GpuCullingLabel, GpuCullingNode);
+        render_graph.add_node_edge(GpuCullingLabel, CameraDriverLabel);
+    }
+}
+
+/// Extracts the [`InstanceAttributes`](crate::instance_data::attributes::InstanceAttributes) of `T`, for the
+/// instanced materials to draw them.
+pub struct InstanceAttributesPlugin<T: InstanceLayout> {
+    pub _marker: PhantomData<T>,
+}
+
+impl<T: InstanceLayout> Default for InstanceAttributesPlugin<T> {
+    fn default() -> Self {
+        InstanceAttributesPlugin {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: InstanceLayout> Plugin for InstanceAttributesPlugin<T> {
+    fn build(&self, app: &mut App) {
+        check_layout::<T>();
+        app.init_resource::<InstanceAttributeLayouts>().add_systems(
+            PostUpdate,
+            (
+                InstanceAttributes::<T>::reject_second_layout,
+                InstanceAttributes::<T>::refuse_gpu_culling,
+            ),
+        );
+        app.sub_app_mut(RenderApp)
+            .add_systems(ExtractSchedule, ExtractedInstanceAttributes::extract::<T>);
+    }
+}
+
+/// Draws the [`InstanceFlags`] of the instances of entities with an [`InstanceHighlight`], and their outline for the
+/// cameras with an [`InstanceOutline`].
+pub struct InstanceHighlightPlugin;
+
+impl Plugin for InstanceHighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            InstanceAttributesPlugin::<InstanceFlags>::default(),
+            ExtractComponentPlugin::<InstanceHighlight>::default(),
+            ExtractComponentPlugin::<InstanceOutline>::default(),
+            UniformComponentPlugin::<InstanceOutline>::default(),
+        ))
+        .init_resource::<InternalShaders>();
+        app.sub_app_mut(RenderApp)
+            .init_resource::<SpecializedRenderPipelines<InstanceOutlinePipeline>>()
+            .add_systems(
+                Render,
+                ViewInstanceOutlinePipeline::prepare.in_set(RenderSet::Prepare),
+            )
+            .add_render_graph_node::<ViewNodeRunner<InstanceOutlineNode>>(
+                Core3d,
+                InstanceOutlineLabel,
+            )
+            // After tonemapping, so that the outline has the exact colours it is given.
+            .add_render_graph_edges(
+                Core3d,
                 (
-                    queue_instanced_material::<M>.in_set(RenderSet::QueueMeshes),
-                    GpuInstancesData::prepare.in_set(RenderSet::PrepareResources),
+                    Node3d::Tonemapping,
+                    InstanceOutlineLabel,
+                    Node3d::EndMainPassPostProcessing,
                 ),
             );
     }
@@ -46,6 +226,6 @@ where
         load_instancing_shaders(app);
 
         app.sub_app_mut(RenderApp)
-            .init_resource::<InstancedRenderPipeline<M>>();
+            .init_resource::<InstanceOutlinePipeline>();
     }
 }
@@ -0,0 +1,39 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    pbr::{Material, RenderMaterials},
+    render::render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+};
+
+use crate::instance_data::instanced::InstancedMaterial;
+
+/// Binds the [`InstancedMaterial`] of the drawn entity at index `I`, as `SetMaterialBindGroup` does for regular meshes.
+pub struct SetInstancedMaterialBindGroup<M: Material, const I: usize>(PhantomData<M>);
+
+impl<P: PhaseItem, M: Material, const I: usize> RenderCommand<P>
+    for SetInstancedMaterialBindGroup<M, I>
+{
+    type Param = SRes<RenderMaterials<M>>;
+    type ViewQuery = ();
+    type ItemQuery = Read<InstancedMaterial<M>>;
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        material: Option<&'w InstancedMaterial<M>>,
+        materials: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(material) = material else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(material) = materials.into_inner().get(&material.id()) else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_bind_group(I, &material.bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
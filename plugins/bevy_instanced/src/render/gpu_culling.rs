@@ -0,0 +1,443 @@
+//! GPU-driven instancing: a compute pass culls the instances of each entity for each view and picks their LOD,
+//! writing the indices of the visible instances and the arguments of one indirect draw per LOD.
+
+use std::num::NonZeroU64;
+
+use bevy::{
+    ecs::system::{Query, Res, ResMut, Resource},
+    math::{Mat4, Vec3, Vec4},
+    prelude::*,
+    render::{
+        mesh::GpuBufferInfo,
+        primitives::Frustum,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraphContext, RenderLabel},
+        render_resource::{binding_types::*, *},
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        view::ExtractedView,
+    },
+    utils::HashMap,
+};
+
+use crate::instance_data::{
+    culling::{CullingBackend, ExtractedInstanceCulling, MAX_GPU_LODS},
+    gpu_instanced::GpuInstancesBuffers,
+    instanced::Instance,
+};
+
+use super::{
+    mesh_index::{mesh_index_layout_entry, MeshIndices},
+    shaders::CULLING_SHADER,
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+/// Words of indirect draw arguments per LOD: those of an indexed draw, the first four of which are those of a
+/// non-indexed one.
+const DRAW_ARGS_WORDS: usize = 5;
+
+/// Where the culling pass of one job writes the indices of the visible instances of each LOD and their draw arguments,
+/// as `instance_culling.wgsl` does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GpuCullingLayout {
+    /// The LODs, the mesh of the entity included.
+    pub lod_count: usize,
+    /// Distance between the indices of consecutive LODs, in indices, aligned for dynamic offsets.
+    pub lod_stride: usize,
+}
+
+impl GpuCullingLayout {
+    /// Room for `capacity` instances in each of `lod_count` LODs, each starting at a multiple of `alignment` indices.
+    pub fn new(lod_count: usize, capacity: usize, alignment: usize) -> Self {
+        Self {
+            lod_count,
+            lod_stride: capacity.next_multiple_of(alignment.max(1)),
+        }
+    }
+
+    /// Length of the visible instance indices, in indices.
+    pub fn visible_instances_len(&self) -> usize {
+        self.lod_count * self.lod_stride
+    }
+
+    /// Length of the draw arguments, in words.
+    pub fn draw_args_len(&self) -> usize {
+        self.lod_count * DRAW_ARGS_WORDS
+    }
+
+    /// Index of the first visible instance of a LOD.
+    pub fn visible_instances_offset(&self, lod: usize) -> usize {
+        lod * self.lod_stride
+    }
+
+    /// Word of the draw arguments of a LOD.
+    pub fn draw_args_offset(&self, lod: usize) -> usize {
+        lod * DRAW_ARGS_WORDS
+    }
+
+    /// Word of the instance count of a LOD, which the culling pass counts up from 0.
+    pub fn instance_count_offset(&self, lod: usize) -> usize {
+        self.draw_args_offset(lod) + 1
+    }
+
+    /// The draw arguments before culling: the index (or vertex) count of the mesh of each LOD and no instances.
+    pub fn draw_args(&self, counts: impl IntoIterator<Item = u32>) -> Vec<u32> {
+        let mut draw_args = vec![0; self.draw_args_len()];
+        for (lod, count) in counts.into_iter().enumerate().take(self.lod_count) {
+            draw_args[self.draw_args_offset(lod)] = count;
+        }
+        draw_args
+    }
+
+    /// Counts an instance visible with `lod` in the draw arguments, as the culling pass does, and returns where its
+    /// index goes in the visible instance indices.
+    pub fn push_visible(&self, draw_args: &mut [u32], lod: usize) -> usize {
+        let instance_count = &mut draw_args[self.instance_count_offset(lod)];
+        let slot = *instance_count as usize;
+        *instance_count += 1;
+        self.visible_instances_offset(lod) + slot
+    }
+}
+
+/// The layout of the mesh index, instances and visible instance indices read by the vertex shaders of GPU culled
+/// entities.
+pub fn instances_storage_layout(render_device: &RenderDevice) -> BindGroupLayout {
+    render_device.create_bind_group_layout(
+        "instances storage layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::VERTEX,
+            (
+                mesh_index_layout_entry(),
+                storage_buffer_read_only_sized(false, None),
+                // Offset to the indices of the LOD being drawn.
+                storage_buffer_read_only_sized(true, None),
+            ),
+        ),
+    )
+}
+
+/// Matches `Culling` in `instance_culling.wgsl`.
+#[derive(ShaderType, Default)]
+pub struct CullingUniform {
+    pub world_from_local: Mat4,
+    pub view_from_world: Mat4,
+    pub frustum: [Vec4; 6],
+    pub mesh_center: Vec3,
+    pub world_scale: f32,
+    pub mesh_half_extents: Vec3,
+    pub projection_scale: f32,
+    pub lod_max_screen_sizes: Vec4,
+    pub lod_count: u32,
+    pub instance_count: u32,
+    pub lod_stride: u32,
+    pub min_screen_size: f32,
+    pub is_orthographic: u32,
+}
+
+#[derive(Resource)]
+pub struct GpuCullingPipeline {
+    culling_layout: BindGroupLayout,
+    instances_layout: BindGroupLayout,
+    pipeline_id: CachedComputePipelineId,
+}
+
+impl FromWorld for GpuCullingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let culling_layout = render_device.create_bind_group_layout(
+            "instance culling layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer::<CullingUniform>(false),
+                    storage_buffer_read_only_sized(false, None),
+                    storage_buffer_sized(false, None),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+        let instances_layout = instances_storage_layout(render_device);
+
+        let shader = world.resource::<AssetServer>().load(CULLING_SHADER);
+        let pipeline_id = world
+            .resource_mut::<PipelineCache>()
+            .queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("instance culling pipeline".into()),
+                layout: vec![culling_layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader,
+                shader_defs: Vec::new(),
+                entry_point: "cull".into(),
+            });
+
+        GpuCullingPipeline {
+            culling_layout,
+            instances_layout,
+            pipeline_id,
+        }
+    }
+}
+
+/// The culling of the instances of one entity for one view, and what drawing them needs.
+pub struct GpuCullingJob {
+    uniform: UniformBuffer<CullingUniform>,
+    visible_instances: Buffer,
+    pub draw_args: Buffer,
+    /// Instances the buffers have room for, per LOD.
+    capacity: usize,
+    layout: GpuCullingLayout,
+    instance_count: u32,
+    /// The mesh of each LOD, in the order of the draw arguments.
+    pub lods: Vec<AssetId<Mesh>>,
+    culling_bind_group: Option<BindGroup>,
+    pub instances_bind_group: Option<BindGroup>,
+}
+
+impl GpuCullingJob {
+    /// Byte offset of the draw arguments of a LOD, in [`Self::draw_args`].
+    pub fn draw_args_offset(&self, lod: usize) -> u64 {
+        (self.layout.draw_args_offset(lod) * std::mem::size_of::<u32>()) as u64
+    }
+
+    /// Dynamic offset of the indices of a LOD, for [`Self::instances_bind_group`].
+    pub fn visible_instances_offset(&self, lod: usize) -> u32 {
+        (self.layout.visible_instances_offset(lod) * std::mem::size_of::<u32>()) as u32
+    }
+}
+
+/// The GPU culling of each entity for each view, by view and entity, kept across frames to reuse the buffers.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct GpuCullingJobs(HashMap<(Entity, Entity), GpuCullingJob>);
+
+impl GpuCullingJobs {
+    pub fn get_job(&self, view: Entity, entity: Entity) -> Option<&GpuCullingJob> {
+        self.get(&(view, entity))
+    }
+
+    /// Writes the culling parameters of every GPU culled entity for each view, resets the instance counts of the
+    /// draw arguments and binds the buffers of the culling pass, for [`GpuCullingNode`] to dispatch.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        views: Query<(Entity, &ExtractedView, &Frustum)>,
+        query: Query<(Entity, &ExtractedInstanceCulling)>,
+        instance_buffers: Res<GpuInstancesBuffers>,
+        meshes: Res<RenderAssets<Mesh>>,
+        pipeline: Res<GpuCullingPipeline>,
+        render_device: Res<RenderDevice>,
+        render_queue: Res<RenderQueue>,
+        mut jobs: ResMut<GpuCullingJobs>,
+    ) {
+        let culled_on_gpu = |entity: Entity| {
+            query
+                .get(entity)
+                .is_ok_and(|(_, culling)| culling.backend == CullingBackend::Gpu)
+        };
+        jobs.retain(|(view, entity), _| views.contains(*view) && culled_on_gpu(*entity));
+
+        let alignment = render_device.limits().min_storage_buffer_offset_alignment as usize
+            / std::mem::size_of::<u32>();
+
+        for (view_entity, view, frustum) in &views {
+            let view_from_world = view.transform.compute_matrix().inverse();
+            let is_orthographic = view.projection.w_axis.w == 1.0;
+
+            for (entity, culling) in &query {
+                if culling.backend != CullingBackend::Gpu {
+                    continue;
+                }
+                let Some(instances) = instance_buffers.get(&entity) else {
+                    continue;
+                };
+
+                let lod_count = culling.lods.len().min(MAX_GPU_LODS);
+                let lods: Vec<_> = culling.lods[..lod_count]
+                    .iter()
+                    .map(|(mesh, _)| *mesh)
+                    .chain([culling.mesh])
+                    .collect();
+
+                let capacity = match jobs.get(&(view_entity, entity)) {
+                    Some(job)
+                        if job.capacity >= instances.length && job.lods.len() == lods.len() =>
+                    {
+                        None
+                    }
+                    previous => Some(
+                        previous
+                            .map_or(0, |job| 2 * job.capacity)
+                            .max(instances.length)
+                            .max(1),
+                    ),
+                };
+                if let Some(capacity) = capacity {
+                    let layout = GpuCullingLayout::new(lods.len(), capacity, alignment);
+                    jobs.insert(
+                        (view_entity, entity),
+                        GpuCullingJob {
+                            uniform: UniformBuffer::default(),
+                            visible_instances: render_device.create_buffer(&BufferDescriptor {
+                                label: Some("visible instance indices buffer"),
+                                size: (layout.visible_instances_len() * std::mem::size_of::<u32>())
+                                    as u64,
+                                usage: BufferUsages::STORAGE,
+                                mapped_at_creation: false,
+                            }),
+                            draw_args: render_device.create_buffer(&BufferDescriptor {
+                                label: Some("instance draw arguments buffer"),
+                                size: (layout.draw_args_len() * std::mem::size_of::<u32>()) as u64,
+                                usage: BufferUsages::STORAGE
+                                    | BufferUsages::INDIRECT
+                                    | BufferUsages::COPY_DST,
+                                mapped_at_creation: false,
+                            }),
+                            capacity,
+                            layout,
+                            instance_count: 0,
+                            lods: Vec::new(),
+                            culling_bind_group: None,
+                            instances_bind_group: None,
+                        },
+                    );
+                }
+                let Some(job) = jobs.get_mut(&(view_entity, entity)) else {
+                    continue;
+                };
+
+                // The culling pass counts the instances of each LOD up from 0.
+                let draw_args = job.layout.draw_args(lods.iter().map(|mesh| {
+                    meshes
+                        .get(*mesh)
+                        .map_or(0, |gpu_mesh| match &gpu_mesh.buffer_info {
+                            GpuBufferInfo::Indexed { count, .. } => *count,
+                            GpuBufferInfo::NonIndexed => gpu_mesh.vertex_count,
+                        })
+                }));
+                render_queue.write_buffer(&job.draw_args, 0, bytemuck::cast_slice(&draw_args));
+
+                let matrix = culling.transform.matrix3;
+                let mut lod_max_screen_sizes = Vec4::ZERO;
+                for (lod, (_, max_screen_size)) in culling.lods[..lod_count].iter().enumerate() {
+                    lod_max_screen_sizes[lod] = *max_screen_size;
+                }
+                job.uniform.set(CullingUniform {
+                    world_from_local: Mat4::from(culling.transform),
+                    view_from_world,
+                    frustum: frustum.half_spaces.map(|half_space| half_space.normal_d()),
+                    mesh_center: culling.bounds.0.center.into(),
+                    world_scale: matrix
+                        .x_axis
+                        .length()
+                        .max(matrix.y_axis.length())
+                        .max(matrix.z_axis.length()),
+                    mesh_half_extents: culling.bounds.0.half_extents.into(),
+                    projection_scale: view.projection.y_axis.y,
+                    lod_max_screen_sizes,
+                    lod_count: lod_count as u32,
+                    instance_count: instances.length as u32,
+                    lod_stride: job.layout.lod_stride as u32,
+                    min_screen_size: culling.min_screen_size,
+                    is_orthographic: is_orthographic as u32,
+                });
+                job.uniform.write_buffer(&render_device, &render_queue);
+                job.instance_count = instances.length as u32;
+                job.lods = lods;
+
+                // The instance buffer is reallocated when it grows: bind it anew every frame.
+                job.culling_bind_group = Some(render_device.create_bind_group(
+                    "instance culling bind group",
+                    &pipeline.culling_layout,
+                    &BindGroupEntries::sequential((
+                        job.uniform.binding().unwrap(),
+                        instances.buffer.as_entire_binding(),
+                        job.visible_instances.as_entire_binding(),
+                        job.draw_args.as_entire_binding(),
+                    )),
+                ));
+            }
+        }
+    }
+
+    /// Binds the buffers the vertex shaders read the instances of each job from.
+    /// The mesh indices are only sized once the phase items are batched, after [`Self::prepare`].
+    pub fn prepare_bind_groups(
+        instance_buffers: Res<GpuInstancesBuffers>,
+        mesh_indices: Res<MeshIndices>,
+        pipeline: Res<GpuCullingPipeline>,
+        render_device: Res<RenderDevice>,
+        mut jobs: ResMut<GpuCullingJobs>,
+    ) {
+        for ((_, entity), job) in jobs.iter_mut() {
+            let (Some(instances), Some(mesh_index)) =
+                (instance_buffers.get(entity), mesh_indices.binding())
+            else {
+                job.instances_bind_group = None;
+                continue;
+            };
+            job.instances_bind_group = Some(render_device.create_bind_group(
+                "instances storage bind group",
+                &pipeline.instances_layout,
+                &BindGroupEntries::sequential((
+                    mesh_index,
+                    instances.buffer.as_entire_binding(),
+                    BufferBinding {
+                        buffer: &job.visible_instances,
+                        offset: 0,
+                        size: NonZeroU64::new(
+                            (job.layout.lod_stride * std::mem::size_of::<u32>()) as u64,
+                        ),
+                    },
+                )),
+            ));
+        }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct GpuCullingLabel;
+
+/// Dispatches the culling of every [`GpuCullingJob`], before any camera is drawn.
+#[derive(Default)]
+pub struct GpuCullingNode;
+
+impl Node for GpuCullingNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let jobs = world.resource::<GpuCullingJobs>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = world.resource::<GpuCullingPipeline>();
+
+        // Until the pipeline is compiled, the instance counts stay at 0 and nothing is drawn.
+        let Some(compute_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.pipeline_id)
+        else {
+            return Ok(());
+        };
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("instance culling"),
+                    timestamp_writes: None,
+                });
+        pass.set_pipeline(compute_pipeline);
+        for job in jobs.values() {
+            let Some(bind_group) = &job.culling_bind_group else {
+                continue;
+            };
+            if job.instance_count == 0 {
+                continue;
+            }
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups(job.instance_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        Ok(())
+    }
+}
+
+// Keeps `Instance` in sync with the 14 floats the shaders read.
+const _: () = assert!(std::mem::size_of::<Instance>() == 14 * std::mem::size_of::<f32>());
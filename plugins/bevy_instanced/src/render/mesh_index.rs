@@ -0,0 +1,115 @@
+//! The index of an instanced entity in Bevy's mesh uniforms, for the instancing vertex shaders to find its transform.
+//!
+//! Bevy's mesh shaders read that index from the `instance_index` builtin, which numbers the instances of an instanced
+//! draw instead. The batching systems store it in the `batch_range` of each phase item: every draw binds, at a dynamic
+//! offset, the slot of a uniform buffer holding it.
+
+use std::num::NonZeroU64;
+
+use bevy::{
+    ecs::system::{Res, ResMut, Resource},
+    pbr::MeshUniform,
+    prelude::*,
+    render::{
+        render_resource::{binding_types::uniform_buffer, *},
+        renderer::RenderDevice,
+    },
+};
+
+/// The layout entry of the mesh index, which comes first in the instances bind group.
+pub fn mesh_index_layout_entry() -> BindGroupLayoutEntryBuilder {
+    uniform_buffer::<u32>(true)
+}
+
+/// The layout of the mesh index, for the entities whose instances are read from a vertex buffer.
+pub fn mesh_index_layout(render_device: &RenderDevice) -> BindGroupLayout {
+    render_device.create_bind_group_layout(
+        "mesh index layout",
+        &BindGroupLayoutEntries::single(ShaderStages::VERTEX, mesh_index_layout_entry()),
+    )
+}
+
+/// A uniform buffer whose n-th slot holds n, for every index the mesh uniforms have this frame.
+#[derive(Resource)]
+pub struct MeshIndices {
+    buffer: Option<Buffer>,
+    /// Indices the buffer holds.
+    capacity: usize,
+    /// Bytes between consecutive slots, the alignment of dynamic uniform offsets.
+    stride: usize,
+    layout: BindGroupLayout,
+    pub bind_group: Option<BindGroup>,
+}
+
+impl FromWorld for MeshIndices {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        MeshIndices {
+            buffer: None,
+            capacity: 0,
+            stride: render_device.limits().min_uniform_buffer_offset_alignment as usize,
+            layout: mesh_index_layout(render_device),
+            bind_group: None,
+        }
+    }
+}
+
+impl MeshIndices {
+    /// The dynamic offset of the slot holding `mesh_index`.
+    pub fn offset(&self, mesh_index: u32) -> Option<u32> {
+        ((mesh_index as usize) < self.capacity).then(|| mesh_index * self.stride as u32)
+    }
+
+    /// The slot at offset 0, to bind along with other resources.
+    pub fn binding(&self) -> Option<BindingResource<'_>> {
+        let buffer = self.buffer.as_ref()?;
+        Some(BindingResource::Buffer(BufferBinding {
+            buffer,
+            offset: 0,
+            size: NonZeroU64::new(self.stride as u64),
+        }))
+    }
+
+    /// Grows the buffer to hold every index the batching systems handed out this frame.
+    /// Runs before the mesh uniforms are written, which empties them.
+    pub fn prepare(
+        mesh_uniforms: Res<GpuArrayBuffer<MeshUniform>>,
+        render_device: Res<RenderDevice>,
+        mut mesh_indices: ResMut<MeshIndices>,
+    ) {
+        // Without storage buffers, the indices start again from 0 in each batch of uniforms.
+        let count = match &*mesh_uniforms {
+            GpuArrayBuffer::Storage(buffer) => buffer.get().len(),
+            GpuArrayBuffer::Uniform(_) => {
+                GpuArrayBuffer::<MeshUniform>::batch_size(&render_device).unwrap_or(0) as usize
+            }
+        };
+        if count <= mesh_indices.capacity {
+            return;
+        }
+
+        let capacity = count.max(2 * mesh_indices.capacity);
+        let stride_words = mesh_indices.stride / std::mem::size_of::<u32>();
+        let mut contents = vec![0u32; capacity * stride_words];
+        for (index, slot) in contents.chunks_exact_mut(stride_words).enumerate() {
+            slot[0] = index as u32;
+        }
+
+        mesh_indices.buffer = Some(
+            render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("mesh index buffer"),
+                contents: bytemuck::cast_slice(&contents),
+                usage: BufferUsages::UNIFORM,
+            }),
+        );
+        mesh_indices.capacity = capacity;
+        mesh_indices.bind_group = mesh_indices.binding().map(|binding| {
+            render_device.create_bind_group(
+                "mesh index bind group",
+                &mesh_indices.layout,
+                &BindGroupEntries::single(binding),
+            )
+        });
+    }
+}
@@ -1,3 +1,7 @@
 pub mod bind_group;
+pub mod gpu_culling;
+pub mod mesh_index;
+pub mod outline;
+pub mod prepass;
 pub mod render_pipeline;
 pub mod shaders;
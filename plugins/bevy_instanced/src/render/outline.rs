@@ -0,0 +1,227 @@
+//! A screen-space outline around the hovered and selected instances, drawn after tonemapping.
+//!
+//! The instancing prepass shaders mark highlighted instances in the alpha channel of the normal prepass texture,
+//! which Bevy leaves at 1: the outline pass draws around the pixels whose alpha is lower.
+
+use bevy::{
+    core_pipeline::{
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state, prepass::ViewPrepassTextures,
+    },
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{ComponentUniforms, DynamicUniformIndex, ExtractComponent},
+        render_graph::{NodeRunError, RenderGraphContext, RenderLabel, ViewNode},
+        render_resource::{
+            binding_types::{texture_2d, texture_2d_multisampled, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        view::{ExtractedView, ViewTarget},
+    },
+};
+
+use super::shaders::OUTLINE_SHADER;
+
+/// Outlines the highlighted instances seen by the camera, which also needs a `NormalPrepass`.
+#[derive(Component, ExtractComponent, ShaderType, Clone, Copy, Debug)]
+pub struct InstanceOutline {
+    pub hovered_color: Color,
+    pub selected_color: Color,
+    /// In pixels.
+    pub width: f32,
+}
+
+impl Default for InstanceOutline {
+    fn default() -> Self {
+        InstanceOutline {
+            hovered_color: Color::ORANGE,
+            selected_color: Color::YELLOW,
+            width: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct InstanceOutlineLabel;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceOutlinePipelineKey {
+    hdr: bool,
+    /// The normal prepass texture is multisampled.
+    multisampled: bool,
+}
+
+#[derive(Resource)]
+pub struct InstanceOutlinePipeline {
+    layout: BindGroupLayout,
+    multisampled_layout: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+impl FromWorld for InstanceOutlinePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = |normals| {
+            render_device.create_bind_group_layout(
+                "instance outline layout",
+                &BindGroupLayoutEntries::sequential(
+                    ShaderStages::FRAGMENT,
+                    (
+                        texture_2d(TextureSampleType::Float { filterable: true }),
+                        normals,
+                        uniform_buffer::<InstanceOutline>(true),
+                    ),
+                ),
+            )
+        };
+
+        InstanceOutlinePipeline {
+            layout: layout(texture_2d(TextureSampleType::Float { filterable: false })),
+            multisampled_layout: layout(texture_2d_multisampled(TextureSampleType::Float {
+                filterable: false,
+            })),
+            shader: world.resource::<AssetServer>().load(OUTLINE_SHADER),
+        }
+    }
+}
+
+impl InstanceOutlinePipeline {
+    fn layout(&self, multisampled: bool) -> &BindGroupLayout {
+        if multisampled {
+            &self.multisampled_layout
+        } else {
+            &self.layout
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for InstanceOutlinePipeline {
+    type Key = InstanceOutlinePipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = Vec::new();
+        if key.multisampled {
+            shader_defs.push("MULTISAMPLED".into());
+        }
+
+        RenderPipelineDescriptor {
+            label: Some("instance outline pipeline".into()),
+            layout: vec![self.layout(key.multisampled).clone()],
+            push_constant_ranges: Vec::new(),
+            vertex: fullscreen_shader_vertex_state(),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+        }
+    }
+}
+
+/// The outline pipeline of a view.
+#[derive(Component)]
+pub struct ViewInstanceOutlinePipeline {
+    id: CachedRenderPipelineId,
+    multisampled: bool,
+}
+
+impl ViewInstanceOutlinePipeline {
+    pub fn prepare(
+        mut commands: Commands,
+        views: Query<(Entity, &ExtractedView), With<InstanceOutline>>,
+        pipeline_cache: Res<PipelineCache>,
+        pipeline: Res<InstanceOutlinePipeline>,
+        mut pipelines: ResMut<SpecializedRenderPipelines<InstanceOutlinePipeline>>,
+        msaa: Res<Msaa>,
+    ) {
+        for (entity, view) in &views {
+            let key = InstanceOutlinePipelineKey {
+                hdr: view.hdr,
+                multisampled: msaa.samples() > 1,
+            };
+            commands.entity(entity).insert(ViewInstanceOutlinePipeline {
+                id: pipelines.specialize(&pipeline_cache, &pipeline, key),
+                multisampled: key.multisampled,
+            });
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InstanceOutlineNode;
+
+impl ViewNode for InstanceOutlineNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static ViewPrepassTextures,
+        &'static ViewInstanceOutlinePipeline,
+        &'static DynamicUniformIndex<InstanceOutline>,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, prepass_textures, view_pipeline, outline_index): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let outline_pipeline = world.resource::<InstanceOutlinePipeline>();
+        // Without a normal prepass, nothing is marked.
+        let Some(normals) = &prepass_textures.normal else {
+            return Ok(());
+        };
+        let Some(pipeline) = pipeline_cache.get_render_pipeline(view_pipeline.id) else {
+            return Ok(());
+        };
+        let Some(outline_uniforms) = world
+            .resource::<ComponentUniforms<InstanceOutline>>()
+            .uniforms()
+            .binding()
+        else {
+            return Ok(());
+        };
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "instance outline bind group",
+            outline_pipeline.layout(view_pipeline.multisampled),
+            &BindGroupEntries::sequential((
+                post_process.source,
+                &normals.texture.default_view,
+                outline_uniforms,
+            )),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("instance outline pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[outline_index.index()]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,365 @@
+//! Instanced meshes in the depth, normal and motion vector prepasses and in shadow maps.
+
+use std::hash::Hash;
+
+use bevy::{
+    core_pipeline::prepass::{
+        AlphaMask3dPrepass, DepthPrepass, MotionVectorPrepass, NormalPrepass, Opaque3dPrepass,
+    },
+    pbr::{
+        LightEntity, MaterialPipelineKey, MeshPipelineKey, PrepassPipeline, RenderMaterials,
+        RenderMeshInstances, SetMeshBindGroup, SetPrepassViewBindGroup, Shadow, ViewLightEntities,
+    },
+    prelude::*,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::RenderAssets,
+        render_phase::{DrawFunctions, RenderPhase, SetItemPipeline},
+        render_resource::*,
+        renderer::RenderDevice,
+        view::{ExtractedView, VisibleEntities},
+    },
+};
+
+use crate::instance_data::{highlight::InstanceHighlight, instanced::ExtractedInstances};
+
+use super::{
+    bind_group::SetInstancedMaterialBindGroup,
+    render_pipeline::{
+        add_highlight, add_imposter, add_instances, is_gpu_culled, DrawMeshInstanced,
+        InstancedMaterialQuery, InstancedPipelineKey, InstancesLayouts,
+    },
+    shaders::{IMPOSTER_PREPASS_SHADER, PREPASS_SHADER},
+};
+
+/// The prepass pipeline of the material, with the instancing prepass vertex shader in place of the mesh one.
+#[derive(Resource)]
+pub struct InstancedPrepassPipeline<M: Material> {
+    shader: Handle<Shader>,
+    imposter_shader: Handle<Shader>,
+    prepass_pipeline: PrepassPipeline<M>,
+    instances_layouts: InstancesLayouts,
+}
+
+impl<M: Material> FromWorld for InstancedPrepassPipeline<M> {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let shader = asset_server.load(PREPASS_SHADER);
+        let imposter_shader = asset_server.load(IMPOSTER_PREPASS_SHADER);
+        // `PrepassPipeline` isn't `Clone`: build another one, whose bind group layouts match the ones of the
+        // material's prepass pipeline.
+        let prepass_pipeline = PrepassPipeline::<M>::from_world(world);
+
+        InstancedPrepassPipeline {
+            shader,
+            imposter_shader,
+            prepass_pipeline,
+            instances_layouts: InstancesLayouts::new(world.resource::<RenderDevice>()),
+        }
+    }
+}
+
+impl<M: Material> InstancedPrepassPipeline<M> {
+    /// Marks the highlighted instances in the normal prepass texture with the fragment shader of the instancing
+    /// prepass, in place of the material's. Deferred prepasses keep theirs.
+    fn add_outline_fragment(&self, descriptor: &mut RenderPipelineDescriptor) {
+        let Some(fragment) = &mut descriptor.fragment else {
+            return;
+        };
+        if !fragment.shader_defs.contains(&"NORMAL_PREPASS".into())
+            || fragment.shader_defs.contains(&"DEFERRED_PREPASS".into())
+        {
+            return;
+        }
+
+        fragment.shader = self.shader.clone();
+        fragment.entry_point = "fragment".into();
+        fragment.shader_defs.push("INSTANCE_OUTLINE_PREPASS".into());
+        descriptor
+            .vertex
+            .shader_defs
+            .push("INSTANCE_OUTLINE_PREPASS".into());
+    }
+}
+
+impl<M: Material> SpecializedMeshPipeline for InstancedPrepassPipeline<M>
+where
+    M::Data: PartialEq + Eq + Hash + Clone,
+{
+    type Key = InstancedPipelineKey<M>;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.prepass_pipeline.specialize(key.material, layout)?;
+
+        descriptor.vertex.shader = self.shader.clone();
+        // As in the main passes, so that alpha masking takes the instance colour into account.
+        let fragment_shader_defs = descriptor
+            .fragment
+            .as_mut()
+            .map(|fragment| &mut fragment.shader_defs);
+        for shader_defs in [
+            Some(&mut descriptor.vertex.shader_defs),
+            fragment_shader_defs,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !shader_defs.contains(&"VERTEX_COLORS".into()) {
+                shader_defs.push("VERTEX_COLORS".into());
+            }
+        }
+        if let Some(imposter) = key.imposter {
+            add_imposter(&mut descriptor, imposter, &self.imposter_shader);
+        }
+        if let Some(highlight) = key.highlight {
+            add_highlight(&mut descriptor, highlight);
+            // Imposters mark the highlighted instances in their own fragment shader.
+            if key.imposter.is_none() && !key.gpu_culling {
+                self.add_outline_fragment(&mut descriptor);
+            }
+        }
+        add_instances(
+            &mut descriptor,
+            key.gpu_culling,
+            key.attributes,
+            &self.instances_layouts,
+        );
+        Ok(descriptor)
+    }
+}
+
+pub type DrawInstancedPrepass<M> = (
+    SetItemPipeline,
+    SetPrepassViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetInstancedMaterialBindGroup<M, 2>,
+    DrawMeshInstanced<M>,
+);
+
+type PrepassViewQuery = (
+    &'static ExtractedView,
+    &'static VisibleEntities,
+    &'static mut RenderPhase<Opaque3dPrepass>,
+    &'static mut RenderPhase<AlphaMask3dPrepass>,
+    Has<DepthPrepass>,
+    Has<NormalPrepass>,
+    Has<MotionVectorPrepass>,
+);
+
+/// Adds the instanced meshes of opaque and alpha-masked materials to the prepasses of the views that have one.
+#[allow(clippy::too_many_arguments)]
+pub fn queue_instanced_prepass<M: Material>(
+    opaque_draw_functions: Res<DrawFunctions<Opaque3dPrepass>>,
+    alpha_mask_draw_functions: Res<DrawFunctions<AlphaMask3dPrepass>>,
+    prepass_pipeline: Res<InstancedPrepassPipeline<M>>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<InstancedPrepassPipeline<M>>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_materials: Res<RenderMaterials<M>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    material_meshes: Query<InstancedMaterialQuery<M>, With<ExtractedInstances>>,
+    mut views: Query<PrepassViewQuery>,
+) where
+    M::Data: PartialEq + Eq + Hash + Clone,
+{
+    let opaque_draw_prepass = opaque_draw_functions.read().id::<DrawInstancedPrepass<M>>();
+    let alpha_mask_draw_prepass = alpha_mask_draw_functions
+        .read()
+        .id::<DrawInstancedPrepass<M>>();
+
+    for (
+        view,
+        visible_entities,
+        mut opaque_phase,
+        mut alpha_mask_phase,
+        depth_prepass,
+        normal_prepass,
+        motion_vector_prepass,
+    ) in &mut views
+    {
+        let mut view_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+        for (enabled, key) in [
+            (depth_prepass, MeshPipelineKey::DEPTH_PREPASS),
+            (normal_prepass, MeshPipelineKey::NORMAL_PREPASS),
+            (
+                motion_vector_prepass,
+                MeshPipelineKey::MOTION_VECTOR_PREPASS,
+            ),
+        ] {
+            if enabled {
+                view_key |= key;
+            }
+        }
+        let rangefinder = view.rangefinder3d();
+
+        for &entity in visible_entities.iter() {
+            let Ok((entity, material, culling, imposter, attributes, highlight)) =
+                material_meshes.get(entity)
+            else {
+                continue;
+            };
+            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+            let Some(material) = render_materials.get(&material.id()) else {
+                continue;
+            };
+
+            let mut key =
+                view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            match material.properties.alpha_mode {
+                AlphaMode::Opaque => {}
+                AlphaMode::Mask(_) => key |= MeshPipelineKey::MAY_DISCARD,
+                // Blended materials aren't written to the prepass.
+                AlphaMode::Blend
+                | AlphaMode::Premultiplied
+                | AlphaMode::Add
+                | AlphaMode::Multiply => continue,
+            }
+
+            let pipeline_id = pipelines.specialize(
+                &pipeline_cache,
+                &prepass_pipeline,
+                InstancedPipelineKey {
+                    material: MaterialPipelineKey {
+                        mesh_key: key,
+                        bind_group_data: material.key.clone(),
+                    },
+                    gpu_culling: is_gpu_culled(culling),
+                    imposter: imposter.copied(),
+                    attributes: attributes.map(|attributes| attributes.attributes),
+                    highlight: highlight.map(InstanceHighlight::key),
+                },
+                &mesh.layout,
+            );
+            let pipeline_id = match pipeline_id {
+                Ok(pipeline_id) => pipeline_id,
+                Err(err) => {
+                    error!("{}", err);
+                    continue;
+                }
+            };
+
+            if let AlphaMode::Mask(_) = material.properties.alpha_mode {
+                alpha_mask_phase.add(AlphaMask3dPrepass {
+                    entity,
+                    pipeline_id,
+                    draw_function: alpha_mask_draw_prepass,
+                    distance: rangefinder
+                        .distance_translation(&mesh_instance.transforms.transform.translation)
+                        + material.properties.depth_bias,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+            } else {
+                opaque_phase.add(Opaque3dPrepass {
+                    entity,
+                    asset_id: mesh_instance.mesh_asset_id,
+                    pipeline_id,
+                    draw_function: opaque_draw_prepass,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+            }
+        }
+    }
+}
+
+/// Adds the instanced meshes to the shadow map of every light, unless they have `NotShadowCaster`.
+/// The entities aren't culled against the light, their instances are when they have an `InstanceCulling`.
+#[allow(clippy::too_many_arguments)]
+pub fn queue_instanced_shadows<M: Material>(
+    shadow_draw_functions: Res<DrawFunctions<Shadow>>,
+    prepass_pipeline: Res<InstancedPrepassPipeline<M>>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<InstancedPrepassPipeline<M>>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_materials: Res<RenderMaterials<M>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    material_meshes: Query<InstancedMaterialQuery<M>, With<ExtractedInstances>>,
+    view_lights: Query<&ViewLightEntities>,
+    mut view_light_shadow_phases: Query<(&LightEntity, &mut RenderPhase<Shadow>)>,
+) where
+    M::Data: PartialEq + Eq + Hash + Clone,
+{
+    let draw_shadow = shadow_draw_functions.read().id::<DrawInstancedPrepass<M>>();
+
+    for view_lights in &view_lights {
+        for &view_light_entity in &view_lights.lights {
+            let Ok((light_entity, mut shadow_phase)) =
+                view_light_shadow_phases.get_mut(view_light_entity)
+            else {
+                continue;
+            };
+            let is_directional_light = matches!(light_entity, LightEntity::Directional { .. });
+
+            for (entity, material, culling, imposter, attributes, highlight) in &material_meshes {
+                let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                    continue;
+                };
+                if !mesh_instance.shadow_caster {
+                    continue;
+                }
+                let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                    continue;
+                };
+                let Some(material) = render_materials.get(&material.id()) else {
+                    continue;
+                };
+
+                let mut key = MeshPipelineKey::from_primitive_topology(mesh.primitive_topology)
+                    | MeshPipelineKey::DEPTH_PREPASS;
+                if is_directional_light {
+                    key |= MeshPipelineKey::DEPTH_CLAMP_ORTHO;
+                }
+                key |= match material.properties.alpha_mode {
+                    AlphaMode::Mask(_)
+                    | AlphaMode::Blend
+                    | AlphaMode::Premultiplied
+                    | AlphaMode::Add => MeshPipelineKey::MAY_DISCARD,
+                    _ => MeshPipelineKey::NONE,
+                };
+
+                let pipeline_id = pipelines.specialize(
+                    &pipeline_cache,
+                    &prepass_pipeline,
+                    InstancedPipelineKey {
+                        material: MaterialPipelineKey {
+                            mesh_key: key,
+                            bind_group_data: material.key.clone(),
+                        },
+                        gpu_culling: is_gpu_culled(culling),
+                        imposter: imposter.copied(),
+                        attributes: attributes.map(|attributes| attributes.attributes),
+                        highlight: highlight.map(InstanceHighlight::key),
+                    },
+                    &mesh.layout,
+                );
+                let pipeline_id = match pipeline_id {
+                    Ok(pipeline_id) => pipeline_id,
+                    Err(err) => {
+                        error!("{}", err);
+                        continue;
+                    }
+                };
+
+                shadow_phase.add(Shadow {
+                    draw_function: draw_shadow,
+                    pipeline: pipeline_id,
+                    entity,
+                    distance: 0.0,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+            }
+        }
+    }
+}
@@ -3,36 +3,223 @@
 use std::{hash::Hash, marker::PhantomData};
 
 use bevy::{
-    core_pipeline::core_3d::Transparent3d,
-    ecs::system::{lifetimeless::*, SystemParamItem},
+    core_pipeline::{
+        core_3d::{AlphaMask3d, Camera3d, Opaque3d, Transmissive3d, Transparent3d},
+        prepass::{DeferredPrepass, DepthPrepass, MotionVectorPrepass, NormalPrepass},
+        tonemapping::{DebandDither, Tonemapping},
+    },
+    ecs::{
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParamItem},
+    },
     pbr::{
-        MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup, SetMeshViewBindGroup,
+        alpha_mode_pipeline_key, irradiance_volume::IrradianceVolume,
+        screen_space_specular_transmission_pipeline_key, tonemapping_pipeline_key,
+        MaterialPipeline, MaterialPipelineKey, MeshPipelineKey, RenderMaterials,
+        RenderMeshInstances, RenderViewLightProbes, ScreenSpaceAmbientOcclusionSettings,
+        SetMeshBindGroup, SetMeshViewBindGroup, ShadowFilteringMethod,
     },
     prelude::*,
     render::{
-        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        camera::TemporalJitter,
+        mesh::{GpuBufferInfo, GpuMesh, MeshVertexBufferLayout},
         render_asset::RenderAssets,
         render_phase::{
             DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult, RenderPhase,
             SetItemPipeline, TrackedRenderPass,
         },
         render_resource::*,
-        view::ExtractedView,
+        renderer::RenderDevice,
+        view::{ExtractedView, VisibleEntities},
     },
 };
 
 use crate::instance_data::{
+    attributes::{
+        self, ExtractedInstanceAttributes, InstanceAttribute, InstanceAttributesBuffers,
+        InstanceLayout,
+    },
+    culling::{CullingBackend, ExtractedInstanceCulling, VisibleInstancesBuffers},
     gpu_instanced::GpuInstancesData,
-    instanced::{Instance, InstancesData},
+    highlight::{HighlightKey, InstanceHighlight},
+    imposter::Imposter,
+    instanced::{ExtractedInstances, Instance, InstancedMaterial},
+};
+
+use super::{
+    bind_group::SetInstancedMaterialBindGroup,
+    gpu_culling::{instances_storage_layout, GpuCullingJobs},
+    mesh_index::{mesh_index_layout, MeshIndices},
+    shaders::{DEFAULT_SHADER, IMPOSTER_SHADER},
 };
 
-use super::shaders::DEFAULT_SHADER;
+/// The key of the instanced pipelines: the one of the material, and where the instances are read from.
+pub struct InstancedPipelineKey<M: Material> {
+    pub material: MaterialPipelineKey<M>,
+    /// The instances are read from a storage buffer, through the indices written by the GPU culling pass,
+    /// rather than from a vertex buffer.
+    pub gpu_culling: bool,
+    pub imposter: Option<Imposter>,
+    /// The layout of the [`InstanceAttributes`](crate::instance_data::attributes::InstanceAttributes) of the entity,
+    /// read from a second instance vertex buffer.
+    pub attributes: Option<&'static [InstanceAttribute]>,
+    /// The tints of the [`InstanceHighlight`] of the entity.
+    pub highlight: Option<HighlightKey>,
+}
+
+impl<M: Material> Clone for InstancedPipelineKey<M>
+where
+    M::Data: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            material: self.material.clone(),
+            gpu_culling: self.gpu_culling,
+            imposter: self.imposter,
+            attributes: self.attributes,
+            highlight: self.highlight,
+        }
+    }
+}
+
+impl<M: Material> PartialEq for InstancedPipelineKey<M>
+where
+    M::Data: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.material == other.material
+            && self.gpu_culling == other.gpu_culling
+            && self.imposter == other.imposter
+            && self.attributes == other.attributes
+            && self.highlight == other.highlight
+    }
+}
+
+impl<M: Material> Eq for InstancedPipelineKey<M> where M::Data: PartialEq {}
+
+impl<M: Material> Hash for InstancedPipelineKey<M>
+where
+    M::Data: Hash,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.material.hash(state);
+        self.gpu_culling.hash(state);
+        self.imposter.hash(state);
+        self.attributes.hash(state);
+        self.highlight.hash(state);
+    }
+}
+
+/// Whether the instances of an entity are culled by the GPU culling pass, see [`InstancedPipelineKey::gpu_culling`].
+pub fn is_gpu_culled(culling: Option<&ExtractedInstanceCulling>) -> bool {
+    culling.is_some_and(|culling| culling.backend == CullingBackend::Gpu)
+}
+
+/// The layouts of the fourth bind group of the instancing pipelines, with or without GPU culling.
+#[derive(Clone)]
+pub struct InstancesLayouts {
+    pub storage: BindGroupLayout,
+    pub mesh_index: BindGroupLayout,
+}
+
+impl InstancesLayouts {
+    pub fn new(render_device: &RenderDevice) -> Self {
+        InstancesLayouts {
+            storage: instances_storage_layout(render_device),
+            mesh_index: mesh_index_layout(render_device),
+        }
+    }
+}
+
+/// The shader location of the first of the [`InstanceAttribute`]s of an entity, after those of [`Instance`].
+pub const INSTANCE_ATTRIBUTES_LOCATION: u32 = 12;
+
+/// Makes the instances, their attributes and the mesh index available to the vertex shader of a pipeline
+/// specialized from a material one.
+pub(crate) fn add_instances(
+    descriptor: &mut RenderPipelineDescriptor,
+    gpu_culling: bool,
+    attributes: Option<&'static [InstanceAttribute]>,
+    layouts: &InstancesLayouts,
+) {
+    // After the view, mesh and material bind groups.
+    if gpu_culling {
+        descriptor
+            .vertex
+            .shader_defs
+            .push("INSTANCE_STORAGE".into());
+        descriptor.layout.push(layouts.storage.clone());
+    } else {
+        descriptor.layout.push(layouts.mesh_index.clone());
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            // Shader locations 0-7 are reserved for the mesh attributes.
+            attributes: attributes::vertex_attributes(Instance::ATTRIBUTES, 8),
+        });
 
+        if let Some(attributes) = attributes {
+            descriptor.vertex.buffers.push(VertexBufferLayout {
+                array_stride: attributes::stride(attributes) as u64,
+                step_mode: VertexStepMode::Instance,
+                attributes: attributes::vertex_attributes(attributes, INSTANCE_ATTRIBUTES_LOCATION),
+            });
+            // The fragment shader gets them too, for what the vertex shader passes on.
+            let shader_defs = attributes::shader_defs(attributes, INSTANCE_ATTRIBUTES_LOCATION);
+            descriptor
+                .vertex
+                .shader_defs
+                .extend(shader_defs.iter().cloned());
+            if let Some(fragment) = &mut descriptor.fragment {
+                fragment.shader_defs.extend(shader_defs);
+            }
+        }
+    }
+}
+
+/// Tints the hovered and selected instances, whose flags are among the instance attributes.
+pub(crate) fn add_highlight(descriptor: &mut RenderPipelineDescriptor, highlight: HighlightKey) {
+    let shader_defs = highlight.shader_defs();
+    descriptor
+        .vertex
+        .shader_defs
+        .extend(shader_defs.iter().cloned());
+    if let Some(fragment) = &mut descriptor.fragment {
+        fragment.shader_defs.extend(shader_defs);
+    }
+}
+
+/// Draws the instances as ray-cast imposters, with the vertex and fragment shaders of `imposter.wgsl`, or of
+/// `imposter_prepass.wgsl` in the prepasses.
+/// Pipelines without a fragment shader, like the ones of shadow maps, get one writing the depth of the shape.
+pub(crate) fn add_imposter(
+    descriptor: &mut RenderPipelineDescriptor,
+    imposter: Imposter,
+    shader: &Handle<Shader>,
+) {
+    descriptor.vertex.shader = shader.clone();
+    descriptor.vertex.shader_defs.push(imposter.shader_def());
+
+    let fragment = descriptor.fragment.get_or_insert_with(|| FragmentState {
+        shader: shader.clone(),
+        shader_defs: descriptor.vertex.shader_defs.clone(),
+        entry_point: "fragment".into(),
+        targets: Vec::new(),
+    });
+    fragment.shader = shader.clone();
+    fragment.entry_point = "fragment".into();
+    if !fragment.shader_defs.contains(&imposter.shader_def()) {
+        fragment.shader_defs.push(imposter.shader_def());
+    }
+}
+
+/// The pipeline of the material, with the instancing vertex shader in place of the mesh one.
 #[derive(Resource)]
 pub struct InstancedRenderPipeline<M: Material> {
     shader: Handle<Shader>,
-    mesh_pipeline: MeshPipeline,
-    marker: PhantomData<M>,
+    imposter_shader: Handle<Shader>,
+    material_pipeline: MaterialPipeline<M>,
+    instances_layouts: InstancesLayouts,
 }
 
 impl<M: Material> FromWorld for InstancedRenderPipeline<M> {
@@ -44,13 +231,15 @@ impl<M: Material> FromWorld for InstancedRenderPipeline<M> {
             ShaderRef::Handle(handle) => handle,
             ShaderRef::Path(path) => asset_server.load(path),
         };
+        let imposter_shader = asset_server.load(IMPOSTER_SHADER);
 
-        let mesh_pipeline = world.resource::<MeshPipeline>();
+        let material_pipeline = world.resource::<MaterialPipeline<M>>();
 
         InstancedRenderPipeline {
             shader,
-            mesh_pipeline: mesh_pipeline.clone(),
-            marker: PhantomData,
+            imposter_shader,
+            material_pipeline: material_pipeline.clone(),
+            instances_layouts: InstancesLayouts::new(world.resource::<RenderDevice>()),
         }
     }
 }
@@ -59,33 +248,38 @@ impl<M: Material> SpecializedMeshPipeline for InstancedRenderPipeline<M>
 where
     M::Data: PartialEq + Eq + Hash + Clone,
 {
-    type Key = MeshPipelineKey;
+    type Key = InstancedPipelineKey<M>;
 
     fn specialize(
         &self,
         key: Self::Key,
         layout: &MeshVertexBufferLayout,
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
-        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        let mut descriptor = self.material_pipeline.specialize(key.material, layout)?;
 
         descriptor.vertex.shader = self.shader.clone();
-        descriptor.vertex.buffers.push(VertexBufferLayout {
-            array_stride: std::mem::size_of::<Instance>() as u64,
-            step_mode: VertexStepMode::Instance,
-            attributes: vec![
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: 0,
-                    shader_location: 3, // shader locations 0-2 are taken up by Position, Normal and UV attributes
-                },
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: VertexFormat::Float32x4.size(),
-                    shader_location: 4,
-                },
-            ],
-        });
-        descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
+        // The instance colour is passed to the fragment shader as a vertex colour, which the PBR shader
+        // multiplies with the material's base colour.
+        for shader_defs in [
+            &mut descriptor.vertex.shader_defs,
+            &mut descriptor.fragment.as_mut().unwrap().shader_defs,
+        ] {
+            if !shader_defs.contains(&"VERTEX_COLORS".into()) {
+                shader_defs.push("VERTEX_COLORS".into());
+            }
+        }
+        if let Some(imposter) = key.imposter {
+            add_imposter(&mut descriptor, imposter, &self.imposter_shader);
+        }
+        if let Some(highlight) = key.highlight {
+            add_highlight(&mut descriptor, highlight);
+        }
+        add_instances(
+            &mut descriptor,
+            key.gpu_culling,
+            key.attributes,
+            &self.instances_layouts,
+        );
         Ok(descriptor)
     }
 }
@@ -93,98 +287,397 @@ where
 pub struct DrawMeshInstanced<M: Material>(PhantomData<M>);
 
 impl<P: PhaseItem, M: Material> RenderCommand<P> for DrawMeshInstanced<M> {
-    type Param = (SRes<RenderAssets<Mesh>>, SRes<RenderMeshInstances>);
-    type ViewQuery = ();
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SRes<RenderMeshInstances>,
+        SRes<VisibleInstancesBuffers>,
+        SRes<GpuCullingJobs>,
+        SRes<MeshIndices>,
+        SRes<InstanceAttributesBuffers>,
+    );
+    type ViewQuery = Entity;
     type ItemQuery = Read<GpuInstancesData>;
 
     #[inline]
     fn render<'w>(
         item: &P,
-        _view: (),
+        view: Entity,
         instance_buffer: Option<&'w GpuInstancesData>,
-        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
+        (
+            meshes,
+            render_mesh_instances,
+            visible_instances,
+            gpu_culling_jobs,
+            mesh_indices,
+            attributes,
+        ): SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
+        let meshes = meshes.into_inner();
         let Some(mesh_instance) = render_mesh_instances.get(&item.entity()) else {
             return RenderCommandResult::Failure;
         };
-        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+        // The batching systems give each phase item the index of its entity in the mesh uniforms.
+        let Some(mesh_index_offset) = mesh_indices.offset(item.batch_range().start) else {
             return RenderCommandResult::Failure;
         };
-        let Some(instance_buffer) = instance_buffer else {
+
+        // Instances culled on the GPU are drawn with the indirect arguments written by the culling pass, one draw per LOD.
+        if let Some(job) = gpu_culling_jobs.into_inner().get_job(view, item.entity()) {
+            let Some(bind_group) = &job.instances_bind_group else {
+                return RenderCommandResult::Failure;
+            };
+            for (lod, mesh) in job.lods.iter().enumerate() {
+                let Some(gpu_mesh) = meshes.get(*mesh) else {
+                    continue;
+                };
+                pass.set_bind_group(
+                    3,
+                    bind_group,
+                    &[mesh_index_offset, job.visible_instances_offset(lod)],
+                );
+                pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+                match &gpu_mesh.buffer_info {
+                    GpuBufferInfo::Indexed {
+                        buffer,
+                        index_format,
+                        ..
+                    } => {
+                        pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                        pass.draw_indexed_indirect(&job.draw_args, job.draw_args_offset(lod));
+                    }
+                    GpuBufferInfo::NonIndexed => {
+                        pass.draw_indirect(&job.draw_args, job.draw_args_offset(lod));
+                    }
+                }
+            }
+            return RenderCommandResult::Success;
+        }
+
+        let Some(mesh_index_bind_group) = &mesh_indices.into_inner().bind_group else {
             return RenderCommandResult::Failure;
         };
+        pass.set_bind_group(3, mesh_index_bind_group, &[mesh_index_offset]);
+        let attributes = attributes.into_inner().get(&item.entity());
 
-        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
-        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
-
-        match &gpu_mesh.buffer_info {
-            GpuBufferInfo::Indexed {
-                buffer,
-                index_format,
-                count,
-            } => {
-                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
-                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
+        // Instances culled on the CPU for this view are drawn from their own buffer, one draw per LOD.
+        if let Some(visible_instances) = visible_instances.into_inner().get(view, item.entity()) {
+            if attributes.is_some() && visible_instances.attributes.is_none() {
+                return RenderCommandResult::Failure;
             }
-            GpuBufferInfo::NonIndexed => {
-                pass.draw(0..gpu_mesh.vertex_count, 0..instance_buffer.length as u32);
+            for (mesh, instances) in &visible_instances.lods {
+                let Some(gpu_mesh) = meshes.get(*mesh) else {
+                    continue;
+                };
+                let offset = instances.start as u64 * std::mem::size_of::<Instance>() as u64;
+                let attributes = attributes.zip(visible_instances.attributes.as_ref()).map(
+                    |(attributes, buffer)| {
+                        buffer.slice(instances.start as u64 * attributes.stride as u64..)
+                    },
+                );
+                draw_mesh(
+                    pass,
+                    gpu_mesh,
+                    visible_instances.buffer.slice(offset..),
+                    attributes,
+                    instances.len() as u32,
+                );
             }
+            return RenderCommandResult::Success;
+        }
+
+        let Some(gpu_mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(instance_buffer) = instance_buffer else {
+            return RenderCommandResult::Failure;
+        };
+        if instance_buffer.length == 0 {
+            return RenderCommandResult::Success;
         }
+        // Every instance drawn needs its attributes.
+        if attributes.is_some_and(|attributes| attributes.length() < instance_buffer.length) {
+            return RenderCommandResult::Failure;
+        }
+
+        draw_mesh(
+            pass,
+            gpu_mesh,
+            instance_buffer.buffer.slice(..),
+            attributes.map(|attributes| attributes.buffer.slice(..)),
+            instance_buffer.length as u32,
+        );
         RenderCommandResult::Success
     }
 }
 
+fn draw_mesh<'w>(
+    pass: &mut TrackedRenderPass<'w>,
+    gpu_mesh: &'w GpuMesh,
+    instances: BufferSlice<'w>,
+    attributes: Option<BufferSlice<'w>>,
+    instance_count: u32,
+) {
+    pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+    pass.set_vertex_buffer(1, instances);
+    if let Some(attributes) = attributes {
+        pass.set_vertex_buffer(2, attributes);
+    }
+
+    match &gpu_mesh.buffer_info {
+        GpuBufferInfo::Indexed {
+            buffer,
+            index_format,
+            count,
+        } => {
+            pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+            pass.draw_indexed(0..*count, 0, 0..instance_count);
+        }
+        GpuBufferInfo::NonIndexed => {
+            pass.draw(0..gpu_mesh.vertex_count, 0..instance_count);
+        }
+    }
+}
+
 pub type DrawInstanced<M> = (
     SetItemPipeline,
     SetMeshViewBindGroup<0>,
     SetMeshBindGroup<1>,
+    SetInstancedMaterialBindGroup<M, 2>,
     DrawMeshInstanced<M>,
 );
 
+/// The parts of the pipeline key that depend on the view (prepasses, tonemapping, shadow filtering...),
+/// as computed for regular material meshes so that the view bind group layouts agree.
+pub type ViewKeyQuery = (
+    Option<&'static Tonemapping>,
+    Option<&'static DebandDither>,
+    Option<&'static ShadowFilteringMethod>,
+    Has<ScreenSpaceAmbientOcclusionSettings>,
+    (
+        Has<NormalPrepass>,
+        Has<DepthPrepass>,
+        Has<MotionVectorPrepass>,
+        Has<DeferredPrepass>,
+    ),
+    Option<&'static Camera3d>,
+    Has<TemporalJitter>,
+    Option<&'static Projection>,
+    (
+        Has<RenderViewLightProbes<EnvironmentMapLight>>,
+        Has<RenderViewLightProbes<IrradianceVolume>>,
+    ),
+);
+
+pub fn view_key(
+    msaa: &Msaa,
+    view: &ExtractedView,
+    view_query: QueryItem<ViewKeyQuery>,
+) -> MeshPipelineKey {
+    let (
+        tonemapping,
+        dither,
+        shadow_filter_method,
+        ssao,
+        (normal_prepass, depth_prepass, motion_vector_prepass, deferred_prepass),
+        camera_3d,
+        temporal_jitter,
+        projection,
+        (has_environment_maps, has_irradiance_volumes),
+    ) = view_query;
+
+    let mut view_key =
+        MeshPipelineKey::from_msaa_samples(msaa.samples()) | MeshPipelineKey::from_hdr(view.hdr);
+
+    for (enabled, key) in [
+        (normal_prepass, MeshPipelineKey::NORMAL_PREPASS),
+        (depth_prepass, MeshPipelineKey::DEPTH_PREPASS),
+        (
+            motion_vector_prepass,
+            MeshPipelineKey::MOTION_VECTOR_PREPASS,
+        ),
+        (deferred_prepass, MeshPipelineKey::DEFERRED_PREPASS),
+        (temporal_jitter, MeshPipelineKey::TEMPORAL_JITTER),
+        (has_environment_maps, MeshPipelineKey::ENVIRONMENT_MAP),
+        (has_irradiance_volumes, MeshPipelineKey::IRRADIANCE_VOLUME),
+        (ssao, MeshPipelineKey::SCREEN_SPACE_AMBIENT_OCCLUSION),
+    ] {
+        if enabled {
+            view_key |= key;
+        }
+    }
+
+    if let Some(projection) = projection {
+        view_key |= match projection {
+            Projection::Perspective(_) => MeshPipelineKey::VIEW_PROJECTION_PERSPECTIVE,
+            Projection::Orthographic(_) => MeshPipelineKey::VIEW_PROJECTION_ORTHOGRAPHIC,
+        };
+    }
+
+    view_key |= match shadow_filter_method.unwrap_or(&ShadowFilteringMethod::default()) {
+        ShadowFilteringMethod::Hardware2x2 => MeshPipelineKey::SHADOW_FILTER_METHOD_HARDWARE_2X2,
+        ShadowFilteringMethod::Castano13 => MeshPipelineKey::SHADOW_FILTER_METHOD_CASTANO_13,
+        ShadowFilteringMethod::Jimenez14 => MeshPipelineKey::SHADOW_FILTER_METHOD_JIMENEZ_14,
+    };
+
+    if !view.hdr {
+        if let Some(tonemapping) = tonemapping {
+            view_key |= MeshPipelineKey::TONEMAP_IN_SHADER;
+            view_key |= tonemapping_pipeline_key(*tonemapping);
+        }
+        if let Some(DebandDither::Enabled) = dither {
+            view_key |= MeshPipelineKey::DEBAND_DITHER;
+        }
+    }
+    if let Some(camera_3d) = camera_3d {
+        view_key |= screen_space_specular_transmission_pipeline_key(
+            camera_3d.screen_space_specular_transmission_quality,
+        );
+    }
+
+    view_key
+}
+
+pub type InstancedMaterialQuery<M> = (
+    Entity,
+    &'static InstancedMaterial<M>,
+    Option<&'static ExtractedInstanceCulling>,
+    Option<&'static Imposter>,
+    Option<&'static ExtractedInstanceAttributes>,
+    Option<&'static InstanceHighlight>,
+);
+
+type MainPhases = (
+    &'static mut RenderPhase<Opaque3d>,
+    &'static mut RenderPhase<AlphaMask3d>,
+    &'static mut RenderPhase<Transmissive3d>,
+    &'static mut RenderPhase<Transparent3d>,
+);
+
+/// Adds the instanced meshes to the phase matching the alpha mode of their material, as Bevy does for regular meshes.
 #[allow(clippy::too_many_arguments)]
 pub fn queue_instanced_material<M: Material>(
-    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    opaque_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    alpha_mask_draw_functions: Res<DrawFunctions<AlphaMask3d>>,
+    transmissive_draw_functions: Res<DrawFunctions<Transmissive3d>>,
+    transparent_draw_functions: Res<DrawFunctions<Transparent3d>>,
     custom_pipeline: Res<InstancedRenderPipeline<M>>,
     msaa: Res<Msaa>,
     mut pipelines: ResMut<SpecializedMeshPipelines<InstancedRenderPipeline<M>>>,
     pipeline_cache: Res<PipelineCache>,
     meshes: Res<RenderAssets<Mesh>>,
+    render_materials: Res<RenderMaterials<M>>,
     render_mesh_instances: Res<RenderMeshInstances>,
-    material_meshes: Query<Entity, With<InstancesData>>,
-    mut views: Query<(&ExtractedView, &mut RenderPhase<Transparent3d>)>,
+    material_meshes: Query<InstancedMaterialQuery<M>, With<ExtractedInstances>>,
+    mut views: Query<(&ExtractedView, &VisibleEntities, ViewKeyQuery, MainPhases)>,
 ) where
     M::Data: PartialEq + Eq + Hash + Clone,
 {
-    let draw_custom = transparent_3d_draw_functions
-        .read()
-        .id::<DrawInstanced<M>>();
+    let draw_opaque = opaque_draw_functions.read().id::<DrawInstanced<M>>();
+    let draw_alpha_mask = alpha_mask_draw_functions.read().id::<DrawInstanced<M>>();
+    let draw_transmissive = transmissive_draw_functions.read().id::<DrawInstanced<M>>();
+    let draw_transparent = transparent_draw_functions.read().id::<DrawInstanced<M>>();
 
-    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
-
-    for (view, mut transparent_phase) in &mut views {
-        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+    for (
+        view,
+        visible_entities,
+        view_query,
+        (mut opaque_phase, mut alpha_mask_phase, mut transmissive_phase, mut transparent_phase),
+    ) in &mut views
+    {
+        let view_key = view_key(&msaa, view, view_query);
         let rangefinder = view.rangefinder3d();
-        for entity in &material_meshes {
+        for &entity in visible_entities.iter() {
+            let Ok((entity, material, culling, imposter, attributes, highlight)) =
+                material_meshes.get(entity)
+            else {
+                continue;
+            };
             let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
                 continue;
             };
             let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
                 continue;
             };
-            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
-            let pipeline = pipelines
-                .specialize(&pipeline_cache, &custom_pipeline, key, &mesh.layout)
-                .unwrap();
-            transparent_phase.add(Transparent3d {
-                entity,
-                pipeline,
-                draw_function: draw_custom,
-                distance: rangefinder
-                    .distance_translation(&mesh_instance.transforms.transform.translation),
-                batch_range: 0..1,
-                dynamic_offset: None,
-            });
+            let Some(material) = render_materials.get(&material.id()) else {
+                continue;
+            };
+            let key = view_key
+                | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology)
+                | alpha_mode_pipeline_key(material.properties.alpha_mode);
+            let pipeline = pipelines.specialize(
+                &pipeline_cache,
+                &custom_pipeline,
+                InstancedPipelineKey {
+                    material: MaterialPipelineKey {
+                        mesh_key: key,
+                        bind_group_data: material.key.clone(),
+                    },
+                    gpu_culling: is_gpu_culled(culling),
+                    imposter: imposter.copied(),
+                    attributes: attributes.map(|attributes| attributes.attributes),
+                    highlight: highlight.map(InstanceHighlight::key),
+                },
+                &mesh.layout,
+            );
+            let pipeline = match pipeline {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    error!("{}", err);
+                    continue;
+                }
+            };
+
+            // The mesh instance has no material bind group id, so the batching systems never merge
+            // instanced items with their neighbours. Deferred materials are drawn forward.
+            let distance = rangefinder
+                .distance_translation(&mesh_instance.transforms.transform.translation)
+                + material.properties.depth_bias;
+            match material.properties.alpha_mode {
+                AlphaMode::Opaque if material.properties.reads_view_transmission_texture => {
+                    transmissive_phase.add(Transmissive3d {
+                        entity,
+                        pipeline,
+                        draw_function: draw_transmissive,
+                        distance,
+                        batch_range: 0..1,
+                        dynamic_offset: None,
+                    });
+                }
+                AlphaMode::Opaque => {
+                    opaque_phase.add(Opaque3d {
+                        entity,
+                        asset_id: mesh_instance.mesh_asset_id,
+                        pipeline,
+                        draw_function: draw_opaque,
+                        batch_range: 0..1,
+                        dynamic_offset: None,
+                    });
+                }
+                AlphaMode::Mask(_) => {
+                    alpha_mask_phase.add(AlphaMask3d {
+                        entity,
+                        pipeline,
+                        draw_function: draw_alpha_mask,
+                        distance,
+                        batch_range: 0..1,
+                        dynamic_offset: None,
+                    });
+                }
+                AlphaMode::Blend
+                | AlphaMode::Premultiplied
+                | AlphaMode::Add
+                | AlphaMode::Multiply => {
+                    transparent_phase.add(Transparent3d {
+                        entity,
+                        pipeline,
+                        draw_function: draw_transparent,
+                        distance,
+                        batch_range: 0..1,
+                        dynamic_offset: None,
+                    });
+                }
+            }
         }
     }
 }
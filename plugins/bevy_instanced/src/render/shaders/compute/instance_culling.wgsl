@@ -0,0 +1,75 @@
+#import bevy_instanced::instance::rotate
+
+// What the culling of the instances of one entity for one view needs, see `CullingUniform`.
+struct Culling {
+    world_from_local: mat4x4<f32>,
+    view_from_world: mat4x4<f32>,
+    frustum: array<vec4<f32>, 6>,
+    mesh_center: vec3<f32>,
+    world_scale: f32,
+    mesh_half_extents: vec3<f32>,
+    projection_scale: f32,
+    lod_max_screen_sizes: vec4<f32>,
+    lod_count: u32,
+    instance_count: u32,
+    lod_stride: u32,
+    min_screen_size: f32,
+    is_orthographic: u32,
+};
+
+@group(0) @binding(0) var<uniform> culling: Culling;
+@group(0) @binding(1) var<storage, read> instances: array<f32>;
+@group(0) @binding(2) var<storage, read_write> visible_instances: array<u32>;
+// Five words of indirect draw arguments per LOD, the second of which is the instance count, see `GpuCullingLayout`.
+@group(0) @binding(3) var<storage, read_write> draw_args: array<atomic<u32>>;
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= culling.instance_count {
+        return;
+    }
+
+    // See `bevy_instanced::instance` for the layout of an instance.
+    let i = index * 14u;
+    let position = vec3(instances[i], instances[i + 1u], instances[i + 2u]);
+    let scale = vec3(instances[i + 3u], instances[i + 4u], instances[i + 5u]);
+    let rotation = vec4(instances[i + 6u], instances[i + 7u], instances[i + 8u], instances[i + 9u]);
+
+    let local_center = rotate(rotation, culling.mesh_center * scale) + position;
+    let center = (culling.world_from_local * vec4(local_center, 1.0)).xyz;
+    let radius = length(culling.mesh_half_extents * scale) * culling.world_scale;
+
+    for (var plane = 0u; plane < 6u; plane += 1u) {
+        let half_space = culling.frustum[plane];
+        if dot(half_space.xyz, center) + half_space.w + radius <= 0.0 {
+            return;
+        }
+    }
+
+    // Diameter on screen as a fraction of the viewport height.
+    var screen_size = radius * culling.projection_scale;
+    if culling.is_orthographic == 0u {
+        let depth = -(culling.view_from_world * vec4(center, 1.0)).z;
+        if depth <= radius {
+            screen_size = 3.4e38;
+        } else {
+            screen_size /= depth;
+        }
+    }
+    if screen_size < culling.min_screen_size {
+        return;
+    }
+
+    var lod = culling.lod_count;
+    for (var level = 0u; level < culling.lod_count; level += 1u) {
+        if screen_size < culling.lod_max_screen_sizes[level] {
+            lod = level;
+            break;
+        }
+    }
+
+    // `GpuCullingLayout::push_visible`.
+    let slot = atomicAdd(&draw_args[lod * 5u + 1u], 1u);
+    visible_instances[lod * culling.lod_stride + slot] = index;
+}
@@ -2,12 +2,42 @@ use crate::util::InternalShaders;
 use bevy::{asset::embedded_asset, prelude::*};
 
 pub const DEFAULT_SHADER: &str = "embedded://bevy_instanced/render/shaders/render/instancing.wgsl";
+pub const PREPASS_SHADER: &str =
+    "embedded://bevy_instanced/render/shaders/render/instancing_prepass.wgsl";
+pub const INSTANCE_SHADER: &str = "embedded://bevy_instanced/render/shaders/render/instance.wgsl";
+pub const HIGHLIGHT_SHADER: &str = "embedded://bevy_instanced/render/shaders/render/highlight.wgsl";
+pub const IMPOSTER_FUNCTIONS_SHADER: &str =
+    "embedded://bevy_instanced/render/shaders/render/imposter_functions.wgsl";
+pub const IMPOSTER_SHADER: &str = "embedded://bevy_instanced/render/shaders/render/imposter.wgsl";
+pub const IMPOSTER_PREPASS_SHADER: &str =
+    "embedded://bevy_instanced/render/shaders/render/imposter_prepass.wgsl";
+pub const CULLING_SHADER: &str =
+    "embedded://bevy_instanced/render/shaders/compute/instance_culling.wgsl";
+pub const OUTLINE_SHADER: &str = "embedded://bevy_instanced/render/shaders/post/outline.wgsl";
 
 pub(crate) fn load_instancing_shaders(app: &mut App) {
+    embedded_asset!(app, "render/instance.wgsl");
+    embedded_asset!(app, "render/highlight.wgsl");
     embedded_asset!(app, "render/instancing.wgsl");
+    embedded_asset!(app, "render/instancing_prepass.wgsl");
+    embedded_asset!(app, "render/imposter_functions.wgsl");
+    embedded_asset!(app, "render/imposter.wgsl");
+    embedded_asset!(app, "render/imposter_prepass.wgsl");
+    embedded_asset!(app, "compute/instance_culling.wgsl");
+    embedded_asset!(app, "post/outline.wgsl");
 
     InternalShaders::load(
         app,
-        &["embedded://bevy_instanced/render/shaders/render/instancing.wgsl"],
+        &[
+            INSTANCE_SHADER,
+            HIGHLIGHT_SHADER,
+            DEFAULT_SHADER,
+            PREPASS_SHADER,
+            IMPOSTER_FUNCTIONS_SHADER,
+            IMPOSTER_SHADER,
+            IMPOSTER_PREPASS_SHADER,
+            CULLING_SHADER,
+            OUTLINE_SHADER,
+        ],
     );
 }
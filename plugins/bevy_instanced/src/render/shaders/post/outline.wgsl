@@ -0,0 +1,55 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_instanced::highlight::{OUTLINE_NONE, OUTLINE_HOVERED, OUTLINE_SELECTED}
+
+struct InstanceOutline {
+    hovered_color: vec4<f32>,
+    selected_color: vec4<f32>,
+    width: f32,
+};
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+#ifdef MULTISAMPLED
+@group(0) @binding(1) var normal_prepass_texture: texture_multisampled_2d<f32>;
+#else
+@group(0) @binding(1) var normal_prepass_texture: texture_2d<f32>;
+#endif
+@group(0) @binding(2) var<uniform> outline: InstanceOutline;
+
+// The mark the instancing prepass shaders left in the alpha channel of the normal at `pixel`.
+fn outline_mark(pixel: vec2<i32>) -> u32 {
+    let clamped = clamp(pixel, vec2(0), vec2<i32>(textureDimensions(normal_prepass_texture)) - 1);
+    let alpha = textureLoad(normal_prepass_texture, clamped, 0).a;
+    return u32(round(alpha * 3.0));
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let color = textureLoad(screen_texture, pixel, 0);
+    // The outline is drawn around the highlighted instances, not over them.
+    if outline_mark(pixel) != OUTLINE_NONE {
+        return color;
+    }
+
+    var hovered = false;
+    var selected = false;
+    let radius = i32(ceil(outline.width));
+    for (var y = -radius; y <= radius; y += 1) {
+        for (var x = -radius; x <= radius; x += 1) {
+            if f32(x * x + y * y) > outline.width * outline.width {
+                continue;
+            }
+            let mark = outline_mark(pixel + vec2(x, y));
+            hovered = hovered || mark == OUTLINE_HOVERED;
+            selected = selected || mark == OUTLINE_SELECTED;
+        }
+    }
+
+    var outline_color = vec4(0.0);
+    if hovered {
+        outline_color = outline.hovered_color;
+    } else if selected {
+        outline_color = outline.selected_color;
+    }
+    return vec4(mix(color.rgb, outline_color.rgb, outline_color.a), color.a);
+}
@@ -0,0 +1,42 @@
+#define_import_path bevy_instanced::highlight
+
+// The bits of `InstanceFlags`.
+const INSTANCE_HOVERED: u32 = 1u;
+const INSTANCE_SELECTED: u32 = 2u;
+const INSTANCE_HIDDEN: u32 = 4u;
+
+// The marks the prepass shaders leave in the alpha channel of the normal prepass texture for the outline pass, in
+// thirds: the texture keeps 2 bits of alpha. Bevy writes 1 for everything else.
+const OUTLINE_SELECTED: u32 = 0u;
+const OUTLINE_HOVERED: u32 = 1u;
+const OUTLINE_NONE: u32 = 3u;
+
+fn is_hidden(flags: u32) -> bool {
+    return (flags & INSTANCE_HIDDEN) != 0u;
+}
+
+// The colour of an instance with the tint of its state, see `InstanceHighlight`.
+fn tint(color: vec4<f32>, flags: u32) -> vec4<f32> {
+#ifdef INSTANCE_HIGHLIGHT
+    var highlight_tint = vec4(0.0);
+    if (flags & INSTANCE_HOVERED) != 0u {
+        highlight_tint = unpack4x8unorm(#{INSTANCE_HOVERED_TINT}u);
+    } else if (flags & INSTANCE_SELECTED) != 0u {
+        highlight_tint = unpack4x8unorm(#{INSTANCE_SELECTED_TINT}u);
+    }
+    return vec4(mix(color.rgb, highlight_tint.rgb, highlight_tint.a), color.a);
+#else
+    return color;
+#endif
+}
+
+// The alpha of the normal of an instance in the normal prepass texture.
+fn outline_alpha(flags: u32) -> f32 {
+    var mark = OUTLINE_NONE;
+    if (flags & INSTANCE_HOVERED) != 0u {
+        mark = OUTLINE_HOVERED;
+    } else if (flags & INSTANCE_SELECTED) != 0u {
+        mark = OUTLINE_SELECTED;
+    }
+    return f32(mark) / 3.0;
+}
@@ -0,0 +1,58 @@
+#import bevy_instanced::imposter_functions::{ImposterOutput, Vertex, imposter_vertex, intersect, view_ray}
+#import bevy_pbr::{
+    forward_io::VertexOutput,
+    mesh_view_bindings::view,
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) frag_depth: f32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> ImposterOutput {
+    return imposter_vertex(vertex);
+}
+
+@fragment
+fn fragment(in: ImposterOutput) -> FragmentOutput {
+    let ray = view_ray(in);
+    let hit = intersect(ray, in);
+    if !hit.hit {
+        discard;
+    }
+    let world_position = vec4(ray.origin + hit.distance * ray.direction, 1.0);
+    let clip_position = view.view_proj * world_position;
+
+    var out: FragmentOutput;
+    out.frag_depth = clip_position.z / clip_position.w;
+
+    // Shade the hit like a mesh fragment there.
+    var vertex_output: VertexOutput;
+    vertex_output.position = vec4(in.position.xy, out.frag_depth, 1.0);
+    vertex_output.world_position = world_position;
+    vertex_output.world_normal = hit.normal;
+#ifdef VERTEX_UVS
+    vertex_output.uv = in.uv;
+#endif
+#ifdef VERTEX_COLORS
+    vertex_output.color = in.color;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    vertex_output.instance_index = in.instance_mesh_index;
+#endif
+
+    var pbr_input = pbr_input_from_standard_material(vertex_output, true);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+    if (pbr_input.material.flags & STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+        out.color = apply_pbr_lighting(pbr_input);
+    } else {
+        out.color = pbr_input.material.base_color;
+    }
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+
+    return out;
+}
@@ -0,0 +1,241 @@
+#define_import_path bevy_instanced::imposter_functions
+
+#import bevy_instanced::instance::{InstanceData, mesh_index, rotate}
+#ifdef INSTANCE_STORAGE
+#import bevy_instanced::instance::visible_instance
+#endif
+#import bevy_instanced::highlight::{is_hidden, outline_alpha, tint}
+#import bevy_pbr::{
+    mesh_functions::get_model_matrix,
+    mesh_view_bindings::view,
+}
+
+// What the imposter vertex and fragment shaders of the main passes and prepasses share. Those are in separate files:
+// the forward one needs `pbr_fragment`, which only builds in the prepasses that write normals.
+
+// The vertices of `Imposter::quad`, whose corners are at ±0.5 in X and Y.
+struct Vertex {
+    @location(0) position: vec3<f32>,
+
+#ifdef INSTANCE_STORAGE
+    @builtin(instance_index) instance_index: u32,
+#else
+    @location(8) i_position: vec3<f32>,
+    @location(9) i_scale: vec3<f32>,
+    @location(10) i_rotation: vec4<f32>,
+    @location(11) i_color: vec4<f32>,
+#ifdef INSTANCE_FLAGS
+    @location(#{INSTANCE_FLAGS_LOCATION}) flags: u32,
+#endif
+#endif
+};
+
+fn instance_data(vertex: Vertex) -> InstanceData {
+#ifdef INSTANCE_STORAGE
+    return visible_instance(vertex.instance_index);
+#else
+    var instance = InstanceData(vertex.i_position, vertex.i_scale, vertex.i_rotation, vertex.i_color);
+#ifdef INSTANCE_FLAGS
+    // A hidden instance collapses to a point, which draws nothing.
+    if is_hidden(vertex.flags) {
+        instance.scale = vec3(0.0);
+    }
+    instance.color = tint(instance.color, vertex.flags);
+#endif
+    return instance;
+#endif
+}
+
+// The quad covering an instance, and the shape to intersect in world space. A sphere is a cylinder whose caps
+// are both at its centre.
+struct ImposterOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) @interpolate(flat) start: vec3<f32>,
+    @location(4) @interpolate(flat) end: vec3<f32>,
+    @location(5) @interpolate(flat) radius: f32,
+    @location(6) @interpolate(flat) instance_mesh_index: u32,
+    // The alpha of the normal in the normal prepass texture, see `outline_alpha`.
+    @location(7) @interpolate(flat) outline: f32,
+};
+
+struct RayHit {
+    hit: bool,
+    distance: f32,
+    normal: vec3<f32>,
+};
+
+fn miss() -> RayHit {
+    return RayHit(false, 0.0, vec3(0.0));
+}
+
+fn is_orthographic() -> bool {
+    return view.projection[3].w == 1.0;
+}
+
+// The direction the camera looks in.
+fn view_forward() -> vec3<f32> {
+    return -view.view[2].xyz;
+}
+
+// See `ray_sphere` in `imposter.rs`.
+fn ray_sphere(origin: vec3<f32>, direction: vec3<f32>, centre: vec3<f32>, radius: f32) -> RayHit {
+    let to_origin = origin - centre;
+    let b = dot(to_origin, direction);
+    let c = dot(to_origin, to_origin) - radius * radius;
+    let h = b * b - c;
+    if h < 0.0 {
+        return miss();
+    }
+
+    let distance = -b - sqrt(h);
+    if distance < 0.0 {
+        return miss();
+    }
+    return RayHit(true, distance, (to_origin + distance * direction) / radius);
+}
+
+// See `ray_capped_cylinder` in `imposter.rs`.
+fn ray_capped_cylinder(
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+    start: vec3<f32>,
+    end: vec3<f32>,
+    radius: f32,
+) -> RayHit {
+    let axis = end - start;
+    let to_origin = origin - start;
+    let axis_axis = dot(axis, axis);
+    let axis_direction = dot(axis, direction);
+    let axis_origin = dot(axis, to_origin);
+
+    let k2 = axis_axis - axis_direction * axis_direction;
+    let k1 = axis_axis * dot(to_origin, direction) - axis_origin * axis_direction;
+    let k0 = axis_axis * dot(to_origin, to_origin)
+        - axis_origin * axis_origin
+        - radius * radius * axis_axis;
+    let h = k1 * k1 - k2 * k0;
+    if h < 0.0 {
+        return miss();
+    }
+
+    let parallel = k2 <= 1e-6 * axis_axis;
+    var along_axis = 0.0;
+    if !parallel {
+        let distance = (-k1 - sqrt(h)) / k2;
+        along_axis = axis_origin + distance * axis_direction;
+        if distance >= 0.0 && along_axis > 0.0 && along_axis < axis_axis {
+            return RayHit(true, distance, (to_origin + distance * direction - axis * along_axis / axis_axis) / radius);
+        }
+    }
+
+    var at_start = along_axis <= 0.0;
+    if parallel {
+        at_start = axis_direction > 0.0;
+    }
+    let faces_cap = select(axis_direction < 0.0, axis_direction > 0.0, at_start);
+    let cap = select(axis_axis, 0.0, at_start);
+    let distance = (cap - axis_origin) / axis_direction;
+    let point = to_origin + distance * direction;
+    let radial = point - axis * (dot(point, axis) / axis_axis);
+    if !faces_cap || distance < 0.0 || dot(radial, radial) > radius * radius {
+        return miss();
+    }
+    return RayHit(true, distance, select(axis, -axis, at_start) / sqrt(axis_axis));
+}
+
+// See `perspective_scale` in `imposter.rs`.
+fn perspective_scale(distance: f32, radius: f32) -> f32 {
+    return distance / max(distance - radius, 1e-3 * distance);
+}
+
+fn imposter_vertex(vertex: Vertex) -> ImposterOutput {
+    let instance = instance_data(vertex);
+    let model = get_model_matrix(mesh_index);
+    let model_scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+
+#ifdef IMPOSTER_CYLINDER
+    let half_axis = rotate(instance.rotation, vec3(0.0, 0.5 * instance.scale.y, 0.0));
+    let start = (model * vec4(instance.position - half_axis, 1.0)).xyz;
+    let end = (model * vec4(instance.position + half_axis, 1.0)).xyz;
+    let radius = 0.5 * instance.scale.x * model_scale;
+#else
+    let start = (model * vec4(instance.position, 1.0)).xyz;
+    let end = start;
+    let radius = 0.5 * max(instance.scale.x, max(instance.scale.y, instance.scale.z)) * model_scale;
+#endif
+
+    let centre = 0.5 * (start + end);
+    let half_length = 0.5 * distance(start, end);
+    var to_camera = -view_forward();
+    var scale = 1.0;
+    if !is_orthographic() {
+        let camera_distance = distance(view.world_position, centre);
+        to_camera = (view.world_position - centre) / camera_distance;
+        scale = perspective_scale(camera_distance, half_length + radius);
+    }
+
+    // The quad is across the axis of the cylinder as seen from the camera, or upright for a sphere and for a
+    // cylinder seen end-on.
+    var axis = vec3(0.0);
+    if half_length > 0.0 {
+        axis = (end - start) / (2.0 * half_length);
+    }
+    var side = cross(axis, to_camera);
+    if dot(side, side) < 1e-6 {
+        let right = view.view[0].xyz;
+        side = right - to_camera * dot(right, to_camera);
+    }
+    side = normalize(side);
+    let up = cross(to_camera, side);
+    let half_width = radius * scale;
+    let half_height = (half_length * abs(dot(axis, up)) + radius) * scale;
+
+    let corner = vertex.position.xy;
+    var out: ImposterOutput;
+    out.world_position = vec4(centre + 2.0 * (corner.x * half_width * side + corner.y * half_height * up), 1.0);
+    out.position = view.view_proj * out.world_position;
+#ifdef DEPTH_CLAMP_ORTHO
+    // The fragment shader writes the depth of the shape: only keep the quad from being clipped by the near plane.
+    out.position.z = min(out.position.z, 1.0);
+#endif
+    out.uv = vec2(corner.x + 0.5, 0.5 - corner.y);
+    out.color = instance.color;
+    out.start = start;
+    out.end = end;
+    out.radius = radius;
+    out.instance_mesh_index = mesh_index;
+    out.outline = 1.0;
+#ifdef INSTANCE_FLAGS
+#ifdef INSTANCE_HIGHLIGHT
+    out.outline = outline_alpha(vertex.flags);
+#endif
+#endif
+    return out;
+}
+
+struct Ray {
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+};
+
+// The view ray through the fragment.
+fn view_ray(in: ImposterOutput) -> Ray {
+    if is_orthographic() {
+        // Start in front of the shape, wherever the camera is.
+        let direction = view_forward();
+        let origin = in.world_position.xyz - direction * (distance(in.start, in.end) + 2.0 * in.radius);
+        return Ray(origin, direction);
+    }
+    return Ray(view.world_position, normalize(in.world_position.xyz - view.world_position));
+}
+
+fn intersect(ray: Ray, in: ImposterOutput) -> RayHit {
+#ifdef IMPOSTER_CYLINDER
+    return ray_capped_cylinder(ray.origin, ray.direction, in.start, in.end, in.radius);
+#else
+    return ray_sphere(ray.origin, ray.direction, in.start, in.radius);
+#endif
+}
@@ -0,0 +1,46 @@
+#import bevy_instanced::imposter_functions::{ImposterOutput, Vertex, imposter_vertex, intersect, view_ray}
+#import bevy_pbr::mesh_view_bindings::view
+#ifdef MOTION_VECTOR_PREPASS
+#import bevy_pbr::prepass_bindings::previous_view_proj
+#endif
+
+// The depth of the shape, with its normal and motion when the prepass writes them.
+struct FragmentOutput {
+#ifdef NORMAL_PREPASS
+    @location(0) normal: vec4<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    @location(1) motion_vector: vec2<f32>,
+#endif
+    @builtin(frag_depth) frag_depth: f32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> ImposterOutput {
+    return imposter_vertex(vertex);
+}
+
+@fragment
+fn fragment(in: ImposterOutput) -> FragmentOutput {
+    let ray = view_ray(in);
+    let hit = intersect(ray, in);
+    if !hit.hit {
+        discard;
+    }
+    let world_position = vec4(ray.origin + hit.distance * ray.direction, 1.0);
+    let clip_position = view.view_proj * world_position;
+
+    var out: FragmentOutput;
+    out.frag_depth = clip_position.z / clip_position.w;
+#ifdef NORMAL_PREPASS
+    out.normal = vec4(hit.normal * 0.5 + vec3(0.5), in.outline);
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    // Only the motion of the camera is tracked.
+    let clip = view.unjittered_view_proj * world_position;
+    let previous_clip = previous_view_proj * world_position;
+    out.motion_vector = (clip.xy / clip.w - previous_clip.xy / previous_clip.w) * vec2(0.5, -0.5);
+#endif
+
+    return out;
+}
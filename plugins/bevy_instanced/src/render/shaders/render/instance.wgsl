@@ -0,0 +1,36 @@
+#define_import_path bevy_instanced::instance
+
+struct InstanceData {
+    position: vec3<f32>,
+    scale: vec3<f32>,
+    rotation: vec4<f32>,
+    color: vec4<f32>,
+};
+
+// Rotates `v` by the unit quaternion `q`.
+fn rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
+
+// The index of the entity in the mesh uniforms, which Bevy's mesh functions take in place of the `instance_index`
+// builtin: in instanced draws, that numbers the instances.
+@group(3) @binding(0) var<uniform> mesh_index: u32;
+
+#ifdef INSTANCE_STORAGE
+// `Instance` is 14 tightly packed floats, which no WGSL struct with vector fields matches:
+// the instances are read as a plain array of floats.
+@group(3) @binding(1) var<storage, read> instances: array<f32>;
+// The indices of the instances left by the culling pass, for the LOD being drawn.
+@group(3) @binding(2) var<storage, read> visible_instances: array<u32>;
+
+fn visible_instance(instance_index: u32) -> InstanceData {
+    let i = visible_instances[instance_index] * 14u;
+    return InstanceData(
+        vec3(instances[i], instances[i + 1u], instances[i + 2u]),
+        vec3(instances[i + 3u], instances[i + 4u], instances[i + 5u]),
+        vec4(instances[i + 6u], instances[i + 7u], instances[i + 8u], instances[i + 9u]),
+        vec4(instances[i + 10u], instances[i + 11u], instances[i + 12u], instances[i + 13u]),
+    );
+}
+#endif
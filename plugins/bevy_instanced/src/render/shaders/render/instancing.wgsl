@@ -1,39 +1,80 @@
-#import bevy_pbr::mesh_functions::{get_model_matrix, mesh_position_local_to_clip}
+#import bevy_instanced::instance::{InstanceData, mesh_index, rotate}
+#ifdef INSTANCE_STORAGE
+#import bevy_instanced::instance::visible_instance
+#endif
+#import bevy_instanced::highlight::{is_hidden, tint}
+#import bevy_pbr::{
+    mesh_functions::{get_model_matrix, mesh_position_local_to_world, mesh_normal_local_to_world, mesh_tangent_local_to_world},
+    forward_io::VertexOutput,
+    view_transformations::position_world_to_clip,
+}
 
 struct Vertex {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
+#ifdef VERTEX_UVS
     @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
 
-    @location(3) i_pos_scale: vec4<f32>,
-    @location(4) i_color: vec4<f32>,
+#ifdef INSTANCE_STORAGE
+    @builtin(instance_index) instance_index: u32,
+#else
+    @location(8) i_position: vec3<f32>,
+    @location(9) i_scale: vec3<f32>,
+    @location(10) i_rotation: vec4<f32>,
+    @location(11) i_color: vec4<f32>,
+#ifdef INSTANCE_FLAGS
+    @location(#{INSTANCE_FLAGS_LOCATION}) flags: u32,
+#endif
+#endif
 };
 
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) color: vec4<f32>,
-};
+fn instance_data(vertex: Vertex) -> InstanceData {
+#ifdef INSTANCE_STORAGE
+    return visible_instance(vertex.instance_index);
+#else
+    var instance = InstanceData(vertex.i_position, vertex.i_scale, vertex.i_rotation, vertex.i_color);
+#ifdef INSTANCE_FLAGS
+    // A hidden instance collapses to a point, which draws nothing.
+    if is_hidden(vertex.flags) {
+        instance.scale = vec3(0.0);
+    }
+    instance.color = tint(instance.color, vertex.flags);
+#endif
+    return instance;
+#endif
+}
 
+// The output matches the one of Bevy's mesh vertex shader, so that the material's fragment shader
+// (PBR for `StandardMaterial`) shades instances like any other mesh.
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
+    let instance = instance_data(vertex);
+    let position = rotate(instance.rotation, vertex.position * instance.scale) + instance.position;
+    // Normals transform by the inverse transpose: the rotation is unchanged, the scale is inverted.
+    let normal = normalize(rotate(instance.rotation, vertex.normal / instance.scale));
 
-    let light_dir = vec3<f32>(1.0, 0., 0.);
+    let model = get_model_matrix(mesh_index);
 
-    let position = vertex.position * vertex.i_pos_scale.w + vertex.i_pos_scale.xyz;
     var out: VertexOutput;
-    // NOTE: Passing 0 as the instance_index to get_model_matrix() is a hack
-    // for this example as the instance_index builtin would map to the wrong
-    // index in the Mesh array. This index could be passed in via another
-    // uniform instead but it's unnecessary for the example.
-    out.clip_position = mesh_position_local_to_clip(
-        get_model_matrix(0u),
-        vec4<f32>(position, 1.0)
-    );
-    out.color = dot(light_dir, vertex.normal) * vertex.i_color;
+    out.world_position = mesh_position_local_to_world(model, vec4<f32>(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+    out.world_normal = mesh_normal_local_to_world(normal, mesh_index);
+#ifdef VERTEX_UVS
+    out.uv = vertex.uv;
+#endif
+#ifdef VERTEX_TANGENTS
+    let tangent = rotate(instance.rotation, vertex.tangent.xyz * instance.scale);
+    out.world_tangent = mesh_tangent_local_to_world(model, vec4<f32>(tangent, vertex.tangent.w), mesh_index);
+#endif
+#ifdef VERTEX_COLORS
+    out.color = instance.color;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = mesh_index;
+#endif
     return out;
-}
-
-@fragment
-fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color;
 }
\ No newline at end of file
@@ -0,0 +1,138 @@
+#import bevy_instanced::instance::{InstanceData, mesh_index, rotate}
+#ifdef INSTANCE_STORAGE
+#import bevy_instanced::instance::visible_instance
+#endif
+#import bevy_instanced::highlight::{is_hidden, outline_alpha, tint}
+#import bevy_pbr::{
+    mesh_functions::{get_model_matrix, get_previous_model_matrix, mesh_position_local_to_world, mesh_normal_local_to_world, mesh_tangent_local_to_world},
+    prepass_io::VertexOutput,
+    view_transformations::position_world_to_clip,
+}
+
+#ifdef INSTANCE_OUTLINE_PREPASS
+#import bevy_pbr::{
+    mesh_view_bindings::view,
+    prepass_io::FragmentOutput,
+}
+#ifdef MOTION_VECTOR_PREPASS
+#import bevy_pbr::prepass_bindings::previous_view_proj
+#endif
+#endif
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_UVS
+    @location(1) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_UVS_B
+    @location(2) uv_b: vec2<f32>,
+#endif
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    @location(3) normal: vec3<f32>,
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
+#endif
+
+#ifdef INSTANCE_STORAGE
+    @builtin(instance_index) instance_index: u32,
+#else
+    @location(8) i_position: vec3<f32>,
+    @location(9) i_scale: vec3<f32>,
+    @location(10) i_rotation: vec4<f32>,
+    @location(11) i_color: vec4<f32>,
+#ifdef INSTANCE_FLAGS
+    @location(#{INSTANCE_FLAGS_LOCATION}) flags: u32,
+#endif
+#endif
+};
+
+fn instance_data(vertex: Vertex) -> InstanceData {
+#ifdef INSTANCE_STORAGE
+    return visible_instance(vertex.instance_index);
+#else
+    var instance = InstanceData(vertex.i_position, vertex.i_scale, vertex.i_rotation, vertex.i_color);
+#ifdef INSTANCE_FLAGS
+    // A hidden instance collapses to a point, which draws nothing.
+    if is_hidden(vertex.flags) {
+        instance.scale = vec3(0.0);
+    }
+    instance.color = tint(instance.color, vertex.flags);
+#endif
+    return instance;
+#endif
+}
+
+// The instancing vertex shader for the depth, normal and motion vector prepasses and for shadow maps.
+// The output matches the one of Bevy's prepass vertex shader, so that the material's prepass fragment shader
+// (alpha masking for `StandardMaterial`) applies to instances.
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let instance = instance_data(vertex);
+    let position = rotate(instance.rotation, vertex.position * instance.scale) + instance.position;
+
+    let model = get_model_matrix(mesh_index);
+
+    var out: VertexOutput;
+    out.world_position = mesh_position_local_to_world(model, vec4<f32>(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+#ifdef DEPTH_CLAMP_ORTHO
+    out.clip_position_unclamped = out.position;
+    out.position.z = min(out.position.z, 1.0);
+#endif
+#ifdef VERTEX_UVS
+    out.uv = vertex.uv;
+#endif
+#ifdef VERTEX_UVS_B
+    out.uv_b = vertex.uv_b;
+#endif
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    let normal = normalize(rotate(instance.rotation, vertex.normal / instance.scale));
+    out.world_normal = mesh_normal_local_to_world(normal, mesh_index);
+#ifdef VERTEX_TANGENTS
+    let tangent = rotate(instance.rotation, vertex.tangent.xyz * instance.scale);
+    out.world_tangent = mesh_tangent_local_to_world(model, vec4<f32>(tangent, vertex.tangent.w), mesh_index);
+#endif
+#endif
+#ifdef VERTEX_COLORS
+    out.color = instance.color;
+#endif
+#ifdef INSTANCE_OUTLINE_PREPASS
+    // Only read by the fragment shader below, which ignores the colour.
+    out.color.r = 1.0;
+#ifdef INSTANCE_FLAGS
+    out.color.r = outline_alpha(vertex.flags);
+#endif
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    // Instances moved since the previous frame aren't tracked: only the motion of the entity is.
+    out.previous_world_position = mesh_position_local_to_world(
+        get_previous_model_matrix(mesh_index),
+        vec4<f32>(position, 1.0)
+    );
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = mesh_index;
+#endif
+    return out;
+}
+
+#ifdef INSTANCE_OUTLINE_PREPASS
+// The prepass fragment shader of highlighted entities, in place of the material's: it marks the hovered and selected
+// instances in the alpha channel of the normal prepass texture, for the outline pass. The normal maps and alpha mask
+// of the material are ignored.
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.normal = vec4(normalize(in.world_normal) * 0.5 + vec3(0.5), in.color.r);
+#ifdef MOTION_VECTOR_PREPASS
+    let clip = view.unjittered_view_proj * in.world_position;
+    let previous_clip = previous_view_proj * in.previous_world_position;
+    out.motion_vector = (clip.xy / clip.w - previous_clip.xy / previous_clip.w) * vec2(0.5, -0.5);
+#endif
+#ifdef DEPTH_CLAMP_ORTHO
+    out.frag_depth = in.clip_position_unclamped.z;
+#endif
+    return out;
+}
+#endif
@@ -0,0 +1,126 @@
+/**
+* Per-element data shared by the representations, colouring schemes and bond perception.
+*
+* Covalent radii are the single-bond radii of Cordero et al. (2008), van der Waals radii those of Bondi (1964),
+* completed from Alvarez (2013) for the metals Bondi does not list. Colours are the CPK colours used by Jmol.
+*/
+use bevy::render::color::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElementData {
+    pub symbol: &'static str,
+    pub atomic_number: u8,
+    /// Standard atomic weight (Da).
+    pub mass: f32,
+    /// Single-bond covalent radius (Å).
+    pub covalent_radius: f32,
+    /// Van der Waals radius (Å).
+    pub vdw_radius: f32,
+    /// CPK colour, as `0xRRGGBB`.
+    pub cpk: u32,
+}
+
+impl ElementData {
+    const fn new(
+        symbol: &'static str,
+        atomic_number: u8,
+        mass: f32,
+        covalent_radius: f32,
+        vdw_radius: f32,
+        cpk: u32,
+    ) -> Self {
+        Self {
+            symbol,
+            atomic_number,
+            mass,
+            covalent_radius,
+            vdw_radius,
+            cpk,
+        }
+    }
+
+    pub fn cpk_color(&self) -> Color {
+        Color::rgb_u8(
+            (self.cpk >> 16) as u8,
+            (self.cpk >> 8) as u8,
+            self.cpk as u8,
+        )
+    }
+}
+
+/// Stands in for elements missing from [`ELEMENTS`] (or unreadable symbols): carbon-sized, drawn in pink.
+pub const UNKNOWN_ELEMENT: ElementData = ElementData::new("X", 0, 0., 0.77, 1.70, 0xFF1493);
+
+/// The elements found in biomolecular structures, ordered by atomic number.
+pub const ELEMENTS: &[ElementData] = &[
+    ElementData::new("H", 1, 1.008, 0.31, 1.20, 0xFFFFFF),
+    ElementData::new("He", 2, 4.003, 0.28, 1.40, 0xD9FFFF),
+    ElementData::new("Li", 3, 6.94, 1.28, 1.82, 0xCC80FF),
+    ElementData::new("Be", 4, 9.012, 0.96, 1.53, 0xC2FF00),
+    ElementData::new("B", 5, 10.81, 0.84, 1.92, 0xFFB5B5),
+    ElementData::new("C", 6, 12.011, 0.76, 1.70, 0x909090),
+    ElementData::new("N", 7, 14.007, 0.71, 1.55, 0x3050F8),
+    ElementData::new("O", 8, 15.999, 0.66, 1.52, 0xFF0D0D),
+    ElementData::new("F", 9, 18.998, 0.57, 1.47, 0x90E050),
+    ElementData::new("Ne", 10, 20.180, 0.58, 1.54, 0xB3E3F5),
+    ElementData::new("Na", 11, 22.990, 1.66, 2.27, 0xAB5CF2),
+    ElementData::new("Mg", 12, 24.305, 1.41, 1.73, 0x8AFF00),
+    ElementData::new("Al", 13, 26.982, 1.21, 1.84, 0xBFA6A6),
+    ElementData::new("Si", 14, 28.085, 1.11, 2.10, 0xF0C8A0),
+    ElementData::new("P", 15, 30.974, 1.07, 1.80, 0xFF8000),
+    ElementData::new("S", 16, 32.06, 1.05, 1.80, 0xFFFF30),
+    ElementData::new("Cl", 17, 35.45, 1.02, 1.75, 0x1FF01F),
+    ElementData::new("Ar", 18, 39.948, 1.06, 1.88, 0x80D1E3),
+    ElementData::new("K", 19, 39.098, 2.03, 2.75, 0x8F40D4),
+    ElementData::new("Ca", 20, 40.078, 1.76, 2.31, 0x3DFF00),
+    ElementData::new("Mn", 25, 54.938, 1.39, 2.05, 0x9C7AC7),
+    ElementData::new("Fe", 26, 55.845, 1.32, 2.04, 0xE06633),
+    ElementData::new("Co", 27, 58.933, 1.26, 2.00, 0xF090A0),
+    ElementData::new("Ni", 28, 58.693, 1.24, 1.63, 0x50D050),
+    ElementData::new("Cu", 29, 63.546, 1.32, 1.40, 0xC88033),
+    ElementData::new("Zn", 30, 65.38, 1.22, 1.39, 0x7D80B0),
+    ElementData::new("Ga", 31, 69.723, 1.22, 1.87, 0xC28F8F),
+    ElementData::new("Se", 34, 78.971, 1.20, 1.90, 0xFFA100),
+    ElementData::new("Br", 35, 79.904, 1.20, 1.85, 0xA62929),
+    ElementData::new("Rb", 37, 85.468, 2.20, 3.03, 0x702EB0),
+    ElementData::new("Sr", 38, 87.62, 1.95, 2.49, 0x00FF00),
+    ElementData::new("Cd", 48, 112.41, 1.44, 1.58, 0xFFD98F),
+    ElementData::new("I", 53, 126.90, 1.39, 1.98, 0x940094),
+    ElementData::new("Cs", 55, 132.91, 2.44, 3.43, 0x57178F),
+    ElementData::new("Ba", 56, 137.33, 2.15, 2.68, 0x00C900),
+    ElementData::new("Sm", 62, 150.36, 1.98, 2.29, 0x8FFFC7),
+    ElementData::new("Yb", 70, 173.05, 1.87, 2.26, 0x00BF38),
+    ElementData::new("Pt", 78, 195.08, 1.36, 1.75, 0xD0D0E0),
+    ElementData::new("Au", 79, 196.97, 1.36, 1.66, 0xFFD123),
+    ElementData::new("Hg", 80, 200.59, 1.32, 1.55, 0xB8B8D0),
+];
+
+/// Looks an element up by its symbol, ignoring case. Deuterium is treated as hydrogen.
+pub fn element_data(symbol: &str) -> &'static ElementData {
+    let symbol = if symbol.eq_ignore_ascii_case("D") {
+        "H"
+    } else {
+        symbol
+    };
+
+    ELEMENTS
+        .iter()
+        .find(|element| element.symbol.eq_ignore_ascii_case(symbol))
+        .unwrap_or(&UNKNOWN_ELEMENT)
+}
+
+pub fn covalent_radius(symbol: &str) -> f32 {
+    element_data(symbol).covalent_radius
+}
+
+pub fn vdw_radius(symbol: &str) -> f32 {
+    element_data(symbol).vdw_radius
+}
+
+pub fn cpk_color(symbol: &str) -> Color {
+    element_data(symbol).cpk_color()
+}
+
+pub fn atomic_mass(symbol: &str) -> f32 {
+    element_data(symbol).mass
+}
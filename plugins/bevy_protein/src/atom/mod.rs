@@ -1,7 +1,14 @@
+pub mod element;
+pub mod residue;
+
 use bevy::prelude::*;
 use bevy::{ecs::component::Component, math::primitives::Sphere};
 use periodic_table_on_an_enum::Element;
 
+use crate::representation::atom_element;
+
+use self::element::element_data;
+
 // Pdb file coordinates are typically expressed in Ångstroms (Å).
 // The size of an atom typically ranges from about  0.5 Ångstroms (Å)
 // to about 2.5 Å.
@@ -27,12 +34,13 @@ impl Atom {
         materials: &mut ResMut<Assets<StandardMaterial>>,
     ) {
         let (x, y, z) = self.atomic_data.pos();
-        let sphere_mesh = Sphere::new(0.25);
+        let element = element_data(atom_element(&self.atomic_data));
+        let sphere_mesh = Sphere::new(element.vdw_radius);
         // Render the mesh with the custom texture using a PbrBundle, add the marker.
         commands.spawn((PbrBundle {
             mesh: meshes.add(sphere_mesh),
             material: materials.add(StandardMaterial {
-                base_color: Color::BLUE,
+                base_color: element.cpk_color(),
                 ..default()
             }),
             transform: Transform::from_xyz(x as f32, y as f32, z as f32),
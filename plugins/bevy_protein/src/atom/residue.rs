@@ -0,0 +1,319 @@
+/*
+* Per-residue data for the standard amino acids (and the selenium variants found in structures).
+*
+* Hydrophobicities are on the Kyte & Doolittle (1982) scale; charges are those of the side chain at pH 7.
+* Templates list the heavy atoms only: hydrogens are left to distance-based bond perception.
+*/
+
+/// Heavy atoms shared by every amino acid. `OXT` is only present at the C-terminus.
+pub const BACKBONE_ATOMS: &[&str] = &["N", "CA", "C", "O", "OXT"];
+pub const BACKBONE_BONDS: &[(&str, &str)] = &[("N", "CA"), ("CA", "C"), ("C", "O"), ("C", "OXT")];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResidueData {
+    pub name: &'static str,
+    pub one_letter_code: char,
+    pub hydrophobicity: f32,
+    pub charge: i8,
+    /// Side chain heavy atoms, in PDB naming.
+    pub side_chain_atoms: &'static [&'static str],
+    /// Bonds involving side chain atoms, including the one to the backbone.
+    pub side_chain_bonds: &'static [(&'static str, &'static str)],
+}
+
+impl ResidueData {
+    pub fn atom_names(&self) -> impl Iterator<Item = &'static str> {
+        BACKBONE_ATOMS.iter().chain(self.side_chain_atoms).copied()
+    }
+
+    pub fn has_atom(&self, atom_name: &str) -> bool {
+        self.atom_names().any(|name| name == atom_name)
+    }
+
+    /// Every intra-residue bond of the template.
+    pub fn bonds(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        BACKBONE_BONDS.iter().chain(self.side_chain_bonds).copied()
+    }
+}
+
+const PHENYL_BONDS: [(&str, &str); 6] = [
+    ("CG", "CD1"),
+    ("CG", "CD2"),
+    ("CD1", "CE1"),
+    ("CD2", "CE2"),
+    ("CE1", "CZ"),
+    ("CE2", "CZ"),
+];
+
+pub const STANDARD_RESIDUES: &[ResidueData] = &[
+    ResidueData {
+        name: "ALA",
+        one_letter_code: 'A',
+        hydrophobicity: 1.8,
+        charge: 0,
+        side_chain_atoms: &["CB"],
+        side_chain_bonds: &[("CA", "CB")],
+    },
+    ResidueData {
+        name: "ARG",
+        one_letter_code: 'R',
+        hydrophobicity: -4.5,
+        charge: 1,
+        side_chain_atoms: &["CB", "CG", "CD", "NE", "CZ", "NH1", "NH2"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            ("CG", "CD"),
+            ("CD", "NE"),
+            ("NE", "CZ"),
+            ("CZ", "NH1"),
+            ("CZ", "NH2"),
+        ],
+    },
+    ResidueData {
+        name: "ASN",
+        one_letter_code: 'N',
+        hydrophobicity: -3.5,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "OD1", "ND2"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG"), ("CG", "OD1"), ("CG", "ND2")],
+    },
+    ResidueData {
+        name: "ASP",
+        one_letter_code: 'D',
+        hydrophobicity: -3.5,
+        charge: -1,
+        side_chain_atoms: &["CB", "CG", "OD1", "OD2"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG"), ("CG", "OD1"), ("CG", "OD2")],
+    },
+    ResidueData {
+        name: "CYS",
+        one_letter_code: 'C',
+        hydrophobicity: 2.5,
+        charge: 0,
+        side_chain_atoms: &["CB", "SG"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "SG")],
+    },
+    ResidueData {
+        name: "GLN",
+        one_letter_code: 'Q',
+        hydrophobicity: -3.5,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "CD", "OE1", "NE2"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            ("CG", "CD"),
+            ("CD", "OE1"),
+            ("CD", "NE2"),
+        ],
+    },
+    ResidueData {
+        name: "GLU",
+        one_letter_code: 'E',
+        hydrophobicity: -3.5,
+        charge: -1,
+        side_chain_atoms: &["CB", "CG", "CD", "OE1", "OE2"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            ("CG", "CD"),
+            ("CD", "OE1"),
+            ("CD", "OE2"),
+        ],
+    },
+    ResidueData {
+        name: "GLY",
+        one_letter_code: 'G',
+        hydrophobicity: -0.4,
+        charge: 0,
+        side_chain_atoms: &[],
+        side_chain_bonds: &[],
+    },
+    ResidueData {
+        name: "HIS",
+        one_letter_code: 'H',
+        hydrophobicity: -3.2,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "ND1", "CD2", "CE1", "NE2"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            ("CG", "ND1"),
+            ("CG", "CD2"),
+            ("ND1", "CE1"),
+            ("CD2", "NE2"),
+            ("CE1", "NE2"),
+        ],
+    },
+    ResidueData {
+        name: "ILE",
+        one_letter_code: 'I',
+        hydrophobicity: 4.5,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG1", "CG2", "CD1"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG1"), ("CB", "CG2"), ("CG1", "CD1")],
+    },
+    ResidueData {
+        name: "LEU",
+        one_letter_code: 'L',
+        hydrophobicity: 3.8,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "CD1", "CD2"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG"), ("CG", "CD1"), ("CG", "CD2")],
+    },
+    ResidueData {
+        name: "LYS",
+        one_letter_code: 'K',
+        hydrophobicity: -3.9,
+        charge: 1,
+        side_chain_atoms: &["CB", "CG", "CD", "CE", "NZ"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            ("CG", "CD"),
+            ("CD", "CE"),
+            ("CE", "NZ"),
+        ],
+    },
+    ResidueData {
+        name: "MET",
+        one_letter_code: 'M',
+        hydrophobicity: 1.9,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "SD", "CE"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG"), ("CG", "SD"), ("SD", "CE")],
+    },
+    ResidueData {
+        name: "MSE",
+        one_letter_code: 'M',
+        hydrophobicity: 1.9,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "SE", "CE"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG"), ("CG", "SE"), ("SE", "CE")],
+    },
+    ResidueData {
+        name: "PHE",
+        one_letter_code: 'F',
+        hydrophobicity: 2.8,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "CD1", "CD2", "CE1", "CE2", "CZ"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            PHENYL_BONDS[0],
+            PHENYL_BONDS[1],
+            PHENYL_BONDS[2],
+            PHENYL_BONDS[3],
+            PHENYL_BONDS[4],
+            PHENYL_BONDS[5],
+        ],
+    },
+    ResidueData {
+        name: "PRO",
+        one_letter_code: 'P',
+        hydrophobicity: -1.6,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "CD"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG"), ("CG", "CD"), ("CD", "N")],
+    },
+    ResidueData {
+        name: "PYL",
+        one_letter_code: 'O',
+        hydrophobicity: -3.9,
+        charge: 0,
+        // Only the lysine part: the pyrroline ring is left to distance-based perception.
+        side_chain_atoms: &["CB", "CG", "CD", "CE", "NZ"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            ("CG", "CD"),
+            ("CD", "CE"),
+            ("CE", "NZ"),
+        ],
+    },
+    ResidueData {
+        name: "SEC",
+        one_letter_code: 'U',
+        hydrophobicity: 2.5,
+        charge: 0,
+        side_chain_atoms: &["CB", "SE"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "SE")],
+    },
+    ResidueData {
+        name: "SER",
+        one_letter_code: 'S',
+        hydrophobicity: -0.8,
+        charge: 0,
+        side_chain_atoms: &["CB", "OG"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "OG")],
+    },
+    ResidueData {
+        name: "THR",
+        one_letter_code: 'T',
+        hydrophobicity: -0.7,
+        charge: 0,
+        side_chain_atoms: &["CB", "OG1", "CG2"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "OG1"), ("CB", "CG2")],
+    },
+    ResidueData {
+        name: "TRP",
+        one_letter_code: 'W',
+        hydrophobicity: -0.9,
+        charge: 0,
+        side_chain_atoms: &[
+            "CB", "CG", "CD1", "CD2", "NE1", "CE2", "CE3", "CZ2", "CZ3", "CH2",
+        ],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            ("CG", "CD1"),
+            ("CG", "CD2"),
+            ("CD1", "NE1"),
+            ("NE1", "CE2"),
+            ("CD2", "CE2"),
+            ("CD2", "CE3"),
+            ("CE2", "CZ2"),
+            ("CE3", "CZ3"),
+            ("CZ2", "CH2"),
+            ("CZ3", "CH2"),
+        ],
+    },
+    ResidueData {
+        name: "TYR",
+        one_letter_code: 'Y',
+        hydrophobicity: -1.3,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG", "CD1", "CD2", "CE1", "CE2", "CZ", "OH"],
+        side_chain_bonds: &[
+            ("CA", "CB"),
+            ("CB", "CG"),
+            PHENYL_BONDS[0],
+            PHENYL_BONDS[1],
+            PHENYL_BONDS[2],
+            PHENYL_BONDS[3],
+            PHENYL_BONDS[4],
+            PHENYL_BONDS[5],
+            ("CZ", "OH"),
+        ],
+    },
+    ResidueData {
+        name: "VAL",
+        one_letter_code: 'V',
+        hydrophobicity: 4.2,
+        charge: 0,
+        side_chain_atoms: &["CB", "CG1", "CG2"],
+        side_chain_bonds: &[("CA", "CB"), ("CB", "CG1"), ("CB", "CG2")],
+    },
+];
+
+pub fn residue_data(residue_name: &str) -> Option<&'static ResidueData> {
+    STANDARD_RESIDUES
+        .iter()
+        .find(|residue| residue.name == residue_name)
+}
+
+/// One-letter code of a (possibly modified) amino acid, `X` when unknown.
+pub fn one_letter_code(residue_name: &str) -> char {
+    residue_data(residue_name).map_or('X', |residue| residue.one_letter_code)
+}
@@ -1 +1,112 @@
+use bevy::math::Vec3;
+use bevy::utils::HashMap;
+use pdbtbx::Residue;
 
+use crate::atom::{element::covalent_radius, residue::residue_data};
+use crate::spatial_grid::SpatialGrid;
+
+// Slack (Å) added to the sum of covalent radii when deciding whether two atoms are bonded.
+const BOND_TOLERANCE: f32 = 0.45;
+// Closer than this (Å) the atoms are alternate positions or clashes rather than bonded.
+const MIN_BOND_LENGTH: f32 = 0.4;
+
+/// A bond between two atoms, identified by their index in the slice passed to [`perceive_covalent_bonds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CovalentBond {
+    pub a: usize,
+    pub b: usize,
+}
+
+/// Infers covalent bonds from inter-atomic distances: two atoms are bonded when they are closer
+/// than the sum of their covalent radii plus a tolerance.
+pub fn perceive_covalent_bonds(positions: &[Vec3], elements: &[&str]) -> Vec<CovalentBond> {
+    let radii: Vec<f32> = elements
+        .iter()
+        .map(|element| covalent_radius(element))
+        .collect();
+    let max_radius = radii.iter().copied().fold(0., f32::max);
+    let search_radius = 2. * max_radius + BOND_TOLERANCE;
+
+    let grid = SpatialGrid::new(search_radius, positions.iter().copied());
+
+    let mut bonds = Vec::<CovalentBond>::new();
+    for (a, position) in positions.iter().enumerate() {
+        for (b, distance) in grid.within(*position, search_radius) {
+            if b <= a || distance < MIN_BOND_LENGTH {
+                continue;
+            }
+            if distance <= radii[a] + radii[b] + BOND_TOLERANCE {
+                bonds.push(CovalentBond { a, b });
+            }
+        }
+    }
+
+    bonds
+}
+
+/// The bonds between the atoms at `indices`, re-indexed by their position in `indices`. Bonds to the other atoms
+/// are dropped.
+pub fn bonds_between(bonds: &[CovalentBond], indices: &[usize]) -> Vec<CovalentBond> {
+    let subset: HashMap<usize, usize> = indices
+        .iter()
+        .enumerate()
+        .map(|(subset_index, &index)| (index, subset_index))
+        .collect();
+    bonds
+        .iter()
+        .filter_map(|bond| {
+            Some(CovalentBond {
+                a: *subset.get(&bond.a)?,
+                b: *subset.get(&bond.b)?,
+            })
+        })
+        .collect()
+}
+
+/// Bonds inside the standard residues, taken from their templates rather than guessed from distances.
+///
+/// Atoms are indexed in the order of `residues`' atoms. Also returns, for every atom, the residue whose
+/// template accounts for all its bonds inside that residue, if any: hydrogens, unexpected atoms and residues
+/// with alternate conformers are left to [`perceive_covalent_bonds`].
+pub fn residue_template_bonds<'a>(
+    residues: impl Iterator<Item = &'a Residue>,
+) -> (Vec<Option<usize>>, Vec<CovalentBond>) {
+    let mut templated = Vec::<Option<usize>>::new();
+    let mut bonds = Vec::<CovalentBond>::new();
+
+    for (residue_index, residue) in residues.enumerate() {
+        let first_atom = templated.len();
+        let atom_names: Vec<&str> = residue.atoms().map(|atom| atom.name()).collect();
+
+        let template = residue
+            .name()
+            .and_then(residue_data)
+            .filter(|_| residue.conformers().count() == 1);
+        let Some(template) = template else {
+            templated.extend(atom_names.iter().map(|_| None));
+            continue;
+        };
+
+        templated.extend(
+            atom_names
+                .iter()
+                .map(|name| template.has_atom(name).then_some(residue_index)),
+        );
+
+        let find = |name: &str| {
+            atom_names
+                .iter()
+                .position(|atom_name| *atom_name == name)
+                .map(|i| first_atom + i)
+        };
+        bonds.extend(template.bonds().filter_map(|(a, b)| {
+            let (a, b) = (find(a)?, find(b)?);
+            Some(CovalentBond {
+                a: a.min(b),
+                b: a.max(b),
+            })
+        }));
+    }
+
+    (templated, bonds)
+}
@@ -0,0 +1,422 @@
+/**
+* Residue–residue distance matrices and contact maps, computed per pair of chains.
+*/
+use std::io::{self, Write};
+
+use bevy::math::Vec3;
+use pdbtbx::PDB;
+
+use crate::spatial_grid::SpatialGrid;
+
+/// Residues closer than this (Å) are conventionally considered in contact when measuring between Cα atoms.
+pub const DEFAULT_CONTACT_THRESHOLD: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceMode {
+    /// Distance between the Cα atoms of the two residues.
+    #[default]
+    CAlpha,
+    /// Smallest distance between any atom of one residue and any atom of the other.
+    MinimumAtom,
+}
+
+/// The amino acid residues of a single chain, i.e. those with a Cα atom.
+#[derive(Debug, Clone)]
+pub struct ChainResidues {
+    pub chain_id: String,
+    /// Position of each residue in [`PDB::residues`].
+    pub residue_indices: Vec<usize>,
+    pub serial_numbers: Vec<isize>,
+    pub ca_positions: Vec<Vec3>,
+    pub atom_positions: Vec<Vec<Vec3>>,
+}
+
+impl ChainResidues {
+    pub fn len(&self) -> usize {
+        self.residue_indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.residue_indices.is_empty()
+    }
+
+    pub fn from_pdb(pdb: &PDB) -> Vec<Self> {
+        let mut chains = Vec::<Self>::new();
+        let mut residue_offset = 0;
+
+        for chain in pdb.chains() {
+            let mut chain_residues = ChainResidues {
+                chain_id: chain.id().to_string(),
+                residue_indices: vec![],
+                serial_numbers: vec![],
+                ca_positions: vec![],
+                atom_positions: vec![],
+            };
+
+            for (i, residue) in chain.residues().enumerate() {
+                let Some(ca) = residue.atoms().find(|atom| atom.name() == "CA") else {
+                    continue;
+                };
+                let position = |atom: &pdbtbx::Atom| {
+                    let (x, y, z) = atom.pos();
+                    Vec3::new(x as f32, y as f32, z as f32)
+                };
+
+                chain_residues.residue_indices.push(residue_offset + i);
+                chain_residues.serial_numbers.push(residue.serial_number());
+                chain_residues.ca_positions.push(position(ca));
+                chain_residues
+                    .atom_positions
+                    .push(residue.atoms().map(position).collect());
+            }
+
+            residue_offset += chain.residue_count();
+            chains.push(chain_residues);
+        }
+
+        chains
+    }
+}
+
+/// Distances between the residues of two chains, row-major with one row per residue of `chain_a`.
+/// Pairs further apart than the cutoff used to build the matrix are stored as [`f32::INFINITY`].
+#[derive(Debug, Clone)]
+pub struct DistanceMatrix {
+    /// Index of the row chain in [`ContactMap::chains`].
+    pub chain_a: usize,
+    /// Index of the column chain in [`ContactMap::chains`].
+    pub chain_b: usize,
+    pub rows: usize,
+    pub columns: usize,
+    pub distances: Vec<f32>,
+}
+
+impl DistanceMatrix {
+    pub fn compute(
+        a: &ChainResidues,
+        b: &ChainResidues,
+        chain_a: usize,
+        chain_b: usize,
+        mode: DistanceMode,
+        cutoff: f32,
+    ) -> Self {
+        let mut distances = vec![f32::INFINITY; a.len() * b.len()];
+
+        match mode {
+            DistanceMode::CAlpha => {
+                let grid = SpatialGrid::new(cutoff, b.ca_positions.iter().copied());
+                for (i, ca) in a.ca_positions.iter().enumerate() {
+                    for (j, distance) in grid.within(*ca, cutoff) {
+                        distances[i * b.len() + j] = distance;
+                    }
+                }
+            }
+            DistanceMode::MinimumAtom => {
+                let owners: Vec<usize> = b
+                    .atom_positions
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(j, atoms)| atoms.iter().map(move |_| j))
+                    .collect();
+                let grid = SpatialGrid::new(cutoff, b.atom_positions.iter().flatten().copied());
+
+                for (i, atoms) in a.atom_positions.iter().enumerate() {
+                    for atom in atoms {
+                        for (k, distance) in grid.within(*atom, cutoff) {
+                            let entry = &mut distances[i * b.len() + owners[k]];
+                            *entry = entry.min(distance);
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            chain_a,
+            chain_b,
+            rows: a.len(),
+            columns: b.len(),
+            distances,
+        }
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f32 {
+        self.distances[row * self.columns + column]
+    }
+
+    /// `(row, column)` of every pair closer than `threshold`.
+    pub fn contacts(&self, threshold: f32) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.distances
+            .iter()
+            .enumerate()
+            .filter(move |(_, &distance)| distance <= threshold)
+            .map(|(k, _)| (k / self.columns, k % self.columns))
+    }
+
+    /// Writes the matrix as CSV, labelling rows and columns as `chain:serial`. Pairs beyond the cutoff are written as `inf`.
+    pub fn write_csv<W: Write>(&self, map: &ContactMap, mut writer: W) -> io::Result<()> {
+        let a = &map.chains[self.chain_a];
+        let b = &map.chains[self.chain_b];
+
+        for serial_number in &b.serial_numbers {
+            write!(writer, ",{}:{}", b.chain_id, serial_number)?;
+        }
+        writeln!(writer)?;
+
+        for (row, serial_number) in a.serial_numbers.iter().enumerate() {
+            write!(writer, "{}:{}", a.chain_id, serial_number)?;
+            for column in 0..self.columns {
+                let distance = self.get(row, column);
+                if distance.is_finite() {
+                    write!(writer, ",{:.3}", distance)?;
+                } else {
+                    write!(writer, ",inf")?;
+                }
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the matrix as a little-endian `float32` NumPy array (`.npy`, format version 1.0).
+    pub fn write_npy<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+            self.rows, self.columns
+        );
+        // The magic, the header length and the header (terminated by a newline) are padded to a multiple of 64 bytes.
+        let unpadded = MAGIC.len() + 2 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+        header.push('\n');
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for distance in &self.distances {
+            writer.write_all(&distance.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Distance matrices for every (unordered) pair of chains of a structure.
+#[derive(Debug, Clone)]
+pub struct ContactMap {
+    pub mode: DistanceMode,
+    pub cutoff: f32,
+    pub chains: Vec<ChainResidues>,
+    pub matrices: Vec<DistanceMatrix>,
+}
+
+impl ContactMap {
+    pub fn from_pdb(pdb: &PDB, mode: DistanceMode, cutoff: f32) -> Self {
+        Self::from_chains(ChainResidues::from_pdb(pdb), mode, cutoff)
+    }
+
+    /// Chains without residues are left out.
+    pub fn from_chains(chains: Vec<ChainResidues>, mode: DistanceMode, cutoff: f32) -> Self {
+        let chains: Vec<_> = chains
+            .into_iter()
+            .filter(|chain| !chain.is_empty())
+            .collect();
+
+        let mut matrices = Vec::<DistanceMatrix>::new();
+        for a in 0..chains.len() {
+            for b in a..chains.len() {
+                matrices.push(DistanceMatrix::compute(
+                    &chains[a], &chains[b], a, b, mode, cutoff,
+                ));
+            }
+        }
+
+        Self {
+            mode,
+            cutoff,
+            chains,
+            matrices,
+        }
+    }
+
+    pub fn matrix(&self, chain_a: usize, chain_b: usize) -> Option<&DistanceMatrix> {
+        self.matrices
+            .iter()
+            .find(|matrix| matrix.chain_a == chain_a && matrix.chain_b == chain_b)
+    }
+
+    pub fn residue_count(&self) -> usize {
+        self.chains.iter().map(ChainResidues::len).sum()
+    }
+
+    /// Distance between two residues addressed by their position in the concatenation of every chain,
+    /// i.e. as they are laid out in the full-structure map.
+    pub fn distance(&self, row: usize, column: usize) -> Option<f32> {
+        let (chain_a, row) = self.locate(row)?;
+        let (chain_b, column) = self.locate(column)?;
+
+        if chain_a <= chain_b {
+            Some(self.matrix(chain_a, chain_b)?.get(row, column))
+        } else {
+            Some(self.matrix(chain_b, chain_a)?.get(column, row))
+        }
+    }
+
+    /// Residue index (in [`PDB::residues`]) at a position of the full-structure map.
+    pub fn residue_index(&self, position: usize) -> Option<usize> {
+        let (chain, i) = self.locate(position)?;
+        Some(self.chains[chain].residue_indices[i])
+    }
+
+    pub fn ca_position(&self, position: usize) -> Option<Vec3> {
+        let (chain, i) = self.locate(position)?;
+        Some(self.chains[chain].ca_positions[i])
+    }
+
+    fn locate(&self, mut position: usize) -> Option<(usize, usize)> {
+        for (chain, residues) in self.chains.iter().enumerate() {
+            if position < residues.len() {
+                return Some((chain, position));
+            }
+            position -= residues.len();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points scattered over a 30 Å cube centred on the origin, the same on every run.
+    fn scattered(count: usize, seed: u32) -> Vec<Vec3> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+        (0..count)
+            .map(|_| Vec3::new(next(), next(), next()) * 30. - 15.)
+            .collect()
+    }
+
+    /// `count` residues of one to four atoms, the first of which is the Cα.
+    fn chain(chain_id: &str, count: usize, seed: u32) -> ChainResidues {
+        let atoms = scattered(4 * count, seed);
+        let atom_positions: Vec<Vec<Vec3>> = (0..count)
+            .map(|i| atoms[4 * i..4 * i + 1 + i % 4].to_vec())
+            .collect();
+        ChainResidues {
+            chain_id: chain_id.to_string(),
+            residue_indices: (0..count).collect(),
+            serial_numbers: (1..=count as isize).collect(),
+            ca_positions: atom_positions.iter().map(|atoms| atoms[0]).collect(),
+            atom_positions,
+        }
+    }
+
+    fn brute_force_distance(a: &[Vec3], b: &[Vec3], cutoff: f32) -> f32 {
+        let distance = a
+            .iter()
+            .flat_map(|a| b.iter().map(|b| a.distance(*b)))
+            .fold(f32::INFINITY, f32::min);
+        if distance <= cutoff {
+            distance
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    #[test]
+    fn distances_match_brute_force() {
+        let (a, b) = (chain("A", 40, 1), chain("B", 30, 2));
+        for mode in [DistanceMode::CAlpha, DistanceMode::MinimumAtom] {
+            let matrix = DistanceMatrix::compute(&a, &b, 0, 1, mode, 8.);
+            assert_eq!((matrix.rows, matrix.columns), (40, 30));
+            for row in 0..matrix.rows {
+                for column in 0..matrix.columns {
+                    let expected = match mode {
+                        DistanceMode::CAlpha => brute_force_distance(
+                            &a.ca_positions[row..=row],
+                            &b.ca_positions[column..=column],
+                            8.,
+                        ),
+                        DistanceMode::MinimumAtom => brute_force_distance(
+                            &a.atom_positions[row],
+                            &b.atom_positions[column],
+                            8.,
+                        ),
+                    };
+                    assert_eq!(matrix.get(row, column), expected, "{mode:?} {row} {column}");
+                }
+            }
+            assert!(matrix.contacts(8.).count() > 0);
+        }
+    }
+
+    #[test]
+    fn distance_spans_every_chain_pair() {
+        let chains = vec![
+            chain("A", 3, 3),
+            chain("E", 0, 4),
+            chain("B", 4, 5),
+            chain("C", 2, 6),
+        ];
+        let map = ContactMap::from_chains(chains, DistanceMode::CAlpha, 20.);
+        assert_eq!(map.chains.len(), 3);
+        assert_eq!(map.matrices.len(), 6);
+        assert_eq!(map.residue_count(), 9);
+
+        assert_eq!(map.locate(0), Some((0, 0)));
+        assert_eq!(map.locate(3), Some((1, 0)));
+        assert_eq!(map.locate(6), Some((1, 3)));
+        assert_eq!(map.locate(8), Some((2, 1)));
+        assert_eq!(map.locate(9), None);
+        assert_eq!(map.distance(9, 0), None);
+
+        let positions: Vec<Vec3> = (0..9).map(|i| map.ca_position(i).unwrap()).collect();
+        for row in 0..9 {
+            for column in 0..9 {
+                let expected =
+                    brute_force_distance(&positions[row..=row], &positions[column..=column], 20.);
+                // Below the diagonal of chain pairs, the matrix of the transposed pair is read.
+                assert_eq!(map.distance(row, column), Some(expected), "{row} {column}");
+            }
+        }
+        assert_eq!(map.matrix(2, 1).map(|matrix| matrix.chain_a), None);
+    }
+
+    #[test]
+    fn npy_header_is_padded_to_64_bytes() {
+        for (rows, columns) in [(3, 4), (120, 7), (1, 1)] {
+            let matrix = DistanceMatrix {
+                chain_a: 0,
+                chain_b: 0,
+                rows,
+                columns,
+                distances: (0..rows * columns).map(|i| i as f32 / 2.).collect(),
+            };
+            let mut bytes = Vec::new();
+            matrix.write_npy(&mut bytes).unwrap();
+
+            assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+            let header_length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+            let data_start = 10 + header_length;
+            assert_eq!(data_start % 64, 0);
+            let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+            assert!(header.ends_with('\n'));
+            assert!(
+                header.contains(&format!("'shape': ({rows}, {columns})")),
+                "{header}"
+            );
+
+            let data: Vec<f32> = bytes[data_start..]
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect();
+            assert_eq!(data, matrix.distances);
+        }
+    }
+}
@@ -0,0 +1,534 @@
+/**
+* Electron density and cryo-EM maps in the CCP4/MRC format.
+*
+* The 1024-byte header gives the grid size along columns, rows and sections, which crystallographic axis
+* each of those runs along, the unit cell and where the grid starts. The samples are reordered so that the
+* grid is always stored x-fastest along the cell axes.
+*/
+use bevy::utils::thiserror;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use thiserror::Error;
+
+const HEADER_LENGTH: usize = 1024;
+
+/// A scalar field sampled on a (possibly non-orthogonal) regular grid.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct DensityMap {
+    /// Number of samples along the x, y and z cell axes.
+    pub dimensions: UVec3,
+    /// Position (Å) of the first sample.
+    pub origin: Vec3,
+    /// Columns are the steps (Å) between neighbouring samples along each cell axis.
+    pub basis: Mat3,
+    /// Samples with x varying fastest, then y, then z.
+    pub values: Vec<f32>,
+    pub mean: f32,
+    /// Root-mean-square deviation from the mean, the unit of contour levels ("σ").
+    pub rms: f32,
+}
+
+impl DensityMap {
+    pub fn index(&self, point: UVec3) -> usize {
+        (point.x + self.dimensions.x * (point.y + self.dimensions.y * point.z)) as usize
+    }
+
+    pub fn position(&self, point: UVec3) -> Vec3 {
+        self.origin + self.basis * point.as_vec3()
+    }
+
+    /// The absolute value of a contour at `sigma_level` standard deviations above the mean.
+    pub fn level(&self, sigma_level: f32) -> f32 {
+        self.mean + sigma_level * self.rms
+    }
+
+    /// Smallest block of grid points (as an inclusive `min..=max` pair) covering an axis-aligned box, if it overlaps the map.
+    pub fn grid_box(&self, min: Vec3, max: Vec3) -> Option<(UVec3, UVec3)> {
+        let to_grid = self.basis.inverse();
+
+        let mut lower = Vec3::splat(f32::INFINITY);
+        let mut upper = Vec3::splat(f32::NEG_INFINITY);
+        for corner in 0..8 {
+            let corner = Vec3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            let point = to_grid * (corner - self.origin);
+            lower = lower.min(point);
+            upper = upper.max(point);
+        }
+
+        let last = (self.dimensions.as_ivec3() - IVec3::ONE).max(IVec3::ZERO);
+        let lower = lower.floor().as_ivec3().max(IVec3::ZERO);
+        let upper = upper.ceil().as_ivec3().min(last);
+        if lower.cmpgt(upper).any() {
+            return None;
+        }
+
+        Some((lower.as_uvec3(), upper.as_uvec3()))
+    }
+
+    /// Copies the samples of the block `min..=max`, x-fastest.
+    pub fn sub_grid(&self, min: UVec3, max: UVec3) -> Vec<f32> {
+        let size = max - min + UVec3::ONE;
+        let mut values = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                let start = self.index(UVec3::new(min.x, y, z));
+                values.extend_from_slice(&self.values[start..=start + (max.x - min.x) as usize]);
+            }
+        }
+        values
+    }
+}
+
+#[derive(Default)]
+pub struct DensityMapLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DensityMapLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("map is shorter than its header says ({0} bytes)")]
+    Truncated(usize),
+    #[error("unsupported map mode {0}")]
+    UnsupportedMode(i32),
+    #[error("invalid axis order {0:?}")]
+    InvalidAxisOrder([i32; 3]),
+    #[error("map of {0:?} samples is too large")]
+    TooLarge([i32; 3]),
+}
+
+struct Header<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Header<'_> {
+    /// Reads the 1-based 32-bit `word` of the header.
+    fn word(&self, word: usize) -> [u8; 4] {
+        let offset = (word - 1) * 4;
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.bytes[offset..offset + 4]);
+        bytes
+    }
+
+    fn i32(&self, word: usize) -> i32 {
+        if self.little_endian {
+            i32::from_le_bytes(self.word(word))
+        } else {
+            i32::from_be_bytes(self.word(word))
+        }
+    }
+
+    fn f32(&self, word: usize) -> f32 {
+        if self.little_endian {
+            f32::from_le_bytes(self.word(word))
+        } else {
+            f32::from_be_bytes(self.word(word))
+        }
+    }
+}
+
+/// Converts fractional coordinates to Cartesian ones (Å), with `a` along x and `b` in the xy plane.
+fn orthogonalisation_matrix(cell: Vec3, angles: Vec3) -> Mat3 {
+    let (cos_α, cos_β, cos_γ) = (
+        angles.x.to_radians().cos(),
+        angles.y.to_radians().cos(),
+        angles.z.to_radians().cos(),
+    );
+    let sin_γ = angles.z.to_radians().sin();
+    let volume =
+        (1. - cos_α * cos_α - cos_β * cos_β - cos_γ * cos_γ + 2. * cos_α * cos_β * cos_γ).sqrt();
+
+    Mat3::from_cols(
+        Vec3::new(cell.x, 0., 0.),
+        Vec3::new(cell.y * cos_γ, cell.y * sin_γ, 0.),
+        Vec3::new(
+            cell.z * cos_β,
+            cell.z * (cos_α - cos_β * cos_γ) / sin_γ,
+            cell.z * volume / sin_γ,
+        ),
+    )
+}
+
+pub fn parse_density_map(bytes: &[u8]) -> Result<DensityMap, DensityMapLoaderError> {
+    if bytes.len() < HEADER_LENGTH {
+        return Err(DensityMapLoaderError::Truncated(bytes.len()));
+    }
+
+    // MACHST (word 54) stamps the byte order; old files leave it empty, in which case a sane mode decides.
+    let mut header = Header {
+        bytes,
+        little_endian: match bytes[212] {
+            0x44 => true,
+            0x11 => false,
+            _ => true,
+        },
+    };
+    if bytes[212] != 0x44 && bytes[212] != 0x11 && !(0..=12).contains(&header.i32(4)) {
+        header.little_endian = false;
+    }
+
+    let crs_counts = [header.i32(1), header.i32(2), header.i32(3)];
+    let mode = header.i32(4);
+    let crs_start = [header.i32(5), header.i32(6), header.i32(7)];
+    let intervals = IVec3::new(header.i32(8), header.i32(9), header.i32(10)).max(IVec3::ONE);
+    let cell = Vec3::new(header.f32(11), header.f32(12), header.f32(13));
+    let mut angles = Vec3::new(header.f32(14), header.f32(15), header.f32(16));
+    if angles.cmple(Vec3::ZERO).any() {
+        angles = Vec3::splat(90.);
+    }
+    let axis_order = [header.i32(17), header.i32(18), header.i32(19)];
+    let extended_header_length = header.i32(24).max(0) as usize;
+    let mrc_origin = Vec3::new(header.f32(50), header.f32(51), header.f32(52));
+
+    // Which of columns/rows/sections runs along x, y and z.
+    let mut crs_of_axis = [usize::MAX; 3];
+    for (crs, axis) in axis_order.iter().enumerate() {
+        match axis {
+            1..=3 if crs_of_axis[(*axis - 1) as usize] == usize::MAX => {
+                crs_of_axis[(*axis - 1) as usize] = crs;
+            }
+            _ => return Err(DensityMapLoaderError::InvalidAxisOrder(axis_order)),
+        }
+    }
+
+    let sample_size = match mode {
+        0 => 1,
+        1 | 6 => 2,
+        2 => 4,
+        _ => return Err(DensityMapLoaderError::UnsupportedMode(mode)),
+    };
+    let [nc, nr, ns] = crs_counts.map(|count| count.max(0) as usize);
+    let data_start = HEADER_LENGTH + extended_header_length;
+    let data_end = nc
+        .checked_mul(nr)
+        .and_then(|count| count.checked_mul(ns))
+        .and_then(|count| count.checked_mul(sample_size))
+        .and_then(|length| length.checked_add(data_start))
+        .ok_or(DensityMapLoaderError::TooLarge(crs_counts))?;
+    if bytes.len() < data_end {
+        return Err(DensityMapLoaderError::Truncated(bytes.len()));
+    }
+    let data = &bytes[data_start..data_end];
+
+    let sample = |i: usize| -> f32 {
+        let bytes = &data[i * sample_size..(i + 1) * sample_size];
+        match (mode, header.little_endian) {
+            (0, _) => bytes[0] as i8 as f32,
+            (1, true) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            (1, false) => i16::from_be_bytes([bytes[0], bytes[1]]) as f32,
+            (6, true) => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            (6, false) => u16::from_be_bytes([bytes[0], bytes[1]]) as f32,
+            (_, true) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            (_, false) => f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    };
+
+    let dimensions = UVec3::from_array(crs_of_axis.map(|crs| crs_counts[crs].max(0) as u32));
+    let start = IVec3::from_array(crs_of_axis.map(|crs| crs_start[crs]));
+
+    let mut values = vec![0.; nc * nr * ns];
+    for s in 0..ns {
+        for r in 0..nr {
+            for c in 0..nc {
+                let crs = [c, r, s];
+                let [x, y, z] = crs_of_axis.map(|i| crs[i]);
+                values[x + dimensions.x as usize * (y + dimensions.y as usize * z)] =
+                    sample(c + nc * (r + nr * s));
+            }
+        }
+    }
+
+    let n = values.len().max(1) as f64;
+    let mean = values.iter().map(|value| *value as f64).sum::<f64>() / n;
+    let rms = (values
+        .iter()
+        .map(|value| (*value as f64 - mean).powi(2))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+
+    let fractional_to_cartesian = orthogonalisation_matrix(cell, angles);
+    let step = Mat3::from_diagonal(intervals.as_vec3().recip());
+    // MRC2014 maps place the grid with ORIGIN instead of NSTART; CCP4 maps leave ORIGIN empty.
+    let origin = if mrc_origin != Vec3::ZERO {
+        mrc_origin
+    } else {
+        fractional_to_cartesian * (step * start.as_vec3())
+    };
+
+    Ok(DensityMap {
+        dimensions,
+        origin,
+        basis: fractional_to_cartesian * step,
+        values,
+        mean: mean as f32,
+        rms: rms as f32,
+    })
+}
+
+impl AssetLoader for DensityMapLoader {
+    type Asset = DensityMap;
+    type Settings = ();
+    type Error = DensityMapLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+
+            let density_map = parse_density_map(&bytes)?;
+            info!(
+                "density map of {} samples, mean {}, rms {}",
+                density_map.dimensions, density_map.mean, density_map.rms
+            );
+
+            Ok(density_map)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ccp4", "map", "mrc"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bytes of a mode 2 map, by header word.
+    struct MapFile {
+        header: Vec<u8>,
+        little_endian: bool,
+    }
+
+    impl MapFile {
+        /// `counts` columns, rows and sections along x, y and z, one sample per Ångström of an orthogonal cell.
+        fn new(counts: [i32; 3], little_endian: bool) -> Self {
+            let mut map = Self {
+                header: vec![0; HEADER_LENGTH],
+                little_endian,
+            };
+            map.i32(4, 2);
+            for (axis, count) in counts.into_iter().enumerate() {
+                map.i32(1 + axis, count)
+                    .i32(8 + axis, count)
+                    .f32(11 + axis, count as f32)
+                    .f32(14 + axis, 90.)
+                    .i32(17 + axis, axis as i32 + 1);
+            }
+            map.header[208..212].copy_from_slice(b"MAP ");
+            map.header[212] = if little_endian { 0x44 } else { 0x11 };
+            map
+        }
+
+        fn word(&mut self, word: usize, bytes: [u8; 4]) -> &mut Self {
+            let offset = (word - 1) * 4;
+            self.header[offset..offset + 4].copy_from_slice(&bytes);
+            self
+        }
+
+        fn i32(&mut self, word: usize, value: i32) -> &mut Self {
+            let bytes = if self.little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            };
+            self.word(word, bytes)
+        }
+
+        fn f32(&mut self, word: usize, value: f32) -> &mut Self {
+            let bytes = if self.little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            };
+            self.word(word, bytes)
+        }
+
+        /// The header followed by `samples`, in file order.
+        fn bytes(&self, samples: impl IntoIterator<Item = f32>) -> Vec<u8> {
+            let mut bytes = self.header.clone();
+            for sample in samples {
+                let sample = if self.little_endian {
+                    sample.to_le_bytes()
+                } else {
+                    sample.to_be_bytes()
+                };
+                bytes.extend_from_slice(&sample);
+            }
+            bytes
+        }
+    }
+
+    /// 2 columns along z, 3 rows along x and 4 sections along y, starting at column 5, row 6 and section 7, with
+    /// samples of 1, 2 and 5 Å along x, y and z. Each sample is `c + 10 r + 100 s`.
+    fn permuted_map(little_endian: bool) -> MapFile {
+        let mut map = MapFile::new([2, 3, 4], little_endian);
+        map.i32(5, 5).i32(6, 6).i32(7, 7);
+        map.i32(8, 30).i32(9, 20).i32(10, 10);
+        map.f32(11, 30.).f32(12, 40.).f32(13, 50.);
+        map.i32(17, 3).i32(18, 1).i32(19, 2);
+        map
+    }
+
+    fn permuted_samples() -> impl Iterator<Item = f32> {
+        (0..4).flat_map(|s| {
+            (0..3).flat_map(move |r| (0..2).map(move |c| (c + 10 * r + 100 * s) as f32))
+        })
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-4), "{a} != {b}");
+    }
+
+    fn assert_permuted_map(density_map: &DensityMap) {
+        assert_eq!(density_map.dimensions, UVec3::new(3, 4, 2));
+        for z in 0..2 {
+            for y in 0..4 {
+                for x in 0..3 {
+                    let point = UVec3::new(x, y, z);
+                    assert_eq!(
+                        density_map.values[density_map.index(point)],
+                        (z + 10 * x + 100 * y) as f32,
+                        "{point}"
+                    );
+                }
+            }
+        }
+        assert_near(density_map.origin, Vec3::new(6., 14., 25.));
+        assert!(density_map
+            .basis
+            .abs_diff_eq(Mat3::from_diagonal(Vec3::new(1., 2., 5.)), 1e-4));
+    }
+
+    #[test]
+    fn axes_are_reordered_x_fastest() {
+        let density_map = parse_density_map(&permuted_map(true).bytes(permuted_samples())).unwrap();
+        assert_permuted_map(&density_map);
+        assert_eq!(density_map.mean, 160.5);
+    }
+
+    #[test]
+    fn machst_sets_the_byte_order() {
+        let density_map =
+            parse_density_map(&permuted_map(false).bytes(permuted_samples())).unwrap();
+        assert_permuted_map(&density_map);
+
+        // Without a stamp, a mode that makes no sense in little-endian gives big-endian away.
+        let mut map = permuted_map(false);
+        map.header[212] = 0;
+        let density_map = parse_density_map(&map.bytes(permuted_samples())).unwrap();
+        assert_permuted_map(&density_map);
+    }
+
+    #[test]
+    fn non_orthogonal_cells_shear_the_grid() {
+        // A hexagonal cell, 10 samples along each 10 Å axis, starting one sample along a and b.
+        let mut map = MapFile::new([10, 10, 10], true);
+        map.f32(16, 120.).i32(5, 1).i32(6, 1);
+        let density_map = parse_density_map(&map.bytes(vec![0.; 1000])).unwrap();
+
+        let b = Vec3::new(-0.5, 3f32.sqrt() / 2., 0.);
+        assert_near(density_map.basis.x_axis, Vec3::X);
+        assert_near(density_map.basis.y_axis, b);
+        assert_near(density_map.basis.z_axis, Vec3::Z);
+        assert_near(density_map.origin, Vec3::X + b);
+        assert_near(
+            density_map.position(UVec3::new(2, 2, 3)),
+            3. * (Vec3::X + b) + 3. * Vec3::Z,
+        );
+    }
+
+    #[test]
+    fn origin_takes_precedence_over_nstart() {
+        let mut map = MapFile::new([2, 2, 2], true);
+        map.i32(5, 4).i32(6, 4).i32(7, 4);
+        map.f32(50, -1.5).f32(51, 2.).f32(52, 3.25);
+        let density_map = parse_density_map(&map.bytes(vec![0.; 8])).unwrap();
+        assert_eq!(density_map.origin, Vec3::new(-1.5, 2., 3.25));
+    }
+
+    #[test]
+    fn invalid_maps_are_rejected() {
+        let mut map = MapFile::new([i32::MAX; 3], true);
+        assert!(matches!(
+            parse_density_map(&map.bytes([])),
+            Err(DensityMapLoaderError::TooLarge([
+                i32::MAX,
+                i32::MAX,
+                i32::MAX
+            ]))
+        ));
+
+        map = MapFile::new([2, 2, 2], true);
+        assert!(matches!(
+            parse_density_map(&map.bytes(vec![0.; 7])),
+            Err(DensityMapLoaderError::Truncated(_))
+        ));
+        map.i32(19, 1);
+        assert!(matches!(
+            parse_density_map(&map.bytes(vec![0.; 8])),
+            Err(DensityMapLoaderError::InvalidAxisOrder([1, 2, 1]))
+        ));
+        map.i32(19, 3).i32(4, 3);
+        assert!(matches!(
+            parse_density_map(&map.bytes(vec![0.; 8])),
+            Err(DensityMapLoaderError::UnsupportedMode(3))
+        ));
+    }
+
+    /// 4 × 4 × 4 samples, 1 Å apart from the origin, each its index.
+    fn cube() -> DensityMap {
+        let map = MapFile::new([4, 4, 4], true);
+        parse_density_map(&map.bytes((0..64).map(|i| i as f32))).unwrap()
+    }
+
+    #[test]
+    fn grid_box_is_clipped_to_the_map() {
+        let density_map = cube();
+        assert_eq!(
+            density_map.grid_box(Vec3::splat(-5.), Vec3::new(1.5, 0.5, 2.)),
+            Some((UVec3::ZERO, UVec3::new(2, 1, 2)))
+        );
+        assert_eq!(
+            density_map.grid_box(Vec3::new(2.2, 1., 0.), Vec3::splat(10.)),
+            Some((UVec3::new(2, 1, 0), UVec3::splat(3)))
+        );
+        assert_eq!(
+            density_map.grid_box(Vec3::splat(5.), Vec3::splat(10.)),
+            None
+        );
+        assert_eq!(
+            density_map.grid_box(Vec3::splat(-10.), Vec3::splat(-2.)),
+            None
+        );
+    }
+
+    #[test]
+    fn sub_grid_copies_the_block_x_fastest() {
+        let density_map = cube();
+        assert_eq!(
+            density_map.sub_grid(UVec3::new(1, 2, 3), UVec3::new(2, 3, 3)),
+            [57., 58., 61., 62.]
+        );
+        assert_eq!(
+            density_map.sub_grid(UVec3::ZERO, UVec3::splat(3)),
+            density_map.values
+        );
+    }
+}
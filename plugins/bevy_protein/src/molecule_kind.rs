@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use pdbtbx::Residue;
+
+use crate::atom::residue::residue_data;
+
+const WATER: &[&str] = &["HOH", "WAT", "H2O", "DOD", "D2O"];
+
+const IONS: &[&str] = &[
+    "NA", "K", "LI", "RB", "CS", "MG", "CA", "SR", "BA", "ZN", "FE", "FE2", "MN", "CU", "CU1",
+    "CO", "NI", "CD", "HG", "PT", "AU", "AG", "CL", "BR", "IOD", "F", "AL", "GA", "YB", "SM",
+];
+
+const CARBOHYDRATES: &[&str] = &[
+    "NAG", "NDG", "MAN", "BMA", "GAL", "GLA", "GLC", "BGC", "FUC", "FUL", "SIA", "XYS", "XYP",
+    "FRU", "A2G", "NGA", "RAM", "GCU", "IDS",
+];
+
+const NUCLEOTIDES: &[&str] = &[
+    "A", "C", "G", "U", "T", "I", "DA", "DC", "DG", "DT", "DU", "DI",
+];
+
+/// What a residue of a structure is, which decides how (and whether) it is drawn by default.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MoleculeKind {
+    Polymer,
+    Ligand,
+    Water,
+    Ion,
+    Carbohydrate,
+}
+
+impl MoleculeKind {
+    pub const ALL: [MoleculeKind; 5] = [
+        MoleculeKind::Polymer,
+        MoleculeKind::Ligand,
+        MoleculeKind::Water,
+        MoleculeKind::Ion,
+        MoleculeKind::Carbohydrate,
+    ];
+
+    pub fn classify(residue: &Residue) -> Self {
+        let name = residue.name().unwrap_or_default();
+
+        if WATER.contains(&name) {
+            Self::Water
+        } else if residue_data(name).is_some() || NUCLEOTIDES.contains(&name) {
+            Self::Polymer
+        } else if CARBOHYDRATES.contains(&name) {
+            Self::Carbohydrate
+        } else if IONS.contains(&name) || residue.atoms().count() == 1 {
+            Self::Ion
+        } else if residue.atoms().all(|atom| !atom.hetero()) {
+            // Non-standard residues written as ATOM records are part of the polymer.
+            Self::Polymer
+        } else {
+            Self::Ligand
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Polymer => "Polymer",
+            Self::Ligand => "Ligands",
+            Self::Water => "Water",
+            Self::Ion => "Ions",
+            Self::Carbohydrate => "Carbohydrates",
+        }
+    }
+}
+
+/// Which kinds of molecule are currently shown. Waters are hidden by default.
+#[derive(Resource, Debug)]
+pub struct MoleculeKindVisibility {
+    pub polymer: bool,
+    pub ligand: bool,
+    pub water: bool,
+    pub ion: bool,
+    pub carbohydrate: bool,
+}
+
+impl Default for MoleculeKindVisibility {
+    fn default() -> Self {
+        Self {
+            polymer: true,
+            ligand: true,
+            water: false,
+            ion: true,
+            carbohydrate: true,
+        }
+    }
+}
+
+impl MoleculeKindVisibility {
+    fn flag_mut(&mut self, kind: MoleculeKind) -> &mut bool {
+        match kind {
+            MoleculeKind::Polymer => &mut self.polymer,
+            MoleculeKind::Ligand => &mut self.ligand,
+            MoleculeKind::Water => &mut self.water,
+            MoleculeKind::Ion => &mut self.ion,
+            MoleculeKind::Carbohydrate => &mut self.carbohydrate,
+        }
+    }
+
+    pub fn is_visible(&self, kind: MoleculeKind) -> bool {
+        match kind {
+            MoleculeKind::Polymer => self.polymer,
+            MoleculeKind::Ligand => self.ligand,
+            MoleculeKind::Water => self.water,
+            MoleculeKind::Ion => self.ion,
+            MoleculeKind::Carbohydrate => self.carbohydrate,
+        }
+    }
+
+    pub fn set_visible(&mut self, kind: MoleculeKind, visible: bool) {
+        *self.flag_mut(kind) = visible;
+    }
+
+    pub fn toggle(&mut self, kind: MoleculeKind) {
+        let flag = self.flag_mut(kind);
+        *flag = !*flag;
+    }
+
+    pub fn visibility(&self, kind: MoleculeKind) -> Visibility {
+        if self.is_visible(kind) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        }
+    }
+}
+
+/// Shows or hides every entity tagged with a [`MoleculeKind`] according to [`MoleculeKindVisibility`].
+pub fn apply_molecule_kind_visibility(
+    molecule_kind_visibility: Res<MoleculeKindVisibility>,
+    mut query: Query<(&MoleculeKind, &mut Visibility)>,
+) {
+    for (kind, mut visibility) in query.iter_mut() {
+        visibility.set_if_neq(molecule_kind_visibility.visibility(*kind));
+    }
+}
@@ -0,0 +1,192 @@
+/**
+* Backbone dihedral angles (φ, ψ, ω) and their Ramachandran classification.
+
+*/
+use bevy::math::Vec3;
+use pdbtbx::*;
+
+// Longest C(i-1)–N(i) distance (Å) still treated as a peptide bond. Anything longer is a chain break.
+const PEPTIDE_BOND_CUTOFF: f32 = 2.0;
+
+/// Signed dihedral angle (in degrees, in `(-180, 180]`) defined by four points, following the IUPAC convention.
+pub fn dihedral(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3) -> f32 {
+    let b1 = p1 - p0;
+    let b2 = p2 - p1;
+    let b3 = p3 - p2;
+
+    let n1 = b1.cross(b2);
+    let n2 = b2.cross(b3);
+
+    let y = b2.length() * b1.dot(n2);
+    let x = n1.dot(n2);
+
+    y.atan2(x).to_degrees()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RamachandranRegion {
+    Favoured,
+    Allowed,
+    Outlier,
+}
+
+/// Glycine and proline have markedly different Ramachandran distributions, so they are classified separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ResidueKind {
+    General,
+    Glycine,
+    Proline,
+}
+
+impl ResidueKind {
+    pub fn from_residue_name(name: &str) -> Self {
+        match name {
+            "GLY" => Self::Glycine,
+            "PRO" => Self::Proline,
+            _ => Self::General,
+        }
+    }
+}
+
+// (φ_min, φ_max, ψ_min, ψ_max), in degrees. These are coarse rectangular approximations of the
+// Lovell et al. (2003) contours: good enough for colouring a plot, not for validation reports.
+type Window = (f32, f32, f32, f32);
+
+const GENERAL_FAVOURED: &[Window] = &[
+    (-180., -45., 100., 180.),
+    (-180., -45., -180., -165.),
+    (-115., -40., -70., -5.),
+];
+const GENERAL_ALLOWED: &[Window] = &[
+    (-180., -30., 60., 180.),
+    (-180., -30., -180., -150.),
+    (-180., -25., -110., 50.),
+    (40., 90., 0., 90.),
+];
+const GLYCINE_FAVOURED: &[Window] = &[
+    (-180., -45., 100., 180.),
+    (45., 180., -180., -100.),
+    (-115., -40., -70., -5.),
+    (40., 115., 5., 70.),
+];
+const GLYCINE_ALLOWED: &[Window] = &[
+    (-180., -25., 60., 180.),
+    (25., 180., -180., -60.),
+    (-180., -25., -110., 50.),
+    (25., 180., -50., 110.),
+    (-180., 180., 150., 180.),
+    (-180., 180., -180., -150.),
+];
+const PROLINE_FAVOURED: &[Window] = &[(-90., -45., 110., 180.), (-90., -45., -60., -10.)];
+const PROLINE_ALLOWED: &[Window] = &[
+    (-110., -35., 60., 180.),
+    (-110., -35., -180., -160.),
+    (-110., -35., -80., 30.),
+];
+
+fn contains(windows: &[Window], φ: f32, ψ: f32) -> bool {
+    windows.iter().any(|&(φ_min, φ_max, ψ_min, ψ_max)| {
+        (φ_min..=φ_max).contains(&φ) && (ψ_min..=ψ_max).contains(&ψ)
+    })
+}
+
+impl RamachandranRegion {
+    pub fn classify(φ: f32, ψ: f32, kind: ResidueKind) -> Self {
+        let (favoured, allowed) = match kind {
+            ResidueKind::General => (GENERAL_FAVOURED, GENERAL_ALLOWED),
+            ResidueKind::Glycine => (GLYCINE_FAVOURED, GLYCINE_ALLOWED),
+            ResidueKind::Proline => (PROLINE_FAVOURED, PROLINE_ALLOWED),
+        };
+
+        if contains(favoured, φ, ψ) {
+            Self::Favoured
+        } else if contains(allowed, φ, ψ) {
+            Self::Allowed
+        } else {
+            Self::Outlier
+        }
+    }
+}
+
+/// The N, Cα and C positions of a single residue.
+#[derive(Debug, Clone, Copy)]
+pub struct BackboneAtoms {
+    pub n: Vec3,
+    pub ca: Vec3,
+    pub c: Vec3,
+}
+
+impl BackboneAtoms {
+    pub fn from_residue(residue: &Residue) -> Option<Self> {
+        let position = |name: &str| {
+            residue
+                .atoms()
+                .find(|atom| atom.name() == name)
+                .map(|atom| {
+                    let (x, y, z) = atom.pos();
+                    Vec3::new(x as f32, y as f32, z as f32)
+                })
+        };
+
+        Some(Self {
+            n: position("N")?,
+            ca: position("CA")?,
+            c: position("C")?,
+        })
+    }
+
+    /// Whether `self` is covalently linked to `next` through a peptide bond.
+    pub fn is_bonded_to(&self, next: &BackboneAtoms) -> bool {
+        self.c.distance(next.n) < PEPTIDE_BOND_CUTOFF
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BackboneTorsion {
+    /// Position of the residue in [`PDB::residues`].
+    pub residue_index: usize,
+    pub chain_id: String,
+    pub serial_number: isize,
+    pub residue_name: String,
+    pub ca_position: Vec3,
+    pub kind: ResidueKind,
+    /// C(i-1) – N – Cα – C
+    pub φ: Option<f32>,
+    /// N – Cα – C – N(i+1)
+    pub ψ: Option<f32>,
+    /// Cα – C – N(i+1) – Cα(i+1)
+    pub ω: Option<f32>,
+}
+
+impl BackboneTorsion {
+    pub fn new(
+        residue_index: usize,
+        chain_id: &str,
+        residue: &Residue,
+        previous: Option<&BackboneAtoms>,
+        current: &BackboneAtoms,
+        next: Option<&BackboneAtoms>,
+    ) -> Self {
+        let previous = previous.filter(|previous| previous.is_bonded_to(current));
+        let next = next.filter(|next| current.is_bonded_to(next));
+
+        let residue_name = residue.name().unwrap_or_default().to_string();
+
+        Self {
+            residue_index,
+            chain_id: chain_id.to_string(),
+            serial_number: residue.serial_number(),
+            kind: ResidueKind::from_residue_name(&residue_name),
+            residue_name,
+            ca_position: current.ca,
+            φ: previous.map(|previous| dihedral(previous.c, current.n, current.ca, current.c)),
+            ψ: next.map(|next| dihedral(current.n, current.ca, current.c, next.n)),
+            ω: next.map(|next| dihedral(current.ca, current.c, next.n, next.ca)),
+        }
+    }
+
+    /// `None` for chain termini, where either φ or ψ is undefined.
+    pub fn region(&self) -> Option<RamachandranRegion> {
+        Some(RamachandranRegion::classify(self.φ?, self.ψ?, self.kind))
+    }
+}
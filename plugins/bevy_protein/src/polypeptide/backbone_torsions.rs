@@ -0,0 +1,68 @@
+use pdbtbx::PDB;
+
+use crate::backbone_torsion::{BackboneAtoms, BackboneTorsion, RamachandranRegion};
+
+#[derive(Default, Debug, serde::Deserialize, serde::Serialize)]
+pub struct BackboneTorsions(pub Vec<BackboneTorsion>);
+
+impl From<Vec<BackboneTorsion>> for BackboneTorsions {
+    fn from(value: Vec<BackboneTorsion>) -> Self {
+        Self(value)
+    }
+}
+
+impl BackboneTorsions {
+    pub fn new(torsions: Vec<BackboneTorsion>) -> Self {
+        Self(torsions)
+    }
+
+    /// Computes φ/ψ/ω for every residue with a complete backbone. Residues are indexed in the
+    /// same order as [`PDB::residues`], so the indices can be used to address atoms of the same asset.
+    pub fn from_pdb(pdb: &PDB) -> Self {
+        let mut torsions = Vec::<BackboneTorsion>::new();
+        let mut residue_offset = 0;
+
+        for chain in pdb.chains() {
+            let residues: Vec<_> = chain.residues().collect();
+            let backbone: Vec<_> = residues
+                .iter()
+                .map(|residue| BackboneAtoms::from_residue(residue))
+                .collect();
+
+            for (i, residue) in residues.iter().enumerate() {
+                let Some(current) = &backbone[i] else {
+                    continue;
+                };
+                let previous = i.checked_sub(1).and_then(|j| backbone[j].as_ref());
+                let next = backbone.get(i + 1).and_then(Option::as_ref);
+
+                torsions.push(BackboneTorsion::new(
+                    residue_offset + i,
+                    chain.id(),
+                    residue,
+                    previous,
+                    current,
+                    next,
+                ));
+            }
+
+            residue_offset += residues.len();
+        }
+
+        Self(torsions)
+    }
+
+    pub fn get(&self, residue_index: usize) -> Option<&BackboneTorsion> {
+        self.0
+            .binary_search_by_key(&residue_index, |torsion| torsion.residue_index)
+            .ok()
+            .map(|i| &self.0[i])
+    }
+
+    pub fn count_in_region(&self, region: RamachandranRegion) -> usize {
+        self.0
+            .iter()
+            .filter(|torsion| torsion.region() == Some(region))
+            .count()
+    }
+}
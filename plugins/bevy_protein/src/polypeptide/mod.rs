@@ -1,2 +1,6 @@
+pub mod backbone_torsion;
+pub mod backbone_torsions;
 pub mod polypeptide_plane;
-pub mod polypeptide_planes;
\ No newline at end of file
+pub mod polypeptide_planes;
+pub mod secondary_structure;
+pub mod sequence;
\ No newline at end of file
@@ -1,4 +1,6 @@
+use bevy::log::info;
 use bevy_geometry::TangentSpace;
+use pdbtbx::PDB;
 
 use crate::polypeptide_plane::PolypeptidePlane;
 
@@ -15,6 +17,41 @@ impl PolypeptidePlanes {
     pub fn new(planes: Vec<PolypeptidePlane>) -> Self {
         Self(planes)
     }
+    /// Builds a plane from every run of three consecutive residues that has the required backbone atoms,
+    /// each paired with the index (in [`PDB::residues`]) of the first residue of the run.
+    pub fn indexed_from_pdb(pdb: &PDB) -> Vec<(usize, PolypeptidePlane)> {
+        let mut polypeptide_planes = Vec::<(usize, PolypeptidePlane)>::new();
+
+        let residues: Vec<_> = pdb.residues().collect();
+
+        for (i, residue_triptych) in residues.windows(3).enumerate() {
+            match *residue_triptych {
+                [r1, r2, r3] => {
+                    match PolypeptidePlane::try_from((r1.clone(), r2.clone(), r3.clone())) {
+                        Ok(polypeptide_plane) => {
+                            polypeptide_planes.push((i, polypeptide_plane));
+                        }
+                        Err(err) => {
+                            info!("{:?}", err)
+                        }
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        polypeptide_planes
+    }
+
+    pub fn from_pdb(pdb: &PDB) -> Self {
+        Self(
+            Self::indexed_from_pdb(pdb)
+                .into_iter()
+                .map(|(_, polypeptide_plane)| polypeptide_plane)
+                .collect(),
+        )
+    }
+
     pub fn discrete_tangent_spaces(&self) -> Vec<TangentSpace> {
         self.0.iter().map(|plane| plane.tangent_space).collect()
     }
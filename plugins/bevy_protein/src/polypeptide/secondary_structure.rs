@@ -0,0 +1,98 @@
+/**
+* Secondary structure assignment from backbone torsions.
+*
+* This is a φ/ψ-only approximation: runs of residues in the helical or extended basins are
+* marked as helices or strands. It needs no hydrogen bond analysis (unlike DSSP), so it is cheap
+* enough to run at load time, but it does not distinguish 3₁₀/π helices or pair β-strands into sheets.
+*/
+use std::collections::BTreeMap;
+
+use crate::backbone_torsion::BackboneTorsion;
+use crate::backbone_torsions::BackboneTorsions;
+
+// Shortest run of consecutive residues accepted as a helix / strand.
+const MIN_HELIX_LENGTH: usize = 4;
+const MIN_STRAND_LENGTH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum SecondaryStructure {
+    Helix,
+    Strand,
+    #[default]
+    Coil,
+}
+
+impl SecondaryStructure {
+    fn from_torsion(torsion: &BackboneTorsion) -> Self {
+        let (Some(φ), Some(ψ)) = (torsion.φ, torsion.ψ) else {
+            return Self::Coil;
+        };
+
+        if (-100.0..=-30.0).contains(&φ) && (-80.0..=-5.0).contains(&ψ) {
+            Self::Helix
+        } else if (-180.0..=-45.0).contains(&φ) && (ψ >= 90.0 || ψ <= -170.0) {
+            Self::Strand
+        } else {
+            Self::Coil
+        }
+    }
+
+    fn min_length(&self) -> usize {
+        match self {
+            Self::Helix => MIN_HELIX_LENGTH,
+            Self::Strand => MIN_STRAND_LENGTH,
+            Self::Coil => 1,
+        }
+    }
+
+    /// A single character used by sequence views.
+    pub fn glyph(&self) -> char {
+        match self {
+            Self::Helix => 'H',
+            Self::Strand => 'E',
+            Self::Coil => '-',
+        }
+    }
+}
+
+/// Secondary structure per residue, keyed by the residue's position in [`pdbtbx::PDB::residues`].
+/// Residues missing from the map are coil.
+#[derive(Default, Debug, serde::Deserialize, serde::Serialize)]
+pub struct SecondaryStructures(pub BTreeMap<usize, SecondaryStructure>);
+
+impl SecondaryStructures {
+    pub fn from_torsions(torsions: &BackboneTorsions) -> Self {
+        let mut assignment = BTreeMap::<usize, SecondaryStructure>::new();
+
+        let mut run: Vec<&BackboneTorsion> = vec![];
+        let mut run_kind = SecondaryStructure::Coil;
+
+        let mut flush = |run: &mut Vec<&BackboneTorsion>, kind: SecondaryStructure| {
+            if kind != SecondaryStructure::Coil && run.len() >= kind.min_length() {
+                assignment.extend(run.iter().map(|torsion| (torsion.residue_index, kind)));
+            }
+            run.clear();
+        };
+
+        for torsion in torsions.0.iter() {
+            let kind = SecondaryStructure::from_torsion(torsion);
+            let contiguous = run.last().is_some_and(|previous| {
+                previous.chain_id == torsion.chain_id
+                    && previous.residue_index + 1 == torsion.residue_index
+            });
+
+            if !(contiguous && kind == run_kind) {
+                flush(&mut run, run_kind);
+                run_kind = kind;
+            }
+            run.push(torsion);
+        }
+        flush(&mut run, run_kind);
+
+        Self(assignment)
+    }
+
+    pub fn get(&self, residue_index: usize) -> SecondaryStructure {
+        self.0.get(&residue_index).copied().unwrap_or_default()
+    }
+}
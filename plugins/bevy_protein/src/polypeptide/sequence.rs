@@ -0,0 +1,69 @@
+use pdbtbx::PDB;
+
+pub use crate::atom::residue::one_letter_code;
+
+use crate::secondary_structure::{SecondaryStructure, SecondaryStructures};
+
+#[derive(Debug, Clone)]
+pub struct SequenceResidue {
+    /// Position of the residue in [`PDB::residues`].
+    pub residue_index: usize,
+    pub serial_number: isize,
+    pub insertion_code: Option<String>,
+    pub one_letter_code: char,
+    pub secondary_structure: SecondaryStructure,
+    /// AlphaFold models store the per-residue confidence (pLDDT) in the B-factor column of every atom.
+    pub b_factor: f32,
+}
+
+/// The amino acid sequence of a single chain, as modelled (unobserved residues are absent).
+#[derive(Debug, Clone)]
+pub struct ChainSequence {
+    pub chain_id: String,
+    pub residues: Vec<SequenceResidue>,
+}
+
+impl ChainSequence {
+    pub fn from_pdb(pdb: &PDB, secondary_structures: &SecondaryStructures) -> Vec<Self> {
+        let mut sequences = Vec::<Self>::new();
+        let mut residue_offset = 0;
+
+        for chain in pdb.chains() {
+            let residues = chain
+                .residues()
+                .enumerate()
+                .filter_map(|(i, residue)| {
+                    let ca = residue.atoms().find(|atom| atom.name() == "CA")?;
+                    let residue_index = residue_offset + i;
+
+                    Some(SequenceResidue {
+                        residue_index,
+                        serial_number: residue.serial_number(),
+                        insertion_code: residue.insertion_code().map(str::to_string),
+                        one_letter_code: one_letter_code(residue.name().unwrap_or_default()),
+                        secondary_structure: secondary_structures.get(residue_index),
+                        b_factor: ca.b_factor() as f32,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            residue_offset += chain.residue_count();
+
+            if !residues.is_empty() {
+                sequences.push(Self {
+                    chain_id: chain.id().to_string(),
+                    residues,
+                });
+            }
+        }
+
+        sequences
+    }
+
+    pub fn to_one_letter_string(&self) -> String {
+        self.residues
+            .iter()
+            .map(|residue| residue.one_letter_code)
+            .collect()
+    }
+}
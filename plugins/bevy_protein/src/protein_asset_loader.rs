@@ -1,14 +1,15 @@
-
-
+use std::io::{BufReader, BufWriter};
 use std::str::{from_utf8, Utf8Error};
 
-use bevy::asset::saver::{AssetSaver};
-use bevy::asset::transformer::{AssetTransformer};
+use bevy::asset::processor::LoadAndSave;
+use bevy::asset::saver::{AssetSaver, SavedAsset};
+use bevy::asset::transformer::AssetTransformer;
 use bevy::asset::AsyncWriteExt;
 use bevy::utils::thiserror;
 use bevy::{
     asset::{
-        io::{Reader}, AssetLoader, AsyncReadExt, LoadContext,
+        io::{Reader, Writer},
+        AssetLoader, AsyncReadExt, LoadContext,
     },
     prelude::*,
     reflect::TypePath,
@@ -18,15 +19,87 @@ use bevy::{
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use pdbtbx::{open_mmcif_raw, PDBError, TransformationMatrix, PDB};
+use pdbtbx::{
+    open_mmcif_raw, open_pdb_raw, save_mmcif_raw, save_pdb_raw, Context, PDBError, StrictnessLevel,
+    TransformationMatrix, PDB,
+};
 
-use crate::polypeptide_plane::PolypeptidePlane;
+use crate::backbone_torsions::BackboneTorsions;
+use crate::bonds::covalent::{perceive_covalent_bonds, residue_template_bonds, CovalentBond};
 use crate::polypeptide_planes::PolypeptidePlanes;
+use crate::representation::{atom_element, atom_position};
+use crate::secondary_structure::SecondaryStructures;
 
 #[derive(Asset, TypePath, Debug, Deserialize)]
 pub struct ProteinAsset {
     pub pdb: PDB,
     pub polypeptide_planes: PolypeptidePlanes,
+    pub backbone_torsions: BackboneTorsions,
+    pub secondary_structures: SecondaryStructures,
+    /// Covalent bonds between atoms, indexed in [`PDB::atoms`] order.
+    pub bonds: Vec<CovalentBond>,
+    /// Where the centre of the structure was in the file's frame. The structure is moved to the origin on load;
+    /// add this to bring it back, e.g. to line it up with a density map.
+    pub origin: Vec3,
+}
+
+impl ProteinAsset {
+    pub fn perceive_bonds(pdb: &PDB) -> Vec<CovalentBond> {
+        let positions: Vec<Vec3> = pdb.atoms().map(atom_position).collect();
+        let elements: Vec<&str> = pdb.atoms().map(atom_element).collect();
+
+        // Standard residues are bonded by their templates; distances decide everything else, including
+        // the peptide and disulphide bonds between residues.
+        let (templated, mut bonds) = residue_template_bonds(pdb.residues());
+        bonds.extend(
+            perceive_covalent_bonds(&positions, &elements)
+                .into_iter()
+                .filter(|bond| {
+                    templated[bond.a].is_none() || templated[bond.a] != templated[bond.b]
+                }),
+        );
+        bonds.sort_unstable_by_key(|bond| (bond.a, bond.b));
+        bonds
+    }
+
+    /// Serialises the structure, including any edits made since it was loaded, moved back to where it was in the
+    /// file it was loaded from.
+    pub fn to_bytes(&self, format: ProteinFileFormat) -> Vec<u8> {
+        let mut pdb = self.pdb.clone();
+        pdb.apply_transformation(&TransformationMatrix::translation(
+            self.origin.x as f64,
+            self.origin.y as f64,
+            self.origin.z as f64,
+        ));
+
+        let mut bytes = Vec::new();
+        match format {
+            ProteinFileFormat::Mmcif => save_mmcif_raw(&pdb, BufWriter::new(&mut bytes)),
+            ProteinFileFormat::Pdb => {
+                save_pdb_raw(&pdb, BufWriter::new(&mut bytes), StrictnessLevel::Loose)
+            }
+        }
+        bytes
+    }
+}
+
+/// The text formats a [`ProteinAsset`] can be read from and written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ProteinFileFormat {
+    #[default]
+    Mmcif,
+    Pdb,
+}
+
+impl ProteinFileFormat {
+    /// mmCIF files open with a `data_` block header; anything else is treated as PDB.
+    pub fn detect(str: &str) -> Self {
+        if str.trim_start().starts_with("data_") {
+            Self::Mmcif
+        } else {
+            Self::Pdb
+        }
+    }
 }
 
 #[derive(Default)]
@@ -69,8 +142,15 @@ impl AssetLoader for ProteinAssetLoader {
 
             let str = from_utf8(&bytes)?;
 
-            let (mut pdb, _) = open_mmcif_raw(str, pdbtbx::StrictnessLevel::Loose)
-                .map_err(|error_log| ProteinAssetLoaderError::PdbError { error_log })?;
+            let (mut pdb, _) = match ProteinFileFormat::detect(str) {
+                ProteinFileFormat::Mmcif => open_mmcif_raw(str, StrictnessLevel::Loose),
+                ProteinFileFormat::Pdb => open_pdb_raw(
+                    BufReader::new(bytes.as_slice()),
+                    Context::none(),
+                    StrictnessLevel::Loose,
+                ),
+            }
+            .map_err(|error_log| ProteinAssetLoaderError::PdbError { error_log })?;
 
             let ((x1, y1, z1), (x2, y2, z2)) = pdb.bounding_box();
 
@@ -82,37 +162,77 @@ impl AssetLoader for ProteinAssetLoader {
                 -centre.0, -centre.1, -centre.2,
             ));
 
-            let mut polypeptide_planes = Vec::<PolypeptidePlane>::new();
-
-            let residues: Vec<_> = pdb.residues().collect();
-
-            let residue_triptych_iter = residues.windows(3);
-
-            for residue_triptych in residue_triptych_iter {
-                match *residue_triptych {
-                    [r1, r2, r3] => {
-                        match PolypeptidePlane::try_from((r1.clone(), r2.clone(), r3.clone())) {
-                            Ok(polypeptide_plane) => {
-                                // info!("{:?}", &polypeptide_plane);
-                                polypeptide_planes.push(polypeptide_plane);
-                            }
-                            Err(err) => {
-                                info!("{:?}", err)
-                            }
-                        }
-                    }
-                    _ => unreachable!(),
-                }
-            }
+            let polypeptide_planes = PolypeptidePlanes::from_pdb(&pdb);
+            let backbone_torsions = BackboneTorsions::from_pdb(&pdb);
+            let secondary_structures = SecondaryStructures::from_torsions(&backbone_torsions);
+            let bonds = ProteinAsset::perceive_bonds(&pdb);
 
             Ok(ProteinAsset {
                 pdb,
-                polypeptide_planes: PolypeptidePlanes::new(polypeptide_planes),
+                polypeptide_planes,
+                backbone_torsions,
+                secondary_structures,
+                bonds,
+                origin: Vec3::new(centre.0 as f32, centre.1 as f32, centre.2 as f32),
             })
         })
     }
 
     fn extensions(&self) -> &[&str] {
-        &["cif"]
+        &["cif", "pdb"]
     }
 }
+
+#[derive(Default)]
+pub struct ProteinAssetSaver;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ProteinAssetSaverError {
+    #[error("Could not save asset: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ProteinAssetSaverSettings {
+    pub format: ProteinFileFormat,
+}
+
+impl AssetSaver for ProteinAssetSaver {
+    type Asset = ProteinAsset;
+    type Settings = ProteinAssetSaverSettings;
+    type OutputLoader = ProteinAssetLoader;
+    type Error = ProteinAssetSaverError;
+
+    fn save<'a>(
+        &'a self,
+        writer: &'a mut Writer,
+        asset: SavedAsset<'a, Self::Asset>,
+        settings: &'a Self::Settings,
+    ) -> BoxedFuture<'a, Result<(), Self::Error>> {
+        Box::pin(async move {
+            writer.write_all(&asset.to_bytes(settings.format)).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Re-writes `.cif`/`.pdb` sources through pdbtbx, so the processed copy is normalised mmCIF or PDB, with the
+/// coordinates of the source.
+///
+/// Sources go through [`ProteinCacheProcessor`](crate::protein_cache::ProteinCacheProcessor) by default. To use this
+/// processor for one of them instead, name it in the `.meta` file next to the source:
+///
+/// ```ron
+/// (
+///     meta_format_version: "1.0",
+///     asset: Process(
+///         processor: "bevy_asset::processor::process::LoadAndSave<bevy_protein::protein_asset_loader::ProteinAssetLoader, bevy_protein::protein_asset_loader::ProteinAssetSaver>",
+///         settings: (
+///             loader_settings: (),
+///             saver_settings: (format: Pdb),
+///         ),
+///     ),
+/// )
+/// ```
+pub type ProteinAssetProcessor = LoadAndSave<ProteinAssetLoader, ProteinAssetSaver>;
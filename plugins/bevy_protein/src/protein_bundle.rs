@@ -0,0 +1,335 @@
+/**
+* Proteins are displayed by spawning a [`ProteinBundle`]. The geometry under it is (re)built whenever
+* its asset finishes loading or is hot-reloaded, or its representations or colouring change, so the same
+* asset can be shown several times with different styles.
+*/
+use bevy::{
+    prelude::*,
+    render::view::VisibilityBundle,
+    utils::{HashMap, HashSet},
+};
+
+use pdbtbx::Residue;
+
+use crate::atom::{element::cpk_color, residue::residue_data};
+use crate::bonds::covalent::bonds_between;
+use crate::molecule_kind::{MoleculeKind, MoleculeKindVisibility};
+use crate::protein_asset_loader::ProteinAsset;
+use crate::representation::{
+    atom_element, ball_and_stick::spawn_ball_and_stick, cartoon::spawn_cartoon,
+    spheres::spawn_spheres, ResidueAtom,
+};
+use crate::selection::ResidueSelection;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Representation {
+    /// Instanced spheres, `scale` times the van der Waals radius of each atom (1.0 is space-filling).
+    Spheres {
+        scale: f32,
+    },
+    BallAndStick,
+    /// A ribbon along the backbone. Only drawn for the polymer.
+    Cartoon,
+}
+
+/// How each kind of molecule is drawn. A kind can have several representations, or none.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct ProteinRepresentations {
+    pub polymer: Vec<Representation>,
+    pub ligand: Vec<Representation>,
+    pub water: Vec<Representation>,
+    pub ion: Vec<Representation>,
+    pub carbohydrate: Vec<Representation>,
+}
+
+impl Default for ProteinRepresentations {
+    fn default() -> Self {
+        Self {
+            polymer: vec![
+                Representation::Spheres { scale: 0.3 },
+                Representation::Cartoon,
+            ],
+            ligand: vec![Representation::BallAndStick],
+            water: vec![Representation::Spheres { scale: 0.15 }],
+            ion: vec![Representation::Spheres { scale: 0.2 }],
+            carbohydrate: vec![Representation::BallAndStick],
+        }
+    }
+}
+
+impl ProteinRepresentations {
+    pub fn get(&self, kind: MoleculeKind) -> &[Representation] {
+        match kind {
+            MoleculeKind::Polymer => &self.polymer,
+            MoleculeKind::Ligand => &self.ligand,
+            MoleculeKind::Water => &self.water,
+            MoleculeKind::Ion => &self.ion,
+            MoleculeKind::Carbohydrate => &self.carbohydrate,
+        }
+    }
+
+    pub fn set(&mut self, kind: MoleculeKind, representations: Vec<Representation>) {
+        match kind {
+            MoleculeKind::Polymer => self.polymer = representations,
+            MoleculeKind::Ligand => self.ligand = representations,
+            MoleculeKind::Water => self.water = representations,
+            MoleculeKind::Ion => self.ion = representations,
+            MoleculeKind::Carbohydrate => self.carbohydrate = representations,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ProteinColouring {
+    /// A fixed colour per [`MoleculeKind`].
+    #[default]
+    ByMoleculeKind,
+    Uniform(Color),
+    /// Blue (0) to red (100) by B-factor. In predicted models the B-factor column holds the pLDDT.
+    ByBFactor,
+    /// CPK colours.
+    ByElement,
+    /// Blue (hydrophilic) to orange (hydrophobic) on the Kyte-Doolittle scale; non-standard residues are white.
+    ByHydrophobicity,
+    /// Red for acidic residues, blue for basic ones, white otherwise.
+    ByCharge,
+}
+
+impl ProteinColouring {
+    pub fn atom_color(&self, kind: MoleculeKind, residue: &Residue, atom: &pdbtbx::Atom) -> Color {
+        let residue_data = || residue.name().and_then(residue_data);
+
+        match self {
+            Self::ByMoleculeKind => match kind {
+                MoleculeKind::Polymer => Color::BLUE,
+                MoleculeKind::Ligand => Color::GREEN,
+                MoleculeKind::Water => Color::RED,
+                MoleculeKind::Ion => Color::PURPLE,
+                MoleculeKind::Carbohydrate => Color::ORANGE,
+            },
+            Self::Uniform(color) => *color,
+            Self::ByBFactor => {
+                let t = (atom.b_factor() as f32 / 100.).clamp(0., 1.);
+                Color::rgb(t, 0.2, 1. - t)
+            }
+            Self::ByElement => cpk_color(atom_element(atom)),
+            Self::ByHydrophobicity => match residue_data() {
+                Some(residue) => {
+                    // Kyte-Doolittle runs from -4.5 (arginine) to 4.5 (isoleucine).
+                    let t = ((residue.hydrophobicity + 4.5) / 9.).clamp(0., 1.);
+                    Color::rgb(0.2 + 0.8 * t, 0.4 + 0.2 * t, 1. - 0.8 * t)
+                }
+                None => Color::WHITE,
+            },
+            Self::ByCharge => match residue_data().map_or(0, |residue| residue.charge) {
+                charge if charge < 0 => Color::RED,
+                charge if charge > 0 => Color::BLUE,
+                _ => Color::WHITE,
+            },
+        }
+    }
+
+    pub fn cartoon_color(&self) -> Color {
+        match self {
+            Self::Uniform(color) => *color,
+            _ => Color::RED,
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct ProteinBundle {
+    pub handle: Handle<ProteinAsset>,
+    pub representations: ProteinRepresentations,
+    pub colouring: ProteinColouring,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: VisibilityBundle,
+}
+
+impl ProteinBundle {
+    pub fn new(handle: Handle<ProteinAsset>) -> Self {
+        Self {
+            handle,
+            ..default()
+        }
+    }
+}
+
+type ChangedProteins = Or<(
+    Changed<Handle<ProteinAsset>>,
+    Changed<ProteinRepresentations>,
+    Changed<ProteinColouring>,
+)>;
+
+/// Builds the geometry of every [`ProteinBundle`] whose asset was (re)loaded or whose components changed,
+/// replacing whatever was built before, and despawns the bundles of removed assets.
+#[allow(clippy::too_many_arguments)]
+pub fn build_proteins(
+    mut commands: Commands,
+    mut ev_protein_asset: EventReader<AssetEvent<ProteinAsset>>,
+    protein_assets: Res<Assets<ProteinAsset>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    molecule_kind_visibility: Res<MoleculeKindVisibility>,
+    mut selection: ResMut<ResidueSelection>,
+    proteins: Query<(
+        Entity,
+        &Handle<ProteinAsset>,
+        &ProteinRepresentations,
+        &ProteinColouring,
+    )>,
+    changed_proteins: Query<Entity, (With<ProteinRepresentations>, ChangedProteins)>,
+) {
+    let mut stale: HashSet<Entity> = changed_proteins.iter().collect();
+
+    for ev in ev_protein_asset.read() {
+        let with_asset = |id: AssetId<ProteinAsset>| {
+            proteins
+                .iter()
+                .filter(move |(_, handle, ..)| handle.id() == id)
+                .map(|(entity, ..)| entity)
+        };
+
+        match ev {
+            AssetEvent::Added { id } => {
+                info!("protein asset loaded: {:?}", id);
+                stale.extend(with_asset(*id));
+            }
+            AssetEvent::Modified { id } => {
+                info!("protein asset reloaded: {:?}", id);
+                stale.extend(with_asset(*id));
+
+                if let Some(protein_asset) = protein_assets.get(*id) {
+                    selection
+                        .get_mut(*id)
+                        .retain_residues(protein_asset.pdb.residues().count());
+                }
+            }
+            AssetEvent::Removed { id } => {
+                info!("protein asset removed: {:?}", id);
+                selection.remove(*id);
+                for entity in with_asset(*id) {
+                    stale.remove(&entity);
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut rebuilt = false;
+    for entity in stale {
+        let Ok((entity, handle, representations, colouring)) = proteins.get(entity) else {
+            continue;
+        };
+        // Not loaded yet: it will be built on `AssetEvent::Added`.
+        let Some(protein_asset) = protein_assets.get(handle) else {
+            continue;
+        };
+
+        // Rebuild under the existing entity, so its transform (and anything else attached to it) survives.
+        commands.entity(entity).despawn_descendants();
+        spawn_molecule_kinds(
+            &mut commands,
+            entity,
+            handle.id(),
+            &mut meshes,
+            &mut materials,
+            &molecule_kind_visibility,
+            protein_asset,
+            representations,
+            colouring,
+        );
+        rebuilt = true;
+    }
+
+    // The new instances start with their base colours: re-apply the selection to them.
+    if rebuilt {
+        selection.set_changed();
+    }
+}
+
+/// Spawns one child of `entity` per [`MoleculeKind`] present in the structure, drawn with the requested representations.
+#[allow(clippy::too_many_arguments)]
+fn spawn_molecule_kinds(
+    commands: &mut Commands,
+    entity: Entity,
+    protein: AssetId<ProteinAsset>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    molecule_kind_visibility: &MoleculeKindVisibility,
+    protein_asset: &ProteinAsset,
+    representations: &ProteinRepresentations,
+    colouring: &ProteinColouring,
+) {
+    let residues: Vec<&Residue> = protein_asset.pdb.residues().collect();
+    let mut atoms_by_kind = HashMap::<MoleculeKind, Vec<ResidueAtom>>::default();
+    // The indices of the same atoms in `PDB::atoms`, which the bonds of the asset refer to.
+    let mut atom_indices_by_kind = HashMap::<MoleculeKind, Vec<usize>>::default();
+    let mut atom_count = 0;
+    for (residue_index, residue) in residues.iter().enumerate() {
+        let kind = MoleculeKind::classify(residue);
+        let first_atom = atom_count;
+        atoms_by_kind
+            .entry(kind)
+            .or_default()
+            .extend(residue.atoms().map(|atom| (residue_index, atom)));
+        atom_count += residue.atoms().count();
+        atom_indices_by_kind
+            .entry(kind)
+            .or_default()
+            .extend(first_atom..atom_count);
+    }
+
+    commands.entity(entity).with_children(|parent| {
+        for kind in MoleculeKind::ALL {
+            let Some(atoms) = atoms_by_kind.get(&kind) else {
+                continue;
+            };
+            let color = |(residue_index, atom): &ResidueAtom| {
+                colouring.atom_color(kind, residues[*residue_index], atom)
+            };
+
+            parent
+                .spawn((
+                    SpatialBundle {
+                        visibility: molecule_kind_visibility.visibility(kind),
+                        ..default()
+                    },
+                    kind,
+                    Name::new(kind.label()),
+                ))
+                .with_children(|parent| {
+                    for representation in representations.get(kind) {
+                        match *representation {
+                            Representation::Spheres { scale } => {
+                                spawn_spheres(
+                                    parent, protein, meshes, materials, atoms, scale, color,
+                                );
+                            }
+                            Representation::BallAndStick => {
+                                let bonds = bonds_between(
+                                    &protein_asset.bonds,
+                                    &atom_indices_by_kind[&kind],
+                                );
+                                spawn_ball_and_stick(
+                                    parent, protein, meshes, materials, atoms, &bonds, color,
+                                );
+                            }
+                            Representation::Cartoon if kind == MoleculeKind::Polymer => {
+                                spawn_cartoon(
+                                    parent,
+                                    meshes,
+                                    materials,
+                                    &protein_asset.polypeptide_planes,
+                                    colouring.cartoon_color(),
+                                );
+                            }
+                            Representation::Cartoon => {}
+                        }
+                    }
+                });
+        }
+    });
+}
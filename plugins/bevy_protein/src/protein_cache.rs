@@ -0,0 +1,821 @@
+/**
+* A compact binary cache of a [`ProteinAsset`], produced by asset processing so that large structures
+* are not re-parsed (and their bonds, secondary structure and ribbon frames not recomputed) on every launch.
+*
+* Layout (little-endian): the magic `PRTC`, a `u32` format version, the structure's origin, then the model, chain, residue,
+* conformer and atom tables, bonds, secondary structure and polypeptide planes. Hierarchy tables store the
+* exclusive end of their children's range in the next table, so the ranges are implicit.
+*/
+use std::str::{from_utf8, Utf8Error};
+
+use bevy::asset::processor::LoadTransformAndSave;
+use bevy::asset::saver::{AssetSaver, SavedAsset};
+use bevy::asset::transformer::{AssetTransformer, TransformedAsset};
+use bevy::asset::AsyncWriteExt;
+use bevy::utils::thiserror;
+use bevy::{
+    asset::{
+        io::{Reader, Writer},
+        AssetLoader, AsyncReadExt, LoadContext,
+    },
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use bevy_geometry::TangentSpace;
+use pdbtbx::{Atom, Chain, Conformer, Model, Residue, PDB};
+use thiserror::Error;
+
+use crate::backbone_torsions::BackboneTorsions;
+use crate::bonds::covalent::CovalentBond;
+use crate::polypeptide_plane::PolypeptidePlane;
+use crate::polypeptide_planes::PolypeptidePlanes;
+use crate::protein_asset_loader::{ProteinAsset, ProteinAssetLoader};
+use crate::representation::{atom_element, atom_position};
+use crate::secondary_structure::{SecondaryStructure, SecondaryStructures};
+
+pub const PROTEIN_CACHE_MAGIC: &[u8; 4] = b"PRTC";
+/// Bumped whenever the layout changes; caches written with another version are rejected.
+pub const PROTEIN_CACHE_VERSION: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct CachedModel {
+    pub serial_number: usize,
+    pub chain_end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedChain {
+    pub id: String,
+    pub residue_end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedResidue {
+    pub serial_number: isize,
+    pub insertion_code: Option<String>,
+    pub conformer_end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedConformer {
+    pub name: String,
+    pub alternative_location: Option<String>,
+    pub atom_end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedAtom {
+    pub serial_number: usize,
+    pub name: String,
+    pub element: String,
+    pub hetero: bool,
+    pub occupancy: f32,
+    pub b_factor: f32,
+    pub charge: isize,
+    pub position: Vec3,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CachedPolypeptidePlane {
+    /// Index (in [`PDB::residues`]) of the first of the plane's three residues.
+    pub residue_index: usize,
+    pub tangent_space: TangentSpace,
+    pub width: f32,
+}
+
+/// The flattened contents of a [`ProteinAsset`], in the order they are written to the cache.
+#[derive(Asset, TypePath, Debug, Clone, Default)]
+pub struct ProteinCache {
+    pub origin: Vec3,
+    pub models: Vec<CachedModel>,
+    pub chains: Vec<CachedChain>,
+    pub residues: Vec<CachedResidue>,
+    pub conformers: Vec<CachedConformer>,
+    pub atoms: Vec<CachedAtom>,
+    pub bonds: Vec<CovalentBond>,
+    pub secondary_structures: Vec<(usize, SecondaryStructure)>,
+    pub polypeptide_planes: Vec<CachedPolypeptidePlane>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ProteinCacheError {
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("not a protein cache (bad magic)")]
+    InvalidMagic,
+    #[error("protein cache version {0} is not supported (expected {PROTEIN_CACHE_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("protein cache is truncated")]
+    UnexpectedEof,
+    #[error("protein cache has an invalid {0}")]
+    Invalid(&'static str),
+    #[error(transparent)]
+    Utf8Error(#[from] Utf8Error),
+}
+
+impl ProteinCache {
+    pub fn from_asset(protein_asset: &ProteinAsset) -> Self {
+        let pdb = &protein_asset.pdb;
+        let mut cache = Self {
+            origin: protein_asset.origin,
+            ..default()
+        };
+
+        for model in pdb.models() {
+            for chain in model.chains() {
+                for residue in chain.residues() {
+                    for conformer in residue.conformers() {
+                        for atom in conformer.atoms() {
+                            cache.atoms.push(CachedAtom {
+                                serial_number: atom.serial_number(),
+                                name: atom.name().to_string(),
+                                element: atom_element(atom).to_string(),
+                                hetero: atom.hetero(),
+                                occupancy: atom.occupancy() as f32,
+                                b_factor: atom.b_factor() as f32,
+                                charge: atom.charge(),
+                                position: atom_position(atom),
+                            });
+                        }
+                        cache.conformers.push(CachedConformer {
+                            name: conformer.name().to_string(),
+                            alternative_location: conformer
+                                .alternative_location()
+                                .map(str::to_string),
+                            atom_end: cache.atoms.len(),
+                        });
+                    }
+                    cache.residues.push(CachedResidue {
+                        serial_number: residue.serial_number(),
+                        insertion_code: residue.insertion_code().map(str::to_string),
+                        conformer_end: cache.conformers.len(),
+                    });
+                }
+                cache.chains.push(CachedChain {
+                    id: chain.id().to_string(),
+                    residue_end: cache.residues.len(),
+                });
+            }
+            cache.models.push(CachedModel {
+                serial_number: model.serial_number(),
+                chain_end: cache.chains.len(),
+            });
+        }
+
+        cache.bonds = protein_asset.bonds.clone();
+        cache.secondary_structures = protein_asset
+            .secondary_structures
+            .0
+            .iter()
+            .map(|(residue_index, secondary_structure)| (*residue_index, *secondary_structure))
+            .collect();
+        // The asset's planes hold copies of their residues rather than indices, so they are rebuilt
+        // here with the index of their first residue.
+        cache.polypeptide_planes = PolypeptidePlanes::indexed_from_pdb(pdb)
+            .into_iter()
+            .map(|(residue_index, plane)| CachedPolypeptidePlane {
+                residue_index,
+                tangent_space: plane.tangent_space,
+                width: plane.width,
+            })
+            .collect();
+
+        cache
+    }
+
+    /// Rebuilds the structure and its derived data. Backbone torsions are cheap and recomputed
+    /// rather than stored.
+    pub fn into_asset(self) -> Result<ProteinAsset, ProteinCacheError> {
+        let mut pdb = PDB::new();
+
+        let (mut chain_start, mut residue_start, mut conformer_start, mut atom_start) =
+            (0, 0, 0, 0);
+        for cached_model in &self.models {
+            let mut model = Model::new(cached_model.serial_number);
+            for cached_chain in self
+                .chains
+                .get(chain_start..cached_model.chain_end)
+                .ok_or(ProteinCacheError::Invalid("chain range"))?
+            {
+                let mut chain = Chain::new(cached_chain.id.as_str())
+                    .ok_or(ProteinCacheError::Invalid("chain id"))?;
+                for cached_residue in self
+                    .residues
+                    .get(residue_start..cached_chain.residue_end)
+                    .ok_or(ProteinCacheError::Invalid("residue range"))?
+                {
+                    let mut residue = Residue::new(
+                        cached_residue.serial_number,
+                        cached_residue.insertion_code.as_deref(),
+                        None,
+                    )
+                    .ok_or(ProteinCacheError::Invalid("residue"))?;
+                    for cached_conformer in self
+                        .conformers
+                        .get(conformer_start..cached_residue.conformer_end)
+                        .ok_or(ProteinCacheError::Invalid("conformer range"))?
+                    {
+                        let mut conformer = Conformer::new(
+                            cached_conformer.name.as_str(),
+                            cached_conformer.alternative_location.as_deref(),
+                            None,
+                        )
+                        .ok_or(ProteinCacheError::Invalid("conformer"))?;
+                        for cached_atom in self
+                            .atoms
+                            .get(atom_start..cached_conformer.atom_end)
+                            .ok_or(ProteinCacheError::Invalid("atom range"))?
+                        {
+                            let Vec3 { x, y, z } = cached_atom.position;
+                            conformer.add_atom(
+                                Atom::new(
+                                    cached_atom.hetero,
+                                    cached_atom.serial_number,
+                                    cached_atom.name.as_str(),
+                                    x as f64,
+                                    y as f64,
+                                    z as f64,
+                                    cached_atom.occupancy as f64,
+                                    cached_atom.b_factor as f64,
+                                    cached_atom.element.as_str(),
+                                    cached_atom.charge,
+                                )
+                                .ok_or(ProteinCacheError::Invalid("atom"))?,
+                            );
+                        }
+                        atom_start = cached_conformer.atom_end;
+                        residue.add_conformer(conformer);
+                    }
+                    conformer_start = cached_residue.conformer_end;
+                    chain.add_residue(residue);
+                }
+                residue_start = cached_chain.residue_end;
+                model.add_chain(chain);
+            }
+            chain_start = cached_model.chain_end;
+            pdb.add_model(model);
+        }
+
+        let residues: Vec<_> = pdb.residues().collect();
+        let mut polypeptide_planes =
+            Vec::<PolypeptidePlane>::with_capacity(self.polypeptide_planes.len());
+        for cached_plane in &self.polypeptide_planes {
+            let i = cached_plane.residue_index;
+            let [r1, r2, r3] = residues
+                .get(i..i + 3)
+                .ok_or(ProteinCacheError::Invalid("polypeptide plane"))?
+            else {
+                unreachable!()
+            };
+            polypeptide_planes.push(PolypeptidePlane {
+                r1: (*r1).clone(),
+                r2: (*r2).clone(),
+                r3: (*r3).clone(),
+                tangent_space: cached_plane.tangent_space,
+                width: cached_plane.width,
+            });
+        }
+
+        let backbone_torsions = BackboneTorsions::from_pdb(&pdb);
+
+        Ok(ProteinAsset {
+            polypeptide_planes: PolypeptidePlanes::new(polypeptide_planes),
+            backbone_torsions,
+            secondary_structures: SecondaryStructures(
+                self.secondary_structures.into_iter().collect(),
+            ),
+            bonds: self.bonds,
+            origin: self.origin,
+            pdb,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = CacheWriter::default();
+        writer.bytes.extend_from_slice(PROTEIN_CACHE_MAGIC);
+        writer.u32(PROTEIN_CACHE_VERSION);
+        writer.vec3(self.origin);
+
+        writer.len(self.models.len());
+        for model in &self.models {
+            writer.len(model.serial_number);
+            writer.len(model.chain_end);
+        }
+        writer.len(self.chains.len());
+        for chain in &self.chains {
+            writer.str(&chain.id);
+            writer.len(chain.residue_end);
+        }
+        writer.len(self.residues.len());
+        for residue in &self.residues {
+            writer.i32(residue.serial_number as i32);
+            writer.optional_str(residue.insertion_code.as_deref());
+            writer.len(residue.conformer_end);
+        }
+        writer.len(self.conformers.len());
+        for conformer in &self.conformers {
+            writer.str(&conformer.name);
+            writer.optional_str(conformer.alternative_location.as_deref());
+            writer.len(conformer.atom_end);
+        }
+        writer.len(self.atoms.len());
+        for atom in &self.atoms {
+            writer.len(atom.serial_number);
+            writer.str(&atom.name);
+            writer.str(&atom.element);
+            writer.bytes.push(atom.hetero as u8);
+            writer.f32(atom.occupancy);
+            writer.f32(atom.b_factor);
+            writer.i32(atom.charge as i32);
+            writer.vec3(atom.position);
+        }
+        writer.len(self.bonds.len());
+        for bond in &self.bonds {
+            writer.len(bond.a);
+            writer.len(bond.b);
+        }
+        writer.len(self.secondary_structures.len());
+        for (residue_index, secondary_structure) in &self.secondary_structures {
+            writer.len(*residue_index);
+            writer.bytes.push(match secondary_structure {
+                SecondaryStructure::Helix => 0,
+                SecondaryStructure::Strand => 1,
+                SecondaryStructure::Coil => 2,
+            });
+        }
+        writer.len(self.polypeptide_planes.len());
+        for plane in &self.polypeptide_planes {
+            writer.len(plane.residue_index);
+            writer.vec3(plane.tangent_space.position);
+            writer.vec3(plane.tangent_space.normal);
+            writer.vec3(plane.tangent_space.tangent);
+            writer.vec3(plane.tangent_space.binormal);
+            writer.f32(plane.width);
+        }
+
+        writer.bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProteinCacheError> {
+        let mut reader = CacheReader { bytes };
+
+        if reader.take(PROTEIN_CACHE_MAGIC.len())? != PROTEIN_CACHE_MAGIC {
+            return Err(ProteinCacheError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if version != PROTEIN_CACHE_VERSION {
+            return Err(ProteinCacheError::UnsupportedVersion(version));
+        }
+
+        let mut cache = Self {
+            origin: reader.vec3()?,
+            ..default()
+        };
+        for _ in 0..reader.len()? {
+            cache.models.push(CachedModel {
+                serial_number: reader.len()?,
+                chain_end: reader.len()?,
+            });
+        }
+        for _ in 0..reader.len()? {
+            cache.chains.push(CachedChain {
+                id: reader.string()?,
+                residue_end: reader.len()?,
+            });
+        }
+        for _ in 0..reader.len()? {
+            cache.residues.push(CachedResidue {
+                serial_number: reader.i32()? as isize,
+                insertion_code: reader.optional_string()?,
+                conformer_end: reader.len()?,
+            });
+        }
+        for _ in 0..reader.len()? {
+            cache.conformers.push(CachedConformer {
+                name: reader.string()?,
+                alternative_location: reader.optional_string()?,
+                atom_end: reader.len()?,
+            });
+        }
+        for _ in 0..reader.len()? {
+            cache.atoms.push(CachedAtom {
+                serial_number: reader.len()?,
+                name: reader.string()?,
+                element: reader.string()?,
+                hetero: reader.take(1)?[0] != 0,
+                occupancy: reader.f32()?,
+                b_factor: reader.f32()?,
+                charge: reader.i32()? as isize,
+                position: reader.vec3()?,
+            });
+        }
+        for _ in 0..reader.len()? {
+            cache.bonds.push(CovalentBond {
+                a: reader.len()?,
+                b: reader.len()?,
+            });
+        }
+        for _ in 0..reader.len()? {
+            let residue_index = reader.len()?;
+            let secondary_structure = match reader.take(1)?[0] {
+                0 => SecondaryStructure::Helix,
+                1 => SecondaryStructure::Strand,
+                2 => SecondaryStructure::Coil,
+                _ => return Err(ProteinCacheError::Invalid("secondary structure")),
+            };
+            cache
+                .secondary_structures
+                .push((residue_index, secondary_structure));
+        }
+        for _ in 0..reader.len()? {
+            cache.polypeptide_planes.push(CachedPolypeptidePlane {
+                residue_index: reader.len()?,
+                tangent_space: TangentSpace::new(
+                    reader.vec3()?,
+                    reader.vec3()?,
+                    reader.vec3()?,
+                    reader.vec3()?,
+                ),
+                width: reader.f32()?,
+            });
+        }
+
+        Ok(cache)
+    }
+}
+
+#[derive(Default)]
+struct CacheWriter {
+    bytes: Vec<u8>,
+}
+
+impl CacheWriter {
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Counts, indices and serial numbers are stored as `u32`.
+    fn len(&mut self, value: usize) {
+        self.u32(value as u32);
+    }
+
+    fn vec3(&mut self, value: Vec3) {
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.z);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// `None` is written as `u32::MAX` in place of the length.
+    fn optional_str(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => self.str(value),
+            None => self.u32(u32::MAX),
+        }
+    }
+}
+
+struct CacheReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> CacheReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProteinCacheError> {
+        if self.bytes.len() < n {
+            return Err(ProteinCacheError::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array(&mut self) -> Result<[u8; 4], ProteinCacheError> {
+        let mut array = [0; 4];
+        array.copy_from_slice(self.take(4)?);
+        Ok(array)
+    }
+
+    fn u32(&mut self) -> Result<u32, ProteinCacheError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, ProteinCacheError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, ProteinCacheError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, ProteinCacheError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, ProteinCacheError> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn string(&mut self) -> Result<String, ProteinCacheError> {
+        let len = self.len()?;
+        Ok(from_utf8(self.take(len)?)?.to_string())
+    }
+
+    fn optional_string(&mut self) -> Result<Option<String>, ProteinCacheError> {
+        match self.u32()? {
+            u32::MAX => Ok(None),
+            len => Ok(Some(from_utf8(self.take(len as usize)?)?.to_string())),
+        }
+    }
+}
+
+/// Flattens a freshly loaded [`ProteinAsset`] into a [`ProteinCache`].
+#[derive(Default)]
+pub struct ProteinCacheTransformer;
+
+impl AssetTransformer for ProteinCacheTransformer {
+    type AssetInput = ProteinAsset;
+    type AssetOutput = ProteinCache;
+    type Settings = ();
+    type Error = ProteinCacheError;
+
+    fn transform<'a>(
+        &'a self,
+        asset: TransformedAsset<Self::AssetInput>,
+        _settings: &'a Self::Settings,
+    ) -> BoxedFuture<'a, Result<TransformedAsset<Self::AssetOutput>, Self::Error>> {
+        Box::pin(async move {
+            let cache = ProteinCache::from_asset(asset.get());
+            Ok(asset.replace_asset(cache))
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct ProteinCacheSaver;
+
+impl AssetSaver for ProteinCacheSaver {
+    type Asset = ProteinCache;
+    type Settings = ();
+    type OutputLoader = ProteinCacheLoader;
+    type Error = ProteinCacheError;
+
+    fn save<'a>(
+        &'a self,
+        writer: &'a mut Writer,
+        asset: SavedAsset<'a, Self::Asset>,
+        _settings: &'a Self::Settings,
+    ) -> BoxedFuture<'a, Result<(), Self::Error>> {
+        Box::pin(async move {
+            writer.write_all(&asset.to_bytes()).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Loads a [`ProteinAsset`] from a cache written by [`ProteinCacheSaver`].
+#[derive(Default)]
+pub struct ProteinCacheLoader;
+
+impl AssetLoader for ProteinCacheLoader {
+    type Asset = ProteinAsset;
+    type Settings = ();
+    type Error = ProteinCacheError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+
+            ProteinCache::from_bytes(&bytes)?.into_asset()
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["protein"]
+    }
+}
+
+/// Converts `.cif`/`.pdb` sources into the binary cache when asset processing is enabled.
+pub type ProteinCacheProcessor =
+    LoadTransformAndSave<ProteinAssetLoader, ProteinCacheTransformer, ProteinCacheSaver>;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Two chains: four residues with backbones that make polypeptide planes, and a water with an alternate location.
+    fn test_pdb() -> PDB {
+        let backbone = [("N", "N"), ("CA", "C"), ("C", "C"), ("O", "O")];
+        let mut serial_number = 0;
+        let mut atom = |name: &str, element: &str, position: Vec3| {
+            serial_number += 1;
+            Atom::new(
+                false,
+                serial_number,
+                name,
+                position.x as f64,
+                position.y as f64,
+                position.z as f64,
+                1.,
+                20.,
+                element,
+                0,
+            )
+            .unwrap()
+        };
+
+        let mut model = Model::new(1);
+        let mut chain_a = Chain::new("A").unwrap();
+        for i in 0..4 {
+            let mut conformer = Conformer::new("ALA", None, None).unwrap();
+            let start = Vec3::new(3.8 * i as f32, (i % 2) as f32, 0.5 * i as f32);
+            for (j, (name, element)) in backbone.iter().enumerate() {
+                let offset = Vec3::new(0.6 * j as f32, 0.9 * (j % 2) as f32, 0.4 * j as f32);
+                conformer.add_atom(atom(name, element, start + offset));
+            }
+            let insertion_code = (i == 2).then_some("A");
+            let mut residue = Residue::new(10 + i as isize, insertion_code, None).unwrap();
+            residue.add_conformer(conformer);
+            chain_a.add_residue(residue);
+        }
+        let mut chain_b = Chain::new("B").unwrap();
+        let mut water = Conformer::new("HOH", Some("B"), None).unwrap();
+        water.add_atom(atom("O", "O", Vec3::new(-5., 2., 1.)));
+        let mut residue = Residue::new(101, None, None).unwrap();
+        residue.add_conformer(water);
+        chain_b.add_residue(residue);
+
+        model.add_chain(chain_a);
+        model.add_chain(chain_b);
+        let mut pdb = PDB::new();
+        pdb.add_model(model);
+        pdb
+    }
+
+    fn test_asset() -> ProteinAsset {
+        let pdb = test_pdb();
+        ProteinAsset {
+            polypeptide_planes: PolypeptidePlanes::from_pdb(&pdb),
+            backbone_torsions: BackboneTorsions::from_pdb(&pdb),
+            // Every kind, so that all of them go through the encoding.
+            secondary_structures: SecondaryStructures(BTreeMap::from([
+                (0, SecondaryStructure::Helix),
+                (1, SecondaryStructure::Strand),
+                (2, SecondaryStructure::Coil),
+            ])),
+            bonds: ProteinAsset::perceive_bonds(&pdb),
+            origin: Vec3::new(12.5, -3.25, 40.),
+            pdb,
+        }
+    }
+
+    fn test_bytes() -> Vec<u8> {
+        ProteinCache::from_asset(&test_asset()).to_bytes()
+    }
+
+    #[test]
+    fn round_trip_reproduces_the_asset() {
+        let asset = test_asset();
+        let restored = ProteinCache::from_bytes(&ProteinCache::from_asset(&asset).to_bytes())
+            .unwrap()
+            .into_asset()
+            .unwrap();
+
+        assert_eq!(restored.origin, asset.origin);
+        assert_eq!(
+            restored.pdb.atoms().map(atom_position).collect::<Vec<_>>(),
+            asset.pdb.atoms().map(atom_position).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            restored.pdb.atoms().map(atom_element).collect::<Vec<_>>(),
+            asset.pdb.atoms().map(atom_element).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            restored.pdb.atoms().map(Atom::name).collect::<Vec<_>>(),
+            asset.pdb.atoms().map(Atom::name).collect::<Vec<_>>()
+        );
+
+        let chains = |pdb: &PDB| {
+            pdb.chains()
+                .map(|chain| (chain.id().to_string(), chain.residue_count()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(chains(&restored.pdb), chains(&asset.pdb));
+
+        let residues = |pdb: &PDB| {
+            pdb.residues()
+                .map(|residue| {
+                    (
+                        residue.serial_number(),
+                        residue.insertion_code().map(str::to_string),
+                        residue.name().map(str::to_string),
+                        residue
+                            .conformers()
+                            .map(|conformer| conformer.alternative_location().map(str::to_string))
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(residues(&restored.pdb), residues(&asset.pdb));
+
+        assert!(!asset.bonds.is_empty());
+        assert_eq!(restored.bonds, asset.bonds);
+        assert_eq!(
+            restored.secondary_structures.0,
+            asset.secondary_structures.0
+        );
+
+        assert!(!asset.polypeptide_planes.0.is_empty());
+        assert_eq!(
+            restored.polypeptide_planes.0.len(),
+            asset.polypeptide_planes.0.len()
+        );
+        for (restored, plane) in restored
+            .polypeptide_planes
+            .0
+            .iter()
+            .zip(&asset.polypeptide_planes.0)
+        {
+            let serial_numbers = |plane: &PolypeptidePlane| {
+                [&plane.r1, &plane.r2, &plane.r3].map(Residue::serial_number)
+            };
+            assert_eq!(serial_numbers(restored), serial_numbers(plane));
+            assert_eq!(
+                restored.tangent_space.position,
+                plane.tangent_space.position
+            );
+            assert_eq!(restored.tangent_space.normal, plane.tangent_space.normal);
+            assert_eq!(restored.tangent_space.tangent, plane.tangent_space.tangent);
+            assert_eq!(
+                restored.tangent_space.binormal,
+                plane.tangent_space.binormal
+            );
+            assert_eq!(restored.width, plane.width);
+        }
+    }
+
+    #[test]
+    fn wrong_magic_is_rejected() {
+        let mut bytes = test_bytes();
+        bytes[0] = b'X';
+        assert!(matches!(
+            ProteinCache::from_bytes(&bytes),
+            Err(ProteinCacheError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn wrong_version_is_rejected() {
+        let mut bytes = test_bytes();
+        let version = PROTEIN_CACHE_VERSION + 1;
+        bytes[4..8].copy_from_slice(&version.to_le_bytes());
+        assert!(matches!(
+            ProteinCache::from_bytes(&bytes),
+            Err(ProteinCacheError::UnsupportedVersion(v)) if v == version
+        ));
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let bytes = test_bytes();
+        for len in 0..bytes.len() {
+            assert!(
+                ProteinCache::from_bytes(&bytes[..len]).is_err(),
+                "a cache cut to {len} of {} bytes was accepted",
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn out_of_range_hierarchy_is_an_error() {
+        let mut cache = ProteinCache::from_asset(&test_asset());
+        cache.chains[0].residue_end = cache.residues.len() + 1;
+        assert!(matches!(
+            cache.into_asset(),
+            Err(ProteinCacheError::Invalid("residue range"))
+        ));
+    }
+}
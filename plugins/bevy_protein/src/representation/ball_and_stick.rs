@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use bevy_instanced::instance_data::{
+    imposter::Imposter,
+    instanced::{Instance, InstancedMaterial, InstancesData},
+};
+
+use crate::bonds::covalent::CovalentBond;
+use crate::protein_asset_loader::ProteinAsset;
+
+use super::{
+    atom_position,
+    spheres::{instanced_material, spawn_spheres},
+    ResidueAtom,
+};
+
+// Atom spheres are this fraction of the van der Waals radius; sticks have a fixed radius (Å).
+const BALL_SCALE: f32 = 0.25;
+const STICK_RADIUS: f32 = 0.12;
+
+/// Spawns atoms as small spheres and the `bonds` between them, indexed in `atoms`, as instanced cylinder imposters.
+pub fn spawn_ball_and_stick(
+    parent: &mut ChildBuilder,
+    protein: AssetId<ProteinAsset>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    atoms: &[ResidueAtom],
+    bonds: &[CovalentBond],
+    color: impl Fn(&ResidueAtom) -> Color,
+) {
+    spawn_spheres(parent, protein, meshes, materials, atoms, BALL_SCALE, color);
+
+    let positions: Vec<Vec3> = atoms.iter().map(|(_, atom)| atom_position(atom)).collect();
+
+    let stick_color = Color::GRAY.as_rgba_f32();
+    let sticks = bonds
+        .iter()
+        .filter_map(|bond| {
+            let (a, b) = (positions[bond.a], positions[bond.b]);
+            let direction = b - a;
+            // Atoms on top of each other have no stick to draw.
+            let axis = direction.try_normalize()?;
+
+            // The cylinder has unit diameter and height along Y: stretch it to the bond and align it with the bond.
+            let transform = Transform {
+                translation: 0.5 * (a + b),
+                rotation: Quat::from_rotation_arc(Vec3::Y, axis),
+                scale: Vec3::new(2. * STICK_RADIUS, direction.length(), 2. * STICK_RADIUS),
+            };
+            Some(Instance::from_transform(transform, stick_color))
+        })
+        .collect();
+
+    parent.spawn((
+        meshes.add(Imposter::quad()),
+        Imposter::Cylinder,
+        SpatialBundle::INHERITED_IDENTITY,
+        InstancesData::new(sticks),
+        InstancedMaterial(materials.add(instanced_material())),
+    ));
+}
@@ -0,0 +1,44 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use bevy_geometry::primitives::ribbon::Ribbon;
+
+use crate::polypeptide_planes::PolypeptidePlanes;
+
+/// Spawns a ribbon swept along the polypeptide planes of the backbone.
+pub fn spawn_cartoon(
+    parent: &mut ChildBuilder,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    polypeptide_planes: &PolypeptidePlanes,
+    color: Color,
+) -> Entity {
+    let discrete_geodesic = polypeptide_planes.discrete_tangent_spaces();
+
+    let t_domain = Range {
+        start: 0,
+        end: discrete_geodesic.len(),
+    };
+
+    let ribbon = Ribbon::new(
+        &discrete_geodesic,
+        t_domain,
+        5.,
+        1.,
+        discrete_geodesic.len() as u32 * 10,
+    );
+
+    let ribbon_mesh_handle = meshes.add(ribbon);
+
+    // Render the mesh with the custom texture using a PbrBundle, add the marker.
+    parent
+        .spawn((PbrBundle {
+            mesh: ribbon_mesh_handle,
+            material: materials.add(StandardMaterial {
+                base_color: color,
+                ..default()
+            }),
+            ..default()
+        },))
+        .id()
+}
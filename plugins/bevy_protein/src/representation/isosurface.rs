@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use bevy_geometry::primitives::isosurface::Isosurface;
+
+use crate::density_map::DensityMap;
+use crate::protein_asset_loader::ProteinAsset;
+use crate::selection::{ResidueSelection, StructureSelection};
+
+use super::atom_position;
+
+/// Which part of a density map is contoured.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IsosurfaceRegion {
+    #[default]
+    Whole,
+    /// An axis-aligned box, in the frame of the fitted protein (or of the map when there is none).
+    Box { min: Vec3, max: Vec3 },
+    /// The bounding box of the selected residues of the fitted protein, grown by `padding` Ångströms.
+    /// Nothing is drawn while the selection is empty.
+    AroundSelection { padding: f32 },
+}
+
+impl IsosurfaceRegion {
+    /// The block of grid points of `density_map` to contour. `offset` moves the protein frame to the map frame, and
+    /// `selection_bounds` gives the bounds of the selected residues, in the protein frame.
+    fn grid_box(
+        &self,
+        density_map: &DensityMap,
+        offset: Vec3,
+        selection_bounds: impl FnOnce() -> Option<(Vec3, Vec3)>,
+    ) -> Option<(UVec3, UVec3)> {
+        match *self {
+            Self::Whole => density_map
+                .dimensions
+                .cmpgt(UVec3::ZERO)
+                .all()
+                .then(|| (UVec3::ZERO, density_map.dimensions - UVec3::ONE)),
+            Self::Box { min, max } => density_map.grid_box(min + offset, max + offset),
+            Self::AroundSelection { padding } => selection_bounds().and_then(|(min, max)| {
+                density_map.grid_box(min + offset - padding, max + offset + padding)
+            }),
+        }
+    }
+}
+
+/// Contours a [`DensityMap`] at a level given in multiples of the map's RMS deviation.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct DensityIsosurface {
+    pub sigma_level: f32,
+    pub region: IsosurfaceRegion,
+    /// The structure the map was fitted to. Proteins are centred on load, so the surface is moved by the
+    /// same amount to stay lined up with it.
+    pub protein: Option<Handle<ProteinAsset>>,
+    pub color: Color,
+}
+
+impl Default for DensityIsosurface {
+    fn default() -> Self {
+        Self {
+            sigma_level: 1.5,
+            region: IsosurfaceRegion::Whole,
+            protein: None,
+            color: Color::rgba(0.3, 0.5, 1.0, 0.5),
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct DensityMapBundle {
+    pub handle: Handle<DensityMap>,
+    pub isosurface: DensityIsosurface,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub visibility: VisibilityBundle,
+}
+
+/// Bounds (in the centred frame of the protein) of the atoms of the selected residues.
+fn selection_bounds(
+    protein_asset: &ProteinAsset,
+    selection: &StructureSelection,
+) -> Option<(Vec3, Vec3)> {
+    protein_asset
+        .pdb
+        .residues()
+        .enumerate()
+        .filter(|(residue_index, _)| selection.is_selected(*residue_index))
+        .flat_map(|(_, residue)| residue.atoms())
+        .map(atom_position)
+        .fold(None, |bounds, position| match bounds {
+            None => Some((position, position)),
+            Some((min, max)) => Some((min.min(position), max.max(position))),
+        })
+}
+
+/// Builds the isosurface mesh of every [`DensityMapBundle`] whose map or protein was (re)loaded, whose settings
+/// changed, or which follows a selection that changed.
+#[allow(clippy::too_many_arguments)]
+pub fn build_isosurfaces(
+    mut commands: Commands,
+    mut ev_density_map: EventReader<AssetEvent<DensityMap>>,
+    mut ev_protein_asset: EventReader<AssetEvent<ProteinAsset>>,
+    density_maps: Res<Assets<DensityMap>>,
+    protein_assets: Res<Assets<ProteinAsset>>,
+    selection: Res<ResidueSelection>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    isosurfaces: Query<(Entity, Ref<Handle<DensityMap>>, Ref<DensityIsosurface>)>,
+) {
+    let reloaded_maps: Vec<AssetId<DensityMap>> = ev_density_map
+        .read()
+        .filter_map(|ev| match ev {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let reloaded_proteins: Vec<AssetId<ProteinAsset>> = ev_protein_asset
+        .read()
+        .filter_map(|ev| match ev {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, handle, isosurface) in isosurfaces.iter() {
+        let follows_selection =
+            matches!(isosurface.region, IsosurfaceRegion::AroundSelection { .. });
+        let stale = handle.is_changed()
+            || isosurface.is_changed()
+            || reloaded_maps.contains(&handle.id())
+            || isosurface
+                .protein
+                .as_ref()
+                .is_some_and(|protein| reloaded_proteins.contains(&protein.id()))
+            || (follows_selection && selection.is_changed());
+        if !stale {
+            continue;
+        }
+
+        let Some(density_map) = density_maps.get(handle.id()) else {
+            continue;
+        };
+        let protein_asset = isosurface
+            .protein
+            .as_ref()
+            .and_then(|protein| protein_assets.get(protein));
+        // Moves the protein frame to the map frame.
+        let offset = protein_asset.map_or(Vec3::ZERO, |protein_asset| protein_asset.origin);
+
+        commands.entity(entity).despawn_descendants();
+
+        let bounds = isosurface.region.grid_box(density_map, offset, || {
+            let protein = isosurface.protein.as_ref()?;
+            selection_bounds(protein_asset?, selection.get(protein.id())?)
+        });
+        let Some((min, max)) = bounds else {
+            continue;
+        };
+
+        let mesh = Isosurface::new(
+            density_map.sub_grid(min, max),
+            max - min + UVec3::ONE,
+            density_map.position(min),
+            density_map.basis,
+            density_map.level(isosurface.sigma_level),
+        );
+
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn(PbrBundle {
+                mesh: meshes.add(mesh),
+                material: materials.add(StandardMaterial {
+                    base_color: isosurface.color,
+                    alpha_mode: if isosurface.color.a() < 1. {
+                        AlphaMode::Blend
+                    } else {
+                        AlphaMode::Opaque
+                    },
+                    double_sided: true,
+                    cull_mode: None,
+                    ..default()
+                }),
+                transform: Transform::from_translation(-offset),
+                ..default()
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples 1 Å apart from (-5, -5, -5).
+    fn density_map(dimensions: UVec3) -> DensityMap {
+        DensityMap {
+            dimensions,
+            origin: Vec3::splat(-5.),
+            basis: Mat3::IDENTITY,
+            values: vec![0.; (dimensions.x * dimensions.y * dimensions.z) as usize],
+            mean: 0.,
+            rms: 1.,
+        }
+    }
+
+    #[test]
+    fn whole_region_covers_the_map() {
+        let region = IsosurfaceRegion::Whole;
+        assert_eq!(
+            region.grid_box(&density_map(UVec3::splat(10)), Vec3::ONE, || None),
+            Some((UVec3::ZERO, UVec3::splat(9)))
+        );
+        assert_eq!(
+            region.grid_box(&density_map(UVec3::new(10, 0, 10)), Vec3::ZERO, || None),
+            None
+        );
+    }
+
+    #[test]
+    fn box_region_is_moved_to_the_map_frame() {
+        let region = IsosurfaceRegion::Box {
+            min: Vec3::ZERO,
+            max: Vec3::ONE,
+        };
+        let density_map = density_map(UVec3::splat(10));
+        assert_eq!(
+            region.grid_box(&density_map, Vec3::ZERO, || None),
+            Some((UVec3::splat(5), UVec3::splat(6)))
+        );
+        assert_eq!(
+            region.grid_box(&density_map, Vec3::new(-2., 0., 10.), || None),
+            None
+        );
+        assert_eq!(
+            region.grid_box(&density_map, Vec3::new(-2., 0., 3.5), || None),
+            Some((UVec3::new(3, 5, 8), UVec3::new(4, 6, 9)))
+        );
+    }
+
+    #[test]
+    fn selection_region_pads_the_selection() {
+        let region = IsosurfaceRegion::AroundSelection { padding: 1.5 };
+        let density_map = density_map(UVec3::splat(10));
+        assert_eq!(region.grid_box(&density_map, Vec3::ZERO, || None), None);
+        assert_eq!(
+            region.grid_box(&density_map, Vec3::X, || {
+                Some((Vec3::new(-1., 0., 0.), Vec3::new(0., 0., 4.)))
+            }),
+            Some((UVec3::splat(3), UVec3::new(8, 7, 9)))
+        );
+    }
+}
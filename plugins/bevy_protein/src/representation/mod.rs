@@ -0,0 +1,22 @@
+pub mod ball_and_stick;
+pub mod cartoon;
+pub mod isosurface;
+pub mod spheres;
+
+use bevy::math::Vec3;
+
+/// An atom together with the index (in [`pdbtbx::PDB::residues`]) of the residue it belongs to.
+pub type ResidueAtom<'a> = (usize, &'a pdbtbx::Atom);
+
+pub fn atom_position(atom: &pdbtbx::Atom) -> Vec3 {
+    let (x, y, z) = atom.pos();
+    Vec3::new(x as f32, y as f32, z as f32)
+}
+
+/// Element symbol of an atom, falling back to the first letter of its name when the element column is empty.
+pub fn atom_element(atom: &pdbtbx::Atom) -> &str {
+    match atom.element() {
+        Some(element) => element.symbol(),
+        None => atom.name().get(..1).unwrap_or_default(),
+    }
+}
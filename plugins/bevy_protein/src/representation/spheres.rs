@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use bevy_instanced::instance_data::{
+    attributes::InstanceAttributes,
+    culling::InstanceCulling,
+    highlight::{InstanceFlags, InstanceHighlight},
+    imposter::Imposter,
+    instanced::{Instance, InstancedMaterial, InstancesData},
+};
+
+use crate::atom::element::vdw_radius;
+use crate::protein_asset_loader::ProteinAsset;
+use crate::selection::{ProteinAtoms, HOVERED_COLOR, SELECTED_COLOR};
+
+use super::{atom_element, atom_position, ResidueAtom};
+
+/// The material of instanced atoms and bonds: white, so that the colour of each instance shows unaltered.
+pub fn instanced_material() -> StandardMaterial {
+    StandardMaterial {
+        base_color: Color::WHITE,
+        perceptual_roughness: 0.6,
+        ..default()
+    }
+}
+
+/// Spawns one instanced sphere per atom, sized by its van der Waals radius times `scale`
+/// (1.0 is space-filling). The spheres are ray-cast imposters, smooth at any distance.
+pub fn spawn_spheres(
+    parent: &mut ChildBuilder,
+    protein: AssetId<ProteinAsset>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    atoms: &[ResidueAtom],
+    scale: f32,
+    color: impl Fn(&ResidueAtom) -> Color,
+) -> Entity {
+    let instances = atoms
+        .iter()
+        .map(|residue_atom| {
+            let atom = residue_atom.1;
+            let diameter = 2. * scale * vdw_radius(atom_element(atom));
+            Instance::new(
+                atom_position(atom),
+                diameter,
+                color(residue_atom).as_rgba_f32(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let residue_indices = atoms
+        .iter()
+        .map(|(residue_index, _)| *residue_index)
+        .collect();
+
+    parent
+        .spawn((
+            meshes.add(Imposter::quad()),
+            Imposter::Sphere,
+            SpatialBundle::INHERITED_IDENTITY,
+            InstanceAttributes::<InstanceFlags>::flags(instances.len()),
+            InstancesData::new(instances),
+            InstancedMaterial(materials.add(instanced_material())),
+            // Hovered and selected atoms take the colours the panels highlight their residues with.
+            InstanceHighlight {
+                hovered_tint: HOVERED_COLOR,
+                selected_tint: SELECTED_COLOR,
+            },
+            ProteinAtoms::new(protein, residue_indices),
+            InstanceCulling::default(),
+        ))
+        .id()
+}
@@ -0,0 +1,284 @@
+use std::{collections::BTreeSet, ops::RangeInclusive};
+
+use bevy::{prelude::*, utils::HashMap, window::PrimaryWindow};
+use bevy_cameras::api::IgnoreInput;
+use bevy_instanced::instance_data::{
+    attributes::InstanceAttributes, highlight::InstanceFlags, imposter::ray_sphere,
+    instanced::InstancesData,
+};
+
+use crate::protein_asset_loader::ProteinAsset;
+
+pub const SELECTED_COLOR: Color = Color::YELLOW;
+pub const HOVERED_COLOR: Color = Color::ORANGE;
+
+/// The residues picked by the user in one structure.
+/// Residues are addressed by their position in [`pdbtbx::PDB::residues`].
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct StructureSelection {
+    pub selected: BTreeSet<usize>,
+    pub hovered: Option<usize>,
+}
+
+impl StructureSelection {
+    pub fn is_selected(&self, residue_index: usize) -> bool {
+        self.selected.contains(&residue_index)
+    }
+
+    pub fn select_only(&mut self, residue_index: usize) {
+        self.selected.clear();
+        self.selected.insert(residue_index);
+    }
+
+    pub fn select_range(&mut self, residue_indices: RangeInclusive<usize>) {
+        self.selected.clear();
+        self.selected.extend(residue_indices);
+    }
+
+    pub fn clear(&mut self) {
+        self.selected.clear();
+    }
+
+    /// Forgets residues that no longer exist, e.g. after the structure was reloaded with fewer residues.
+    pub fn retain_residues(&mut self, residue_count: usize) {
+        self.selected
+            .retain(|residue_index| *residue_index < residue_count);
+        if self
+            .hovered
+            .is_some_and(|residue_index| residue_index >= residue_count)
+        {
+            self.hovered = None;
+        }
+    }
+}
+
+/// The residues picked by the user in each structure, shared by every view of it (3D scene, plots, panels).
+/// At most one residue, of any structure, is hovered at a time.
+#[derive(Resource, Default, Debug)]
+pub struct ResidueSelection(HashMap<AssetId<ProteinAsset>, StructureSelection>);
+
+impl ResidueSelection {
+    pub fn get(&self, protein: AssetId<ProteinAsset>) -> Option<&StructureSelection> {
+        self.0.get(&protein)
+    }
+
+    /// The selection of `protein`, empty if nothing was picked in it yet.
+    pub fn get_mut(&mut self, protein: AssetId<ProteinAsset>) -> &mut StructureSelection {
+        self.0.entry(protein).or_default()
+    }
+
+    pub fn is_selected(&self, protein: AssetId<ProteinAsset>, residue_index: usize) -> bool {
+        self.get(protein)
+            .is_some_and(|selection| selection.is_selected(residue_index))
+    }
+
+    pub fn is_hovered(&self, protein: AssetId<ProteinAsset>, residue_index: usize) -> bool {
+        self.get(protein)
+            .is_some_and(|selection| selection.hovered == Some(residue_index))
+    }
+
+    /// The hovered residue and its structure.
+    pub fn hovered(&self) -> Option<(AssetId<ProteinAsset>, usize)> {
+        self.0.iter().find_map(|(protein, selection)| {
+            selection
+                .hovered
+                .map(|residue_index| (*protein, residue_index))
+        })
+    }
+
+    /// Hovers a residue of a structure, or none, and un-hovers the residue hovered before.
+    pub fn hover(&mut self, hovered: Option<(AssetId<ProteinAsset>, usize)>) {
+        for selection in self.0.values_mut() {
+            selection.hovered = None;
+        }
+        if let Some((protein, residue_index)) = hovered {
+            self.get_mut(protein).hovered = Some(residue_index);
+        }
+    }
+
+    /// Forgets what was picked in a structure, e.g. once it is unloaded.
+    pub fn remove(&mut self, protein: AssetId<ProteinAsset>) {
+        self.0.remove(&protein);
+    }
+}
+
+/// Maps every instance of an atom [`InstancesData`] back to the residue it belongs to.
+#[derive(Component, Debug)]
+pub struct ProteinAtoms {
+    pub protein: AssetId<ProteinAsset>,
+    pub residue_indices: Vec<usize>,
+}
+
+impl ProteinAtoms {
+    pub fn new(protein: AssetId<ProteinAsset>, residue_indices: Vec<usize>) -> Self {
+        Self {
+            protein,
+            residue_indices,
+        }
+    }
+
+    /// The instances of the atoms of the residues matching `residue`.
+    pub fn atoms_of<'a>(
+        &'a self,
+        residue: impl Fn(usize) -> bool + 'a,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.residue_indices
+            .iter()
+            .enumerate()
+            .filter(move |(_, &residue_index)| residue(residue_index))
+            .map(|(atom_index, _)| atom_index)
+    }
+}
+
+/// Flags the atoms of the selected and hovered residues, for the instancing shaders to tint and outline them.
+pub fn highlight_selected_residues(
+    selection: Res<ResidueSelection>,
+    mut query: Query<(&ProteinAtoms, &mut InstanceAttributes<InstanceFlags>)>,
+) {
+    for (atoms, mut flags) in query.iter_mut() {
+        let protein = atoms.protein;
+        flags.set_flags_only(
+            atoms.atoms_of(|residue_index| selection.is_selected(protein, residue_index)),
+            InstanceFlags::SELECTED,
+        );
+        flags.set_flags_only(
+            atoms.atoms_of(|residue_index| selection.is_hovered(protein, residue_index)),
+            InstanceFlags::HOVERED,
+        );
+    }
+}
+
+type PickableAtoms<'a> = (
+    &'a ProteinAtoms,
+    &'a InstancesData,
+    Option<&'a InstanceAttributes<InstanceFlags>>,
+    &'a GlobalTransform,
+    &'a InheritedVisibility,
+);
+
+/// Casts a ray from the cursor through the instanced atoms and marks the residue of the closest hit as hovered.
+/// Instances can't be picked individually by `bevy_mod_picking`, which only sees the single instanced entity.
+pub fn hover_atoms(
+    mut cursor_moved: EventReader<CursorMoved>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), Without<IgnoreInput>>,
+    atoms: Query<PickableAtoms>,
+    ui_nodes: Query<&Interaction>,
+    mut selection: ResMut<ResidueSelection>,
+) {
+    if cursor_moved.read().last().is_none() {
+        return;
+    }
+    // The cursor is over a panel, which is responsible for the hovered residue.
+    if ui_nodes
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let (Some(cursor), Some(physical_cursor)) =
+        (window.cursor_position(), window.physical_cursor_position())
+    else {
+        return;
+    };
+    // The ray goes through the camera drawn under the cursor, from the cursor's position within its viewport.
+    let Some(ray) = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .filter(|(camera, _)| {
+            camera
+                .physical_viewport_rect()
+                .is_some_and(|rect| rect.as_rect().contains(physical_cursor))
+        })
+        .max_by_key(|(camera, _)| camera.order)
+        .and_then(|(camera, transform)| {
+            let viewport = camera.logical_viewport_rect()?;
+            camera.viewport_to_world(transform, cursor - viewport.min)
+        })
+    else {
+        return;
+    };
+
+    let mut closest: Option<(f32, AssetId<ProteinAsset>, usize)> = None;
+    for (atoms, instances, flags, transform, visibility) in atoms.iter() {
+        if !visibility.get() {
+            continue;
+        }
+        let scale = transform.compute_transform().scale.max_element();
+        for (instance_index, (instance, &residue_index)) in
+            instances.iter().zip(&atoms.residue_indices).enumerate()
+        {
+            let hidden = flags
+                .and_then(|flags| flags.0.get(instance_index))
+                .is_some_and(|flags| flags.contains(InstanceFlags::HIDDEN));
+            if hidden {
+                continue;
+            }
+            // The atoms are sphere imposters of unit diameter.
+            let radius = 0.5 * instance.scale().max_element() * scale;
+            let centre = transform.transform_point(instance.position());
+            if let Some(t) =
+                ray_sphere(ray.origin, *ray.direction, centre, radius).map(|hit| hit.distance)
+            {
+                if !closest.is_some_and(|(closest_t, ..)| closest_t <= t) {
+                    closest = Some((t, atoms.protein, residue_index));
+                }
+            }
+        }
+    }
+
+    let hovered = closest.map(|(_, protein, residue_index)| (protein, residue_index));
+    if selection.hovered() != hovered {
+        selection.hover(hovered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::Uuid;
+
+    use super::*;
+
+    fn protein(n: u128) -> AssetId<ProteinAsset> {
+        AssetId::Uuid {
+            uuid: Uuid::from_u128(n),
+        }
+    }
+
+    #[test]
+    fn structures_have_their_own_selection() {
+        let mut selection = ResidueSelection::default();
+        selection.get_mut(protein(1)).select_range(2..=6);
+        selection.get_mut(protein(2)).select_only(5);
+        assert!(selection.is_selected(protein(1), 3));
+        assert!(!selection.is_selected(protein(2), 3));
+        assert!(!selection.is_selected(protein(3), 3));
+
+        // A reload with fewer residues only trims the selection of that structure.
+        selection.get_mut(protein(1)).retain_residues(4);
+        assert_eq!(
+            selection.get(protein(1)).unwrap().selected,
+            BTreeSet::from([2, 3])
+        );
+        assert!(selection.is_selected(protein(2), 5));
+
+        selection.remove(protein(2));
+        assert_eq!(selection.get(protein(2)), None);
+    }
+
+    #[test]
+    fn one_residue_is_hovered_at_a_time() {
+        let mut selection = ResidueSelection::default();
+        selection.hover(Some((protein(1), 4)));
+        selection.hover(Some((protein(2), 7)));
+        assert_eq!(selection.hovered(), Some((protein(2), 7)));
+        assert!(!selection.is_hovered(protein(1), 4));
+
+        selection.hover(None);
+        assert_eq!(selection.hovered(), None);
+    }
+}
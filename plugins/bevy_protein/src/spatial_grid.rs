@@ -0,0 +1,97 @@
+use bevy::{
+    math::{IVec3, Vec3},
+    utils::HashMap,
+};
+
+/// A uniform grid bucketing points by cell, used to answer "what is near this point" queries
+/// without comparing every pair of atoms.
+#[derive(Debug, Default)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    points: Vec<Vec3>,
+    cells: HashMap<IVec3, Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32, points: impl IntoIterator<Item = Vec3>) -> Self {
+        let points: Vec<Vec3> = points.into_iter().collect();
+        let mut cells = HashMap::<IVec3, Vec<usize>>::default();
+
+        for (i, point) in points.iter().enumerate() {
+            cells
+                .entry(Self::cell_of(cell_size, *point))
+                .or_default()
+                .push(i);
+        }
+
+        Self {
+            cell_size,
+            points,
+            cells,
+        }
+    }
+
+    fn cell_of(cell_size: f32, point: Vec3) -> IVec3 {
+        (point / cell_size).floor().as_ivec3()
+    }
+
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    /// Indices of every point within `radius` of `centre`, with their distance to it.
+    pub fn within(&self, centre: Vec3, radius: f32) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let min = Self::cell_of(self.cell_size, centre - Vec3::splat(radius));
+        let max = Self::cell_of(self.cell_size, centre + Vec3::splat(radius));
+
+        (min.x..=max.x)
+            .flat_map(move |x| {
+                (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+            })
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter_map(move |&i| {
+                let distance = self.points[i].distance(centre);
+                (distance <= radius).then_some((i, distance))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 7 × 7 × 7 lattice 1.3 Å apart, straddling the origin so that some cells have negative coordinates.
+    fn lattice() -> Vec<Vec3> {
+        (0..7 * 7 * 7)
+            .map(|i| Vec3::new((i % 7) as f32, (i / 7 % 7) as f32, (i / 49) as f32) * 1.3 - 4.)
+            .collect()
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        let points = lattice();
+        for cell_size in [0.5, 2., 10.] {
+            let grid = SpatialGrid::new(cell_size, points.iter().copied());
+            for (centre, radius) in [
+                (Vec3::ZERO, 2.),
+                (Vec3::new(-3.7, 1.1, 4.2), 3.5),
+                (Vec3::splat(20.), 1.),
+            ] {
+                let mut found: Vec<_> = grid.within(centre, radius).collect();
+                found.sort_by_key(|(i, _)| *i);
+                let expected: Vec<_> = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, point)| (i, point.distance(centre)))
+                    .filter(|(_, distance)| *distance <= radius)
+                    .collect();
+                assert_eq!(found, expected, "{cell_size} {centre} {radius}");
+            }
+        }
+        assert!(SpatialGrid::new(1., [])
+            .within(Vec3::ZERO, 5.)
+            .next()
+            .is_none());
+    }
+}